@@ -51,6 +51,21 @@ pub struct FuncAttr<'s> {
     pub no_mangle: bool,
     pub precedence: Option<u32>,
     pub extern_: Option<String>,
+    pub inline: Option<Inline>,
+    pub shared: SharedAttr<'s>,
+}
+
+/// Inlining hint given by `@[inline]`, `@[inline "always"]` or `@[noinline]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inline {
+    Hint,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Default)]
+pub struct ValAttr<'s> {
+    pub thread_local: bool,
     pub shared: SharedAttr<'s>,
 }
 
@@ -58,6 +73,7 @@ pub struct FuncAttr<'s> {
 pub struct TypeAttr<'s> {
     pub shared: SharedAttr<'s>,
     pub repr: Repr,
+    pub derive: Vec<Tr<Derive>>,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
@@ -70,13 +86,26 @@ pub enum Repr {
     Enum(IntSize),
 }
 
+/// Traits which the compiler can generate an implementation for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Derive {
+    Compare,
+    ToString,
+    Eq,
+    Clone,
+}
+
 impl<'s> TypeAttr<'s> {
     pub fn parse(
         module: key::Module,
         sources: &Sources,
         exprs: &[Tr<parser::Expr<'s>>],
     ) -> TypeAttr<'s> {
-        let mut this = TypeAttr { shared: SharedAttr::new(), repr: Repr::default() };
+        let mut this = TypeAttr {
+            shared: SharedAttr::new(),
+            repr: Repr::default(),
+            derive: vec![],
+        };
 
         for expr in exprs {
             if let Err(err) = this.parse_attr(expr.as_ref()) {
@@ -91,10 +120,30 @@ impl<'s> TypeAttr<'s> {
         let (entry, params) = path(expr, "attribute name")?;
         match entry.path.as_slice() {
             ["repr"] => self.parse_repr(expr.span, params),
+            ["derive"] => self.parse_derive(expr.span, params),
             _ => self.shared.parse_attr(expr.span, entry, params),
         }
     }
 
+    fn parse_derive(&mut self, span: Span, params: &[Tr<parser::Expr<'s>>]) -> Result<(), Error> {
+        if params.is_empty() {
+            return Err(Error::Expected(span, "one or more trait names to derive"));
+        }
+
+        for param in params {
+            let derive = match string(param.as_ref(), "trait name to derive")? {
+                "Compare" => Derive::Compare,
+                "ToString" => Derive::ToString,
+                "Eq" => Derive::Eq,
+                "Clone" => Derive::Clone,
+                str => return Err(Error::UnknownDerive(param.span, str.to_string())),
+            };
+            self.derive.push(derive.tr(param.span));
+        }
+
+        Ok(())
+    }
+
     fn parse_repr(&mut self, span: Span, params: &[Tr<parser::Expr<'s>>]) -> Result<(), Error> {
         if params.is_empty() {
             return Err(Error::Expected(span, "argument for repr"));
@@ -106,7 +155,16 @@ impl<'s> TypeAttr<'s> {
                     params[0].span.move_indice(5),
                     "integer argument for `repr align`",
                 )),
-                "align" => num(params[1].as_ref()).map(|n| self.repr = Repr::Align(n as u8)),
+                "align" => match num(params[1].as_ref())? {
+                    n if n.is_power_of_two() && n <= 128 => {
+                        self.repr = Repr::Align(n as u8);
+                        Ok(())
+                    }
+                    _ => Err(Error::Expected(
+                        params[1].span,
+                        "power of two alignment no larger than 128",
+                    )),
+                },
                 "packed" => {
                     self.repr = Repr::Packed;
                     Ok(())
@@ -155,6 +213,9 @@ fn emit_err(module: key::Module, sources: &Sources, err: Error) {
         Error::Expected(span, exp) => base.eline(span, format!("expected {exp}")),
         Error::Unknown(span) => base.eline(span, format!("unknown attribute")),
         Error::UnknownRepr(span, repr) => base.eline(span, format!("unknown repr: {repr}")),
+        Error::UnknownDerive(span, name) => base
+            .eline(span, format!("can not derive {name}"))
+            .text("only `Compare`, `ToString`, `Eq` and `Clone` can be derived"),
     }
     .emit()
 }
@@ -170,6 +231,7 @@ impl<'s> FuncAttr<'s> {
             precedence: None,
             shared: SharedAttr::new(),
             extern_: None,
+            inline: None,
         };
 
         for expr in exprs {
@@ -197,6 +259,50 @@ impl<'s> FuncAttr<'s> {
                 self.extern_ = Some(name.to_string());
                 Ok(())
             }
+            ["inline"] => {
+                self.inline = match params {
+                    [] => Some(Inline::Hint),
+                    [param] => match string(param.as_ref(), "`\"always\"`")? {
+                        "always" => Some(Inline::Always),
+                        _ => return Err(Error::Expected(param.span, "`\"always\"`")),
+                    },
+                    _ => return Err(Error::Expected(expr.span, "at most one argument")),
+                };
+                Ok(())
+            }
+            ["noinline"] => {
+                self.inline = Some(Inline::Never);
+                Ok(())
+            }
+            _ => self.shared.parse_attr(expr.span, entry, params),
+        }
+    }
+}
+
+impl<'s> ValAttr<'s> {
+    pub fn parse(
+        module: key::Module,
+        sources: &Sources,
+        exprs: &[Tr<parser::Expr<'s>>],
+    ) -> ValAttr<'s> {
+        let mut this = ValAttr::default();
+
+        for expr in exprs {
+            if let Err(err) = this.parse_attr(expr.as_ref()) {
+                emit_err(module, sources, err);
+            }
+        }
+
+        this
+    }
+
+    fn parse_attr(&mut self, expr: Tr<&parser::Expr<'s>>) -> Result<(), Error> {
+        let (entry, params) = path(expr, "attribute name")?;
+        match entry.path.as_slice() {
+            ["thread_local"] => {
+                self.thread_local = true;
+                Ok(())
+            }
             _ => self.shared.parse_attr(expr.span, entry, params),
         }
     }
@@ -290,5 +396,6 @@ fn strings<'s>(exprs: &[Tr<parser::Expr<'s>>], exp: &'static str) -> Result<Vec<
 pub enum Error {
     Expected(Span, &'static str),
     UnknownRepr(Span, String),
+    UnknownDerive(Span, String),
     Unknown(Span),
 }
@@ -435,6 +435,7 @@ impl<'s> Collector<'s> {
             }
 
             self.include_tydef_members(module, visiblity, kind);
+            self.include_derives(module, kind);
 
             Some(kind)
         } else {
@@ -457,7 +458,7 @@ impl<'s> Collector<'s> {
             .collect()
     }
 
-    fn include_impl(
+    pub(super) fn include_impl(
         &mut self,
         module: key::Module,
         imp: parser::r#impl::Declaration<'s>,
@@ -489,10 +490,20 @@ impl<'s> Collector<'s> {
 
     fn include_val(&mut self, module: key::Module, val: val::Declaration<'s>) {
         let (name, span) = (val.name, val.span);
+        let mut attributes = attr::ValAttr::parse(module, &self.sources, &val.attributes);
+        attributes.shared.public |= val.public;
+
+        if !is_targetted(&attributes.shared, &self.target) {
+            return;
+        }
+
         let key = self.entities.vals[module].next_key();
-        let (header, body, attributes) = val_to_func(key, val);
-        let fkey = self.entities.fheaders.push(module, header);
+        if attributes.thread_local {
+            self.entities.thread_locals.insert(key.inside(module));
+        }
         let visibility = Visibility::from_public_flag(module, attributes.shared.public);
+        let (header, body, attributes) = val_to_func(key, val, attributes.shared);
+        let fkey = self.entities.fheaders.push(module, header);
         self.entities.fattributes.push_as(fkey, attributes);
         self.entities.fbodies.push_as(fkey, body);
         self.entities.vals.push_as(key.inside(module), fkey);
@@ -602,6 +613,10 @@ impl<'s> Collector<'s> {
                 span,
                 format!("where bindings can not be prefixed by {}", token.describe()),
             ),
+            ParseError::InvalidEscape(span) => error
+                .eline(span, "invalid escape sequence")
+                .text("valid escapes are \\n \\r \\t \\0 \\\\ \\\" \\' \\{ \\xNN and \\u{NNNN}"),
+            ParseError::IntOverflow(span) => error.eline(span, "integer literal is too large"),
         }
         .emit()
     }
@@ -920,6 +935,7 @@ fn is_op(str: &str) -> bool {
 fn val_to_func<'s>(
     key: key::Val,
     val: val::Declaration<'s>,
+    shared: ast::SharedAttr<'s>,
 ) -> (func::Header<'s>, FuncBody<'s>, ast::FuncAttr<'s>) {
     (
         func::Header {
@@ -936,8 +952,9 @@ fn val_to_func<'s>(
         ast::FuncAttr {
             precedence: None,
             no_mangle: false,
-            shared: ast::SharedAttr { public: val.public, ..ast::SharedAttr::new() },
+            shared,
             extern_: None,
+            inline: None,
         },
     )
 }
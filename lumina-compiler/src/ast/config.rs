@@ -1,3 +1,4 @@
+use crate::{Pass, Profile};
 use lumina_parser as parser;
 use lumina_parser::{Declaration, Parser, Type};
 use lumina_util::{Span, Tr};
@@ -15,6 +16,7 @@ pub struct ProjectConfig {
     pub dependencies: Vec<Dependency>,
     pub linker_args: Vec<String>,
     pub linker_libs: Vec<String>,
+    pub profile: Profile,
 }
 
 #[derive(Debug)]
@@ -77,6 +79,7 @@ impl ProjectConfig {
             "linker_libs" => self
                 .parse_str_list(val.value)
                 .map(|args| self.linker_libs.extend(args)),
+            "profile" => self.parse_profile(val.value),
             _ => Err(Error::InvalidVal(val.span)),
         }
     }
@@ -132,6 +135,40 @@ impl ProjectConfig {
             _ => todo!(),
         }
     }
+
+    fn parse_profile(&mut self, expr: Tr<parser::Expr>) -> Result<(), Error> {
+        let parser::Expr::Record { fields, .. } = expr.value else {
+            return Err(Error::Expected(expr.span, "record"));
+        };
+
+        fields.into_iter().try_for_each(|field| match field {
+            parser::Field::Assigned { field_path, bind: None, value: v } => {
+                match *field_path.as_slice() {
+                    [Tr { value: "opt_level", .. }] => {
+                        let span = v.span;
+                        self.profile.opt_level = name(v)?
+                            .parse()
+                            .map_err(|err| Error::InvalidProfile(span, err))?;
+                        Ok(())
+                    }
+                    [Tr { value: "inline_threshold", .. }] => {
+                        self.profile.inline_threshold = Some(int(v)?);
+                        Ok(())
+                    }
+                    [Tr { value, span }] => {
+                        let pass: Pass = value
+                            .parse()
+                            .map_err(|err| Error::InvalidProfile(span, err))?;
+                        self.profile.passes.insert(pass, bool(v)?);
+                        Ok(())
+                    }
+                    [_, Tr { span, .. }, ..] => Err(Error::InvalidProfile(span, "unknown field")),
+                    [] => Err(Error::InvalidProfile(expr.span, "unknown field")),
+                }
+            }
+            _ => Err(Error::InvalidProfile(expr.span, "expected `field = value`")),
+        })
+    }
 }
 
 fn ty_in_str_literal(expr: Tr<parser::Expr>) -> Result<parser::Type<'static>, Error> {
@@ -154,6 +191,15 @@ fn name(expr: Tr<parser::Expr>) -> Result<String, Error> {
     }
 }
 
+fn int(expr: Tr<parser::Expr>) -> Result<u32, Error> {
+    match expr.value {
+        parser::Expr::Lit(parser::Literal::Int(false, n)) => {
+            u32::try_from(n).map_err(|_| Error::Expected(expr.span, "smaller integer"))
+        }
+        _ => Err(Error::Expected(expr.span, "integer")),
+    }
+}
+
 fn bool(expr: Tr<parser::Expr>) -> Result<bool, Error> {
     match expr.value {
         parser::Expr::Call(path, ..) => match path.path.as_slice()[0] {
@@ -174,4 +220,5 @@ pub enum Error {
     InvalidTy(Span),
     Expected(Span, &'static str),
     InvalidTypeInStr(Span),
+    InvalidProfile(Span, &'static str),
 }
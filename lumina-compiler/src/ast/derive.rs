@@ -0,0 +1,394 @@
+//! Compiler-generated trait implementations for `@[derive ...]`
+//!
+//! The implementations are constructed as parser declarations, structurally over the fields or
+//! variants of the type, and then included as if they were written by hand in the same module.
+//!
+//! Spans of the generated expressions point to the field or variant they operate on, so that a
+//! member type lacking the required implementation is reported there.
+
+use super::attr::Derive;
+use super::collect::Collector;
+use super::sources::Sources;
+use crate::prelude::*;
+use lumina_parser as parser;
+use lumina_parser::{
+    func, r#impl, ty, when, AnnotatedPath, CurlyInit, Expr, Literal, Pattern, Type,
+};
+use lumina_util::{Identifier, Spanned, Tr};
+use tracing::info;
+
+const ORDERING_EQUAL: &[&str] = &["std", "math", "Ordering", "Equal"];
+const STRING_CONCAT: &[&str] = &["std", "string", "<++>"];
+
+impl Derive {
+    fn trait_path(self) -> &'static [&'static str] {
+        match self {
+            Derive::Compare => &["std", "math", "Compare"],
+            Derive::ToString => &["std", "string", "ToString"],
+            Derive::Eq => &["std", "math", "Eq"],
+            Derive::Clone => &["std", "clone", "Clone"],
+        }
+    }
+
+    fn method_path(self) -> &'static [&'static str] {
+        match self {
+            Derive::Compare => &["std", "math", "compare"],
+            Derive::ToString => &["std", "string", "show"],
+            Derive::Eq => &["std", "math", "eq"],
+            Derive::Clone => &["std", "clone", "clone"],
+        }
+    }
+
+    fn method_name(self) -> &'static str {
+        self.method_path().last().unwrap()
+    }
+}
+
+// The members of the type being derived for
+enum Members<'s> {
+    Fields(Vec<(Tr<&'s str>, Span)>),
+    Variants(Vec<(Tr<&'s str>, usize)>),
+}
+
+impl<'s> Collector<'s> {
+    pub(super) fn include_derives(&mut self, module: key::Module, kind: key::TypeKind) {
+        let header = self.entities.header_of_ty(M(module, kind));
+        let derives = header.attributes.derive.clone();
+        if derives.is_empty() {
+            return;
+        }
+
+        let ty::Header { name, ref type_params, .. } = header.header;
+        let type_params = type_params.values().copied().collect::<Vec<_>>();
+
+        let members = match kind {
+            key::TypeKind::Record(record) => Members::Fields(
+                self.entities.field_names[record.inside(module)]
+                    .values()
+                    .zip(self.entities.field_types[record.inside(module)].values())
+                    .map(|(name, ty)| (*name, ty.span))
+                    .collect(),
+            ),
+            key::TypeKind::Sum(sum) => Members::Variants(
+                self.entities.variant_names[sum.inside(module)]
+                    .values()
+                    .zip(self.entities.variant_types[sum.inside(module)].values())
+                    .map(|(name, params)| (*name, params.len()))
+                    .collect(),
+            ),
+            key::TypeKind::Trait(_) => {
+                for derive in derives {
+                    self.sources
+                        .error("invalid attribute")
+                        .m(module)
+                        .eline(derive.span, "traits can not derive implementations")
+                        .emit();
+                }
+                return;
+            }
+        };
+
+        for derive in derives {
+            let span = derive.span;
+
+            if let Some((lib, _)) = self.lookups.lib_should_be_included(derive.trait_path()) {
+                info!("implicitly including standard library {lib} for derive");
+                self.parse_lib(span, module, "std", lib);
+            }
+
+            let mut gen = Generate { derive: *derive, ty: name, span, sources: &mut self.sources };
+
+            let trait_ = Type::Defined(path(derive.trait_path()), vec![]).tr(span);
+            let impltor = Type::Defined(
+                path(&[name]),
+                type_params
+                    .iter()
+                    .map(|&param| Type::name(param, vec![]).tr(span))
+                    .collect(),
+            )
+            .tr(span);
+
+            // Every type parameter needs to implement the trait for the members to do so
+            let when = when::Constraints {
+                generics: type_params
+                    .iter()
+                    .map(|&param| (span, param, vec![trait_.clone()]))
+                    .collect(),
+            };
+
+            let (params, expr) = match &members {
+                Members::Fields(fields) => gen.record(fields),
+                Members::Variants(variants) => gen.sum(variants),
+            };
+
+            let method = func::Declaration {
+                header: func::Header {
+                    when: when::Constraints::empty(),
+                    typing: None,
+                    params: params
+                        .iter()
+                        .map(|&param| {
+                            Pattern::Name(Identifier::parse(param).unwrap(), vec![]).tr(span)
+                        })
+                        .collect(),
+                    name: derive.method_name().tr(span),
+                },
+                body: Some(func::Body { expr, where_binds: vec![] }),
+                attributes: vec![],
+            };
+
+            let imp = r#impl::Declaration {
+                header: r#impl::Header { trait_, impltor, default: false, span, when },
+                attributes: vec![],
+                methods: [method].into_iter().collect(),
+                associations: Map::new(),
+            };
+
+            self.include_impl(module, imp);
+        }
+    }
+}
+
+struct Generate<'a, 's> {
+    derive: Derive,
+    ty: &'s str,
+    span: Span,
+    sources: &'a mut Sources,
+}
+
+impl<'a, 's> Generate<'a, 's> {
+    fn record(&mut self, fields: &[(Tr<&'s str>, Span)]) -> (Vec<&'s str>, Tr<Expr<'s>>) {
+        let access = |bind: &'s str, name: Tr<&'s str>, span: Span| {
+            Expr::FieldAccess(Box::new(var(bind, span)), name).tr(span)
+        };
+
+        match self.derive {
+            Derive::Compare => {
+                let expr = fields.iter().rev().fold(
+                    call(ORDERING_EQUAL, vec![], self.span),
+                    |acc, &(name, span)| {
+                        let ord = self
+                            .method([access("lhs", name, span), access("rhs", name, span)], span);
+                        then_compare(ord, acc, span)
+                    },
+                );
+                (vec!["lhs", "rhs"], expr)
+            }
+            Derive::Eq => {
+                let expr =
+                    fields
+                        .iter()
+                        .rev()
+                        .fold(var("true", self.span), |acc, &(name, span)| {
+                            let eq = self.method(
+                                [access("lhs", name, span), access("rhs", name, span)],
+                                span,
+                            );
+                            if_else(eq, acc, var("false", span), span)
+                        });
+                (vec!["lhs", "rhs"], expr)
+            }
+            Derive::ToString if fields.is_empty() => (vec!["_"], string(self.ty, self.span)),
+            Derive::ToString => {
+                let init = string(self.intern(format!("{{ {} | ", self.ty)), self.span);
+                let expr = fields
+                    .iter()
+                    .enumerate()
+                    .fold(init, |acc, (i, &(name, span))| {
+                        let sep = if i == 0 { "" } else { ", " };
+                        let label = string(self.intern(format!("{sep}{} = ", *name)), span);
+                        let shown = self.method([access("v", name, span)], span);
+                        concat(concat(acc, label, span), shown, span)
+                    });
+                (vec!["v"], concat(expr, string(" }", self.span), self.span))
+            }
+            Derive::Clone => {
+                let fields = fields
+                    .iter()
+                    .map(|&(name, span)| parser::Field::Assigned {
+                        field_path: vec![name],
+                        bind: None,
+                        value: self.method([access("v", name, span)], span),
+                    })
+                    .collect();
+
+                let init = CurlyInit::Construct(Type::name(self.ty, vec![]).tr(self.span));
+                let expr = Expr::Record { init: Box::new(init), fields }.tr(self.span);
+                (vec!["v"], expr)
+            }
+        }
+    }
+
+    fn sum(&mut self, variants: &[(Tr<&'s str>, usize)]) -> (Vec<&'s str>, Tr<Expr<'s>>) {
+        let ty = self.ty;
+        let variant = |name: Tr<&'s str>| [ty, *name];
+
+        match self.derive {
+            Derive::Compare | Derive::Eq => {
+                let mut branches = variants
+                    .iter()
+                    .map(|&(name, count)| {
+                        let span = name.span;
+                        let [lhs, rhs] = [self.binds("a", count), self.binds("b", count)];
+
+                        let pat = Pattern::Tuple(vec![
+                            constructor_pattern(&variant(name), &lhs, span),
+                            constructor_pattern(&variant(name), &rhs, span),
+                        ])
+                        .tr(span);
+
+                        let comparisons = lhs
+                            .iter()
+                            .zip(&rhs)
+                            .map(|(&l, &r)| self.method([var(l, span), var(r, span)], span))
+                            .collect::<Vec<_>>();
+
+                        let expr = match self.derive {
+                            Derive::Compare => comparisons
+                                .into_iter()
+                                .rev()
+                                .fold(call(ORDERING_EQUAL, vec![], span), |acc, ord| {
+                                    then_compare(ord, acc, span)
+                                }),
+                            _ => comparisons
+                                .into_iter()
+                                .rev()
+                                .fold(var("true", span), |acc, eq| {
+                                    if_else(eq, acc, var("false", span), span)
+                                }),
+                        };
+
+                        (pat, expr)
+                    })
+                    .collect::<Vec<_>>();
+
+                // Different variants are ordered by their declaration order
+                if variants.len() > 1 {
+                    let span = self.span;
+                    let otherwise = match self.derive {
+                        Derive::Compare => {
+                            let variant_of = |bind| {
+                                call(&["builtin", "variant_of"], vec![var(bind, span)], span)
+                            };
+                            self.method([variant_of("lhs"), variant_of("rhs")], span)
+                        }
+                        _ => var("false", span),
+                    };
+                    branches.push((wildcard(span), otherwise));
+                }
+
+                let on = Expr::Tuple(vec![var("lhs", self.span), var("rhs", self.span)]);
+                let expr = Expr::Match(Box::new(on.tr(self.span)), branches).tr(self.span);
+
+                (vec!["lhs", "rhs"], expr)
+            }
+            Derive::ToString => {
+                let branches = variants
+                    .iter()
+                    .map(|&(name, count)| {
+                        let span = name.span;
+                        let params = self.binds("a", count);
+                        let pat = constructor_pattern(&variant(name), &params, span);
+
+                        let expr = params.iter().fold(string(*name, span), |acc, &param| {
+                            let shown = self.method([var(param, span)], span);
+                            concat(concat(acc, string(" ", span), span), shown, span)
+                        });
+
+                        (pat, expr)
+                    })
+                    .collect();
+
+                let expr = Expr::Match(Box::new(var("v", self.span)), branches).tr(self.span);
+                (vec!["v"], expr)
+            }
+            Derive::Clone => {
+                let branches = variants
+                    .iter()
+                    .map(|&(name, count)| {
+                        let span = name.span;
+                        let params = self.binds("a", count);
+                        let pat = constructor_pattern(&variant(name), &params, span);
+
+                        let cloned = params
+                            .iter()
+                            .map(|&param| self.method([var(param, span)], span))
+                            .collect();
+                        let expr = call(&variant(name), cloned, span);
+
+                        (pat, expr)
+                    })
+                    .collect();
+
+                let expr = Expr::Match(Box::new(var("v", self.span)), branches).tr(self.span);
+                (vec!["v"], expr)
+            }
+        }
+    }
+
+    fn binds(&mut self, prefix: &str, count: usize) -> Vec<&'s str> {
+        (0..count)
+            .map(|i| self.intern(format!("{prefix}{i}")))
+            .collect()
+    }
+
+    fn intern(&mut self, str: String) -> &'s str {
+        self.sources.intern(str)
+    }
+
+    fn method<const N: usize>(&self, params: [Tr<Expr<'s>>; N], span: Span) -> Tr<Expr<'s>> {
+        call(self.derive.method_path(), params.into(), span)
+    }
+}
+
+// `match ord | Equal -> otherwise | ord -> ord`
+fn then_compare<'s>(ord: Tr<Expr<'s>>, otherwise: Tr<Expr<'s>>, span: Span) -> Tr<Expr<'s>> {
+    let equal = Pattern::Name(Identifier::from_segments(ORDERING_EQUAL), vec![]).tr(span);
+    let other = Pattern::Name(Identifier::parse("ord").unwrap(), vec![]).tr(span);
+    Expr::Match(
+        Box::new(ord),
+        vec![(equal, otherwise), (other, var("ord", span))],
+    )
+    .tr(span)
+}
+
+fn if_else<'s>(
+    cond: Tr<Expr<'s>>,
+    then: Tr<Expr<'s>>,
+    else_: Tr<Expr<'s>>,
+    span: Span,
+) -> Tr<Expr<'s>> {
+    Expr::If(Box::new([cond, then, else_])).tr(span)
+}
+
+fn concat<'s>(lhs: Tr<Expr<'s>>, rhs: Tr<Expr<'s>>, span: Span) -> Tr<Expr<'s>> {
+    call(STRING_CONCAT, vec![lhs, rhs], span)
+}
+
+fn string<'s>(str: &'s str, span: Span) -> Tr<Expr<'s>> {
+    Expr::Lit(Literal::String(str)).tr(span)
+}
+
+fn var<'s>(name: &'s str, span: Span) -> Tr<Expr<'s>> {
+    call(&[name], vec![], span)
+}
+
+fn call<'s>(segments: &[&'s str], params: Vec<Tr<Expr<'s>>>, span: Span) -> Tr<Expr<'s>> {
+    Expr::Call(path(segments).tr(span), params).tr(span)
+}
+
+fn path<'s>(segments: &[&'s str]) -> AnnotatedPath<'s> {
+    AnnotatedPath::without(Identifier::from_segments(segments))
+}
+
+fn constructor_pattern<'s>(variant: &[&'s str], binds: &[&'s str], span: Span) -> Tr<Pattern<'s>> {
+    let params = binds
+        .iter()
+        .map(|&bind| Pattern::Name(Identifier::parse(bind).unwrap(), vec![]).tr(span))
+        .collect();
+    Pattern::Name(Identifier::from_segments(variant), params).tr(span)
+}
+
+fn wildcard<'s>(span: Span) -> Tr<Pattern<'s>> {
+    Pattern::Name(Identifier::parse("_").unwrap(), vec![]).tr(span)
+}
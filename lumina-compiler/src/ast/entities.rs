@@ -9,6 +9,7 @@ pub struct Entities<'s> {
     pub fbodies: MMap<key::Func, FuncBody<'s>>,
     pub fattributes: MMap<key::Func, FuncAttr<'s>>,
     pub vals: MMap<key::Val, M<key::Func>>,
+    pub thread_locals: HashSet<M<key::Val>>,
 
     pub field_types: MMap<key::Record, Map<key::Field, Tr<Type<'s>>>>,
     pub field_names: MMap<key::Record, Map<key::Field, Tr<&'s str>>>,
@@ -17,13 +17,15 @@ pub use entities::{Entities, FuncBody};
 
 mod collect;
 use collect::Collector;
+
+mod derive;
 pub use collect::Error as CollectError;
 
 mod config;
 pub use config::{Error as ConfigError, ProjectConfig};
 
 pub mod attr;
-pub use attr::{FuncAttr, ImplAttr, ModuleAttr, SharedAttr, TypeAttr};
+pub use attr::{Derive, FuncAttr, ImplAttr, Inline, ModuleAttr, SharedAttr, TypeAttr, ValAttr};
 
 pub struct AST<'s> {
     pub entities: Entities<'s>,
@@ -106,6 +106,20 @@ impl<'s> Lookups<'s> {
         }
     }
 
+    /// The root module of the project or library which the module is a part of
+    pub fn library_of(&self, module: key::Module) -> key::Module {
+        match self.modules[module].kind {
+            ModuleKind::Root { parent: Some(parent), .. } => self.library_of(parent),
+            ModuleKind::Root { parent: None, .. } => module,
+            ModuleKind::Member { root } => self.library_of(root),
+        }
+    }
+
+    pub fn is_ext_library(&self, module: key::Module) -> bool {
+        let lib = self.library_of(module);
+        self.libs["ext"].values().any(|&m| m == lib)
+    }
+
     pub fn new_member_module(&mut self, root: key::Module) -> key::Module {
         let mut namespaces = Namespaces::default();
         namespaces.kind = ModuleKind::Member { root };
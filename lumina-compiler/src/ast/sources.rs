@@ -1,11 +1,13 @@
 use crate::prelude::*;
 use lumina_util::LineMode;
 use std::cell::Cell;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
 pub struct Sources {
     strings: Map<key::Module, Box<str>>,
     paths: Map<key::Module, PathBuf>,
+    generated: HashSet<Box<str>>,
     panicy: bool,
     has_failed: Cell<bool>,
 }
@@ -16,6 +18,7 @@ impl Sources {
         Self {
             strings: Map::new(),
             paths: Map::new(),
+            generated: HashSet::new(),
             panicy: true,
             has_failed: Cell::new(false),
         }
@@ -54,6 +57,21 @@ impl Sources {
         self.get(module)
     }
 
+    /// Store a string generated by the compiler so that it lives as long as the sources
+    pub fn intern<'s>(&mut self, str: String) -> &'s str {
+        let ptr = match self.generated.get(str.as_str()) {
+            Some(existing) => existing.as_ref() as *const str,
+            None => {
+                let boxed = str.into_boxed_str();
+                let ptr = boxed.as_ref() as *const str;
+                self.generated.insert(boxed);
+                ptr
+            }
+        };
+
+        unsafe { &*ptr }
+    }
+
     pub fn get_span<'s>(&self, module: key::Module, span: Span) -> &'s str {
         let src = self.get(module);
         span.get_str(src)
@@ -0,0 +1,139 @@
+use super::layout::{StructField, Structs};
+use crate::lir::{self, Constant, MonoType, MonoTypeData};
+use crate::prelude::*;
+
+/// A compile-time evaluated val lowered to the bytes of its static data
+pub struct Serialised {
+    pub bytes: Vec<u8>,
+    pub relocs: Vec<(u32, Reloc)>,
+}
+
+pub enum Reloc {
+    Func(lir::MonoFunc),
+    Extern(M<key::Func>),
+    ReadOnly(M<key::ReadOnly>),
+    Val(M<key::Val>),
+}
+
+impl<'a> Structs<'a> {
+    /// Serialise a constant into its in-memory representation
+    ///
+    /// Returns `None` for layouts which can't be known statically, such as payloads which are
+    /// heap allocated at runtime.
+    pub fn serialise(&self, ty: &MonoType, constant: &Constant) -> Option<Serialised> {
+        let size = self.size_of(ty) as usize;
+        let mut out = Serialised { bytes: vec![0; size], relocs: vec![] };
+        self.write_constant(&mut out, 0, ty, constant)?;
+        Some(out)
+    }
+
+    fn write_constant(
+        &self,
+        out: &mut Serialised,
+        offset: u32,
+        ty: &MonoType,
+        constant: &Constant,
+    ) -> Option<()> {
+        match (ty, constant) {
+            (_, _) if self.size_of(ty) == 0 => Some(()),
+            (MonoType::Int(intsize), Constant::Int(n, _)) => {
+                let bytes = n.to_le_bytes();
+                let size = intsize.bytes() as usize;
+                out.bytes[offset as usize..offset as usize + size].copy_from_slice(&bytes[..size]);
+                Some(())
+            }
+            (MonoType::Float, Constant::Float(f)) => {
+                let at = offset as usize;
+                out.bytes[at..at + 8].copy_from_slice(&f.to_bits().to_le_bytes());
+                Some(())
+            }
+            (MonoType::Pointer(_) | MonoType::FnPointer(..), Constant::Int(n, _)) => {
+                let size = self.records.pointer_bits as usize / 8;
+                let at = offset as usize;
+                out.bytes[at..at + size].copy_from_slice(&n.to_le_bytes()[..size]);
+                Some(())
+            }
+            (MonoType::Pointer(_) | MonoType::FnPointer(..), constant) => {
+                let reloc = match constant {
+                    Constant::FuncPtr(mfunc) => Reloc::Func(*mfunc),
+                    Constant::ExternFuncPtr(key) => Reloc::Extern(*key),
+                    Constant::ReadOnly(ro) => Reloc::ReadOnly(*ro),
+                    Constant::ValPtr(val) => Reloc::Val(*val),
+                    _ => return None,
+                };
+                out.relocs.push((offset, reloc));
+                Some(())
+            }
+            (MonoType::Array(_, inner), Constant::Aggregate(elems)) => {
+                let (elem_size, align) = self.size_and_align_of(inner);
+                let stride = elem_size + (align - elem_size % align) % align;
+                elems.iter().enumerate().try_for_each(|(i, elem)| {
+                    self.write_constant(out, offset + stride * i as u32, inner, elem)
+                })
+            }
+            (&MonoType::Monomorphised(mk), Constant::Aggregate(values)) => {
+                let ftypes: Vec<MonoType> = match &self.records[mk] {
+                    MonoTypeData::Record { fields, .. } => fields.values().cloned().collect(),
+                    MonoTypeData::DynTraitObject { vtable, .. } => {
+                        vec![MonoType::u8_pointer(), vtable.clone()]
+                    }
+                    _ => return None,
+                };
+
+                if ftypes.len() != values.len() {
+                    return None;
+                }
+
+                ftypes
+                    .iter()
+                    .zip(values)
+                    .enumerate()
+                    .try_for_each(|(i, (fty, value))| {
+                        let field = self.get_real_field(mk, key::Field(i as u32));
+                        match &self.get(mk).fields[field] {
+                            StructField::Flat(_) => {
+                                let foffset = self.offset_of(mk, field).0;
+                                self.write_constant(out, offset + foffset, fty, value)
+                            }
+                            _ => None,
+                        }
+                    })
+            }
+            (&MonoType::Monomorphised(mk), Constant::Variant(var, params)) => {
+                let MonoTypeData::Sum { tag, variants, .. } = &self.records[mk] else {
+                    return None;
+                };
+
+                match self.get(mk).niche {
+                    // The null pointer is already written since the buffer is zeroed
+                    Some(niche) if niche.null == *var => return Some(()),
+                    Some(_) => {}
+                    None => {
+                        let tagfield = self.get_real_field(mk, key::Field(0));
+                        let tagoffset = offset + self.offset_of(mk, tagfield).0;
+                        let tagv = Constant::Int(var.0 as i128, *tag);
+                        self.write_constant(out, tagoffset, &MonoType::Int(*tag), &tagv)?;
+
+                        if self.get(mk).fields.len() == 1 {
+                            return Some(());
+                        }
+                    }
+                }
+
+                let payfield = self.get_real_field(mk, key::Field(1));
+                let poffset = offset + self.offset_of(mk, payfield).0;
+                let param_tuple = MonoType::Monomorphised(variants[*var]);
+
+                match &self.get(mk).fields[payfield] {
+                    // The inline payload has the same bytes as the parameter tuple in memory
+                    StructField::SumPayloadInline(_) => {
+                        let params = Constant::Aggregate(params.clone());
+                        self.write_constant(out, poffset, &param_tuple, &params)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
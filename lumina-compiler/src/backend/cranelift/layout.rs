@@ -31,6 +31,27 @@ pub struct Struct {
     // Therefore; we need to map the original fields to their new indice.
     pub field_map: Map<key::Field, Field>,
     pub fields: Map<Field, StructField>,
+
+    // Fields are laid out without padding and may thus be unaligned
+    #[new(default)]
+    pub packed: bool,
+
+    // Stored flat inside a packed struct, so fields may be unaligned despite the padding
+    #[new(default)]
+    pub unaligned: bool,
+
+    // Sums whose payload holds a pointer which can never be null use the null pointer for their
+    // empty variant instead of a tag.
+    #[new(default)]
+    pub niche: Option<Niche>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Niche {
+    /// The variant represented by the null pointer
+    pub null: key::Variant,
+    /// Byte offset of the never-null pointer within the payload
+    pub offset: u32,
 }
 
 #[derive(Clone, Debug)]
@@ -106,6 +127,7 @@ pub enum SpecialPointer {
     StackStruct(MonoTypeKey),
     HeapStruct(MonoTypeKey),
     StackArray(MonoType, u64),
+    HeapArray(MonoType, u64),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -139,15 +161,17 @@ impl Layout<Type> {
             ),
             Layout::SpecialPointer(kind, ptr) => {
                 let kind = match kind {
-                    SpecialPointer::HeapSumPayload { .. } | SpecialPointer::HeapStruct(_) => {
-                        kind.clone()
-                    }
+                    SpecialPointer::HeapSumPayload { .. }
+                    | SpecialPointer::HeapStruct(_)
+                    | SpecialPointer::HeapArray(..) => kind.clone(),
 
                     &SpecialPointer::StackSumPayload { sum } => {
                         SpecialPointer::HeapSumPayload { sum }
                     }
                     SpecialPointer::StackStruct(key) => SpecialPointer::HeapStruct(*key),
-                    SpecialPointer::StackArray(..) => unimplemented!("auto-boxed arrays"),
+                    SpecialPointer::StackArray(inner, len) => {
+                        SpecialPointer::HeapArray(inner.clone(), *len)
+                    }
                 };
                 Layout::SpecialPointer(kind, *ptr)
             }
@@ -197,7 +221,9 @@ impl<T: Copy> Layout<T> {
             Layout::ArrayFlat(_, elems) => elems.iter().any(Layout::has_stack_pointers),
             Layout::StructFlat(_, fields) => fields.values().any(Layout::has_stack_pointers),
             Layout::SpecialPointer(kind, _) => match kind {
-                SpecialPointer::HeapSumPayload { .. } | SpecialPointer::HeapStruct(_) => false,
+                SpecialPointer::HeapSumPayload { .. }
+                | SpecialPointer::HeapStruct(_)
+                | SpecialPointer::HeapArray(..) => false,
                 _ => true,
             },
             Layout::OutPointer(_, _) => false,
@@ -263,7 +289,7 @@ impl<'a> Structs<'a> {
         let mut this = Self {
             structs: records
                 .keys()
-                .map(|_| Struct { align: u32::MAX, field_map: Map::new(), fields: Map::new() })
+                .map(|_| Struct::new(u32::MAX, Map::new(), Map::new()))
                 .collect(),
             records,
             autobox_stack: RefCell::new(vec![]),
@@ -327,6 +353,7 @@ impl<'a> Structs<'a> {
                 }
                 _ => false,
             },
+            MonoType::Array(_, inner) => self.autobox_check(inner),
             _ => false,
         }
     }
@@ -383,65 +410,98 @@ impl<'a> Structs<'a> {
         }
     }
 
-    // Gets the largest scalar count and largest size among the sum variants
-    fn largest_sum_variant(&self, variants: &Map<key::Variant, MonoTypeKey>) -> (usize, u32) {
-        let mut scalars = 0;
-        let mut size = 0;
+    // Whether a sum type contains itself without indirection, in which case its payload can't be
+    // stored inline and has to be put behind a pointer.
+    fn sum_is_recursive(&self, sum: MonoTypeKey) -> bool {
+        let (_, _, variants) = self.records[sum].as_sum();
+        let mut visited = vec![];
+        variants
+            .values()
+            .any(|&param_tuple| self.contains_inline(sum, &param_tuple.into(), &mut visited))
+    }
 
-        for param_tuple in variants.values() {
-            let (s, ss) = self.variant_inline_size_of_struct(*param_tuple);
-            scalars = scalars.max(s);
-            size = size.max(ss);
+    fn contains_inline(
+        &self,
+        target: MonoTypeKey,
+        ty: &MonoType,
+        visited: &mut Vec<MonoTypeKey>,
+    ) -> bool {
+        match ty {
+            MonoType::Array(_, inner) => self.contains_inline(target, inner, visited),
+            &MonoType::Monomorphised(mk) if mk == target => true,
+            &MonoType::Monomorphised(mk) if visited.contains(&mk) => false,
+            &MonoType::Monomorphised(mk) => {
+                visited.push(mk);
+                match &self.records[mk] {
+                    lir::MonoTypeData::Record { fields, .. } => fields
+                        .values()
+                        .any(|fty| self.contains_inline(target, fty, visited)),
+                    lir::MonoTypeData::Sum { variants, .. } => variants.values().any(|&v| {
+                        self.contains_inline(target, &MonoType::Monomorphised(v), visited)
+                    }),
+                    _ => false,
+                }
+            }
+            _ => false,
         }
-
-        (scalars, size)
     }
 
-    fn variant_inline_size_of_struct(&self, key: MonoTypeKey) -> (usize, u32) {
-        let ptr = self.records.pointer_bits / 8;
+    // A sum with one empty variant and one variant holding a pointer which is never null can use
+    // the null pointer as its empty variant, removing the need for a tag.
+    //
+    // Raw pointers may be null through `ptr:null`, so only heap payloads, autoboxed fields and the
+    // vtables of trait objects qualify.
+    fn find_sum_niche(
+        &self,
+        variants: &Map<key::Variant, MonoTypeKey>,
+        inline: bool,
+    ) -> Option<Niche> {
+        if variants.len() != 2 {
+            return None;
+        }
 
-        match &self.records[key] {
-            lir::MonoTypeData::Record { key: _, fields, .. } => {
-                let mut scalars = 0;
-                let mut size = 0;
+        let is_empty = |v: key::Variant| self.records[variants[v]].as_record().is_empty();
 
-                for v in fields.values() {
-                    if self.should_autobox_field(key, v) {
-                        scalars += 1;
-                        size += self.records.pointer_bits / 8;
-                    } else {
-                        let (s, ss) = self.variant_inline_size(v);
-                        scalars += s;
-                        size += ss;
-                    }
-                }
+        let [a, b] = [key::Variant(0), key::Variant(1)];
+        let (null, set) = if is_empty(a) && !is_empty(b) {
+            (a, b)
+        } else if is_empty(b) && !is_empty(a) {
+            (b, a)
+        } else {
+            return None;
+        };
 
-                (scalars, size)
-            }
-            lir::MonoTypeData::Sum { tag, .. } => (2, tag.bytes() as u32 + ptr),
-            lir::MonoTypeData::DynTraitObject { .. } => (2, ptr * 2),
-            lir::MonoTypeData::Placeholder => unreachable!(),
-        }
-    }
+        // A payload behind a pointer is never null since it's always allocated
+        let offset = if inline {
+            self.never_null_offset(&variants[set].into())?
+        } else {
+            0
+        };
 
-    fn variant_inline_size(&self, ty: &MonoType) -> (usize, u32) {
-        let ptr = || self.records.pointer_bits / 8;
+        Some(Niche { null, offset })
+    }
 
+    // Byte offset of a pointer within `ty` which can never be null
+    fn never_null_offset(&self, ty: &MonoType) -> Option<u32> {
         match ty {
-            MonoType::Int(intsize) => (1, intsize.bytes() as u32),
-            MonoType::Pointer(_) | MonoType::FnPointer(_, _) => (1, ptr()),
-            MonoType::Float => (1, 8),
-            MonoType::Const(const_) => match const_ {
-                lumina_typesystem::ConstValue::Usize(_) => (1, self.records.pointer_bits / 8),
-                lumina_typesystem::ConstValue::Bool(_) => (1, 1),
-                lumina_typesystem::ConstValue::Char(_) => (1, 4),
+            MonoType::Monomorphised(mk) => match &self.records[*mk] {
+                lir::MonoTypeData::DynTraitObject { .. } => Some(self.offset_of(*mk, Field(1)).0),
+                lir::MonoTypeData::Record { .. } => {
+                    self.get(*mk).fields.iter().find_map(|(field, sfield)| {
+                        let offset = self.offset_of(*mk, field).0;
+                        match sfield {
+                            StructField::AutoBoxed(_) => Some(offset),
+                            StructField::Flat(ty) => {
+                                self.never_null_offset(ty).map(|inner| offset + inner)
+                            }
+                            _ => None,
+                        }
+                    })
+                }
+                _ => None,
             },
-            MonoType::Array(len, inner) => {
-                let (c, s) = self.variant_inline_size(inner);
-                (c * *len as usize, s * *len as u32)
-            }
-            MonoType::Monomorphised(inner) => self.variant_inline_size_of_struct(*inner),
-            MonoType::Unreachable => unreachable!(),
+            MonoType::Array(len, inner) if *len != 0 => self.never_null_offset(inner),
+            _ => None,
         }
     }
 
@@ -456,6 +516,16 @@ impl<'a> Structs<'a> {
             .unwrap()
     }
 
+    pub fn sum_payload_align(&self, sum: MonoTypeKey) -> u32 {
+        let (_, _, variants) = self.records[sum].as_sum();
+        variants
+            .values()
+            .map(|&param_tuple| self.size_and_align_of(&param_tuple.into()).1)
+            .max()
+            .unwrap()
+            .max(1)
+    }
+
     fn make(&mut self, key: MonoTypeKey) {
         if self.structs[key].is_lowered() {
             trace!("{key}: reusing existing");
@@ -480,26 +550,58 @@ impl<'a> Structs<'a> {
                 let tagfield = StructField::Flat(MonoType::Int(*tag));
                 let ptr = size_t;
 
-                let (scalarcount, largest) = self.largest_sum_variant(variants);
-
-                trace!("for sum {key}: scalarcount={scalarcount}");
-
-                self.structs[key] = if scalarcount == 0 {
-                    Struct::new(tag.bytes() as u32, [Field(0)].into(), [tagfield].into())
-                } else if scalarcount == 1 {
-                    assert!(largest <= ptr.bytes());
-                    let payload =
-                        StructField::SumPayloadInline(Type::int(largest as u16 * 8).unwrap());
-                    let fields = [payload, tagfield].into();
-                    Struct::new(ptr.bytes(), [1, 0].map(Field).into(), fields)
-                } else if scalarcount == 2 && largest <= ptr.bytes() {
-                    let size = Type::int(largest as u16 * 8).unwrap_or(ptr);
-                    let fields = [StructField::SumPayloadInline(size), tagfield].into();
-                    Struct::new(ptr.bytes(), [1, 0].map(Field).into(), fields)
+                // Payloads no larger than two pointers are stored inline as an integer, shared
+                // between all variants. Larger or recursive payloads are put behind a pointer.
+                let largest = if self.sum_is_recursive(key) {
+                    None
                 } else {
-                    let fields = [StructField::SumPayloadPointer { sum: key }, tagfield].into();
-                    Struct::new(ptr.bytes(), [1, 0].map(Field).into(), fields)
+                    let largest = variants
+                        .values()
+                        .map(|&param_tuple| {
+                            self.make(param_tuple);
+                            self.size_of(&param_tuple.into())
+                        })
+                        .max()
+                        .unwrap_or(0);
+                    Some(largest).filter(|&size| size <= ptr.bytes() * 2)
+                };
+
+                trace!("for sum {key}: largest inline payload={largest:?}");
+
+                let niche = match largest {
+                    Some(0) => None,
+                    _ => self.find_sum_niche(variants, largest.is_some()),
                 };
+
+                trace!("for sum {key}: niche={niche:?}");
+
+                self.structs[key] = match largest {
+                    Some(0) => {
+                        Struct::new(tag.bytes() as u32, [Field(0)].into(), [tagfield].into())
+                    }
+                    Some(largest) => {
+                        let bits = largest.next_power_of_two() as u16 * 8;
+                        let payload = StructField::SumPayloadInline(Type::int(bits).unwrap());
+                        let (_, palign) = self.size_and_align_of_field(&payload);
+                        if niche.is_some() {
+                            Struct::new(palign, [0, 0].map(Field).into(), [payload].into())
+                        } else {
+                            let align = palign.max(tag.bytes() as u32);
+                            let fields = [payload, tagfield].into();
+                            Struct::new(align, [1, 0].map(Field).into(), fields)
+                        }
+                    }
+                    None => {
+                        let payload = StructField::SumPayloadPointer { sum: key };
+                        if niche.is_some() {
+                            Struct::new(ptr.bytes(), [0, 0].map(Field).into(), [payload].into())
+                        } else {
+                            let fields = [payload, tagfield].into();
+                            Struct::new(ptr.bytes(), [1, 0].map(Field).into(), fields)
+                        }
+                    }
+                };
+                self.structs[key].niche = niche;
             }
             lir::MonoTypeData::Record { repr, key: _, fields } => {
                 if fields.is_empty() {
@@ -510,7 +612,7 @@ impl<'a> Structs<'a> {
 
                 match repr {
                     ast::attr::Repr::Lumina => {
-                        let _align = self.calculate_align_of_struct(key);
+                        self.structs[key].align = self.calculate_align_of_struct(key);
                         let mut fieldorder: Vec<key::Field> = fields.keys().collect();
 
                         // Sort fields for better alignment
@@ -533,14 +635,30 @@ impl<'a> Structs<'a> {
                         assert!(!self.structs[key].fields.is_empty(), "{key}");
                     }
                     ast::attr::Repr::C => {
-                        let _align = self.calculate_align_of_struct(key);
+                        self.structs[key].align = self.calculate_align_of_struct(key);
+                        self.structs[key].field_map = fields.keys().map(|k| Field(k.0)).collect();
+
+                        let fieldorder = fields.keys();
+                        self.lower_struct_fields(key, fieldorder);
+                    }
+                    ast::attr::Repr::Packed => {
+                        self.structs[key].align = 1;
+                        self.structs[key].packed = true;
+                        self.structs[key].field_map = fields.keys().map(|k| Field(k.0)).collect();
+
+                        let fieldorder = fields.keys();
+                        self.lower_struct_fields(key, fieldorder);
+
+                        self.mark_unaligned_fields(key);
+                    }
+                    ast::attr::Repr::Align(bytes) => {
+                        let align = self.calculate_align_of_struct(key);
+                        self.structs[key].align = align.max(*bytes as u32);
                         self.structs[key].field_map = fields.keys().map(|k| Field(k.0)).collect();
 
                         let fieldorder = fields.keys();
                         self.lower_struct_fields(key, fieldorder);
                     }
-                    ast::attr::Repr::Packed => todo!(),
-                    ast::attr::Repr::Align(_) => todo!(),
                     ast::attr::Repr::Enum(_) => unreachable!(),
                 }
             }
@@ -549,11 +667,11 @@ impl<'a> Structs<'a> {
 
                 let data_field = StructField::Flat(MonoType::u8_pointer());
 
-                self.structs[key] = Struct {
+                self.structs[key] = Struct::new(
                     align,
-                    field_map: [0, 1].map(Field).into(),
-                    fields: [data_field, StructField::Flat(vtable.clone())].into(),
-                };
+                    [0, 1].map(Field).into(),
+                    [data_field, StructField::Flat(vtable.clone())].into(),
+                );
             }
             lir::MonoTypeData::Placeholder => unreachable!(),
         }
@@ -579,6 +697,15 @@ impl<'a> Structs<'a> {
         }
     }
 
+    /// Flags for loads and stores of fields in this struct
+    pub fn mem_flags(&self, key: MonoTypeKey) -> MemFlags {
+        if self.structs[key].packed || self.structs[key].unaligned {
+            MemFlags::new().with_notrap()
+        } else {
+            MemFlags::trusted()
+        }
+    }
+
     pub fn get_real_field(&self, key: MonoTypeKey, field: key::Field) -> Field {
         self.get(key).field_map[field]
     }
@@ -589,12 +716,12 @@ impl<'a> Structs<'a> {
         let mut offset = 0;
         for i in KeysIter::up_to(field) {
             let field = &struct_.fields[i];
-            let (fsize, pad) = self.field_size_and_pad(offset, field);
+            let (fsize, pad) = self.field_size_and_pad(struct_.packed, offset, field);
             offset += fsize + pad;
         }
 
         let (_, align) = self.size_and_align_of_field(&struct_.fields[field]);
-        if align != 0 {
+        if align != 0 && !struct_.packed {
             let end_padding = (align - offset % align) % align;
             offset += end_padding;
         }
@@ -636,17 +763,47 @@ impl<'a> Structs<'a> {
 
         trace!("{for_}: alignment calculated to {align}");
 
-        assert!(align <= self.records.pointer_bits / 8);
-        self.structs[for_].align = align;
+        assert!(align == 0 || align.is_power_of_two());
 
         align
     }
 
-    pub fn field_size_and_pad(&self, offset: u32, field: &StructField) -> (u32, u32) {
+    fn mark_unaligned_fields(&mut self, key: MonoTypeKey) {
+        let flat: Vec<MonoType> = self.structs[key]
+            .fields
+            .values()
+            .filter_map(|field| match field {
+                StructField::Flat(ty) => Some(ty.clone()),
+                _ => None,
+            })
+            .collect();
+
+        flat.iter().for_each(|ty| self.mark_unaligned(ty));
+    }
+
+    /// Marks structs stored flat at `ty` as possibly unaligned, since they're inside a packed struct
+    fn mark_unaligned(&mut self, ty: &MonoType) {
+        match ty {
+            MonoType::Monomorphised(key) => {
+                self.get_or_make(*key);
+                if self.structs[*key].unaligned {
+                    return;
+                }
+                self.structs[*key].unaligned = true;
+                self.mark_unaligned_fields(*key);
+            }
+            MonoType::Array(_, inner) => self.mark_unaligned(inner),
+            _ => {}
+        }
+    }
+
+    pub fn field_size_and_pad(&self, packed: bool, offset: u32, field: &StructField) -> (u32, u32) {
         let (size, align) = self.size_and_align_of_field(field);
         if align == 0 {
             assert_eq!(size, 0);
             (0, 0)
+        } else if packed {
+            (0, size)
         } else {
             let padding = (align - offset % align) % align;
             (padding, size)
@@ -660,9 +817,10 @@ impl<'a> Structs<'a> {
         }
 
         let mut offset = 0;
+        let packed = self.structs[mk].packed;
 
         for field in self.structs[mk].fields.values() {
-            let (fsize, pad) = self.field_size_and_pad(offset, field);
+            let (fsize, pad) = self.field_size_and_pad(packed, offset, field);
             offset += fsize + pad;
         }
 
@@ -677,14 +835,13 @@ impl<'a> Structs<'a> {
             &SpecialPointer::HeapSumPayload { sum, .. }
             | &SpecialPointer::StackSumPayload { sum, .. } => {
                 let size = self.sum_payload_alloca_size(sum);
-                // TODO: this align seems weird. Not sure whether it's valid
-                let align = (self.records.pointer_bits / 8).min(size);
+                let align = self.sum_payload_align(sum);
                 (size, align)
             }
             &SpecialPointer::HeapStruct(mk) | &SpecialPointer::StackStruct(mk) => {
                 self.size_and_align_of_mk(mk)
             }
-            SpecialPointer::StackArray(inner, n) => {
+            SpecialPointer::StackArray(inner, n) | SpecialPointer::HeapArray(inner, n) => {
                 let (size, _, align) = self.size_and_align_of_array(inner, *n);
                 (size, align)
             }
@@ -708,6 +865,7 @@ impl<'a> Structs<'a> {
                 (size, size)
             }
             MonoType::Unreachable => (0, 0),
+            MonoType::Constructor(..) => unreachable!("type constructors are never instantiated"),
         }
     }
 
@@ -742,7 +900,7 @@ impl<'a> Structs<'a> {
         match key {
             Some(key) => {
                 let struct_ = self.get(key);
-                struct_.align == 0 && !struct_.fields.is_empty()
+                (struct_.align == 0 || struct_.packed) && !struct_.fields.is_empty()
             }
             None => false,
         }
@@ -755,7 +913,10 @@ impl<'a> Structs<'a> {
     pub fn size_and_align_of_field(&self, f: &StructField) -> (u32, u32) {
         match f {
             StructField::Flat(ty) => self.size_and_align_of(ty),
-            StructField::SumPayloadInline(clty) => (clty.bytes(), clty.bytes()),
+            StructField::SumPayloadInline(clty) => {
+                let ptr = self.records.pointer_bits / 8;
+                (clty.bytes(), clty.bytes().min(ptr))
+            }
             StructField::AutoBoxed(_) | StructField::SumPayloadPointer { .. } => {
                 let ptr = self.records.pointer_bits / 8;
                 (ptr, ptr)
@@ -1054,6 +1215,7 @@ impl<'a> Structs<'a> {
                 }
             }
             MonoType::Unreachable => todo!("unreachable type"),
+            MonoType::Constructor(..) => unreachable!("type constructors are never instantiated"),
         }
     }
 
@@ -1122,6 +1284,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn repr_packed() {
+        let mut records = lir::MonomorphisedTypes::new(
+            M(key::Module(0), key::Trait::from(0)),
+            64,
+            ast::attr::Repr::Packed,
+        );
+
+        let int = |bits| MonoType::Int(IntSize::new(false, bits));
+
+        let record = records.get_or_make_tuple(vec![int(8), int(32), int(16)]);
+
+        let mut structs = Structs::new(&records.types);
+        let struct_ = structs.get_or_make(record);
+        assert_eq!(struct_.align, 1);
+        assert!(struct_.packed);
+
+        assert_eq!(structs.size_of(&record.into()), 1 + 4 + 2);
+
+        let u16_ = structs.get_real_field(record, key::Field(2));
+        assert_eq!(structs.offset_of(record, u16_), ByteOffset(1 + 4));
+        assert_eq!(structs.pass_mode(record), PassBy::Pointer);
+    }
+
+    #[test]
+    fn repr_align() {
+        let mut records = lir::MonomorphisedTypes::new(
+            M(key::Module(0), key::Trait::from(0)),
+            64,
+            ast::attr::Repr::Align(16),
+        );
+
+        let int = |bits| MonoType::Int(IntSize::new(false, bits));
+
+        let record = records.get_or_make_tuple(vec![int(8), int(32)]);
+
+        let mut structs = Structs::new(&records.types);
+        let struct_ = structs.get_or_make(record);
+        assert_eq!(struct_.align, 16);
+
+        assert_eq!(structs.size_of(&record.into()), 16);
+
+        let u32_ = structs.get_real_field(record, key::Field(1));
+        assert_eq!(structs.offset_of(record, u32_), ByteOffset(4));
+    }
+
     #[test]
     fn padding_at_end() {
         lumina_util::test_logger();
@@ -1180,4 +1388,36 @@ mod tests {
         assert_eq!(tuple_struct.align, 8);
         assert_eq!(structs.size_of(&tuple.into()), 8 * 4);
     }
+
+    #[test]
+    fn recursive_array() {
+        lumina_util::test_logger();
+
+        let m = key::Module(0);
+
+        let mut records =
+            lir::MonomorphisedTypes::new(M(m, key::Trait::from(0)), 64, ast::attr::Repr::Lumina);
+
+        let int = MonoType::Int(IntSize::new(true, 32));
+        let node = MonoTypeKey(1);
+        let noder = M(m, key::Record(0));
+        let children = MonoType::Array(2, Box::new(node.into()));
+
+        // type Node { value: i32, children: [Self; 2] }
+        assert_eq!(
+            node,
+            records.get_or_make_record(noder, vec![], [int, children].into())
+        );
+
+        let mut structs = Structs::new(&records.types);
+
+        let node_struct = structs.get_or_make(node).clone();
+        let children = structs.get_real_field(node, key::Field(1));
+        assert!(matches!(
+            node_struct.fields[children],
+            StructField::AutoBoxed(MonoType::Array(2, _))
+        ));
+        assert_eq!(node_struct.align, 8);
+        assert_eq!(structs.size_of(&node.into()), 16);
+    }
 }
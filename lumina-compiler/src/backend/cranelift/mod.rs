@@ -2,7 +2,7 @@ use crate::debuginfo::BinDebugInfo;
 use crate::lir;
 use crate::prelude::*;
 use crate::target::{Arch, LinuxPlatform, Platform};
-use crate::Target;
+use crate::{OptLevel, Target};
 use cranelift::codegen::ir;
 use cranelift::codegen::isa::CallConv;
 use cranelift::prelude::*;
@@ -13,9 +13,10 @@ use cranelift_object::{ObjectBuilder, ObjectModule};
 use std::sync::Arc;
 use tracing::info_span;
 
+mod constant;
 mod debuginfo;
 use debuginfo::unwind;
-mod layout;
+pub(crate) mod layout;
 mod ssa;
 
 use layout::FuncLayout;
@@ -32,11 +33,19 @@ impl Target {
     }
 }
 
-pub fn run(target: Target, dwarf: BinDebugInfo, lir: lir::Output) -> Vec<u8> {
+pub fn run(target: Target, opt_level: OptLevel, dwarf: BinDebugInfo, lir: lir::Output) -> Vec<u8> {
     let mut shared_builder = settings::builder();
-    shared_builder.set("opt_level", "speed").unwrap();
+    let opt_level = match opt_level {
+        OptLevel::O0 => "none",
+        OptLevel::O1 | OptLevel::O2 => "speed",
+        OptLevel::Os => "speed_and_size",
+    };
+    shared_builder.set("opt_level", opt_level).unwrap();
     shared_builder.enable("preserve_frame_pointers").unwrap();
     shared_builder.enable("unwind_info").unwrap();
+    shared_builder.set("tls_model", "elf_gd").unwrap();
+    // Sum payloads of up to two pointers are passed inline as `i128`
+    shared_builder.enable("enable_llvm_abi_extensions").unwrap();
     let shared_flags = settings::Flags::new(shared_builder);
     shared_flags.enable_alias_analysis();
     assert!(shared_flags.unwind_info());
@@ -53,17 +62,26 @@ pub fn run(target: Target, dwarf: BinDebugInfo, lir: lir::Output) -> Vec<u8> {
 
     let structs = layout::Structs::new(&lir.types);
 
-    let vals = lir.val_types.map(|val, ty| {
-        let size = structs.size_of(ty) as usize;
+    let constants: HashMap<M<key::Val>, constant::Serialised> = lir
+        .val_constants
+        .iter()
+        .filter_map(|(val, c)| Some((*val, structs.serialise(&lir.val_types[*val], c)?)))
+        // The address of a thread-local val isn't known statically
+        .filter(|(_, serialised)| {
+            serialised.relocs.iter().all(|(_, reloc)| {
+                !matches!(reloc, constant::Reloc::Val(val) if lir.thread_locals.contains(val))
+            })
+        })
+        .collect();
+
+    let vals = lir.val_types.map(|val, _| {
         let name = format!("{}___VAL", lir.functions[lir.val_initializers[&val]].symbol);
-        let thread_local = false; // TODO: this is something we're gonna want
-        let id = objmodule
-            .declare_data(&name, Linkage::Export, true, thread_local)
-            .unwrap();
-        let mut data = cranelift_module::DataDescription::new();
-        data.init = cranelift_module::Init::Zeros { size };
-        objmodule.define_data(id, &data).unwrap();
-        id
+        let thread_local = lir.thread_locals.contains(&val);
+        let writable =
+            thread_local || !constants.contains_key(&val) || lir.exposed_vals.contains(&val);
+        objmodule
+            .declare_data(&name, Linkage::Export, writable, thread_local)
+            .unwrap()
     });
 
     let rotable = lir.read_only_table.map(|ro, (bytes, _ty)| {
@@ -82,7 +100,7 @@ pub fn run(target: Target, dwarf: BinDebugInfo, lir: lir::Output) -> Vec<u8> {
 
     info!("lowering function signatures");
 
-    let externmap = lir
+    let externmap: HashMap<M<key::Func>, FuncId> = lir
         .extern_funcs
         .iter()
         .map(|(key, func)| {
@@ -130,10 +148,61 @@ pub fn run(target: Target, dwarf: BinDebugInfo, lir: lir::Output) -> Vec<u8> {
         })
         .collect();
 
+    for val in lir.val_types.iter() {
+        let mut data = cranelift_module::DataDescription::new();
+
+        match constants.get(&val) {
+            Some(serialised) => {
+                data.init = cranelift_module::Init::Bytes {
+                    contents: serialised.bytes.clone().into_boxed_slice(),
+                };
+
+                for (offset, reloc) in &serialised.relocs {
+                    match *reloc {
+                        constant::Reloc::Func(mfunc) => {
+                            let fref = objmodule.declare_func_in_data(funcmap[mfunc], &mut data);
+                            data.write_function_addr(*offset, fref);
+                        }
+                        constant::Reloc::Extern(key) => {
+                            let fref = objmodule.declare_func_in_data(externmap[&key], &mut data);
+                            data.write_function_addr(*offset, fref);
+                        }
+                        constant::Reloc::ReadOnly(ro) => {
+                            let gv = objmodule.declare_data_in_data(rotable[ro], &mut data);
+                            data.write_data_addr(*offset, gv, 0);
+                        }
+                        constant::Reloc::Val(val) => {
+                            let gv = objmodule.declare_data_in_data(vals[val], &mut data);
+                            data.write_data_addr(*offset, gv, 0);
+                        }
+                    }
+                }
+            }
+            None => {
+                let size = structs.size_of(&lir.val_types[val]) as usize;
+                data.init = cranelift_module::Init::Zeros { size };
+            }
+        }
+
+        objmodule.define_data(vals[val], &data).unwrap();
+    }
+
     let unwindinfo = unwind::UnwindContext::new(&*isa, true);
 
+    let constant_vals = constants.into_keys().collect();
+
     let mut ctx = Context::new(
-        isa, &vals, &lir, structs, objmodule, funcmap, externmap, flayouts, rotable, unwindinfo,
+        isa,
+        &vals,
+        constant_vals,
+        &lir,
+        structs,
+        objmodule,
+        funcmap,
+        externmap,
+        flayouts,
+        rotable,
+        unwindinfo,
         dwarf,
     );
 
@@ -162,6 +231,7 @@ pub fn run(target: Target, dwarf: BinDebugInfo, lir: lir::Output) -> Vec<u8> {
     }
 
     ctx.declare_entrypoint(target);
+    ctx.declare_thread_start();
 
     let mut product = ctx.objmodule.finish();
     ctx.unwindinfo.emit(&mut product);
@@ -174,6 +244,8 @@ pub fn run(target: Target, dwarf: BinDebugInfo, lir: lir::Output) -> Vec<u8> {
 pub struct Context<'a> {
     isa: Arc<dyn isa::TargetIsa>,
     val_to_globals: &'a MMap<key::Val, DataId>,
+    // Vals which are emitted as initialised data and thus don't need to run their initialisers
+    constant_vals: HashSet<M<key::Val>>,
     lir: &'a lir::Output,
     structs: layout::Structs<'a>,
     objmodule: ObjectModule,
@@ -196,7 +268,9 @@ impl<'a> Context<'a> {
 
     // Declares a function that runs all the val initialisers, and writes their return types to the
     // global variable mapped to that initialiser.
-    fn declare_val_run_and_store(&mut self) -> FuncId {
+    //
+    // New threads only need to initialise their own copy of the thread-local vals.
+    fn declare_val_run_and_store(&mut self, symbol: &str, thread_locals_only: bool) -> FuncId {
         let mut func_builder_ctx = FunctionBuilderContext::new();
         let mut clfunc = ir::Function::new();
         let mut builder = FunctionBuilder::new(&mut clfunc, &mut func_builder_ctx);
@@ -208,14 +282,16 @@ impl<'a> Context<'a> {
 
         let id = self
             .objmodule
-            .declare_function(
-                "__lumina_val_initialiser__",
-                Linkage::Export,
-                &builder.func.signature,
-            )
+            .declare_function(symbol, Linkage::Export, &builder.func.signature)
             .unwrap();
 
         for val in self.val_to_globals.iter() {
+            if self.constant_vals.contains(&val)
+                || (thread_locals_only && !self.lir.thread_locals.contains(&val))
+            {
+                continue;
+            }
+
             let mfunc = self.lir.val_initializers[&val];
             info!(
                 "lowering value initialiser {}",
@@ -240,7 +316,7 @@ impl<'a> Context<'a> {
 
             let call = ins.new_call(0, &flayout.ret);
             let vlayout = ins.call_direct(funcid, call);
-            ins.write_vlayout_to_ptr(ptr, &vlayout);
+            ins.write_vlayout_to_ptr(ptr, &vlayout, MemFlags::trusted());
         }
 
         builder.ins().return_(&[]);
@@ -259,8 +335,68 @@ impl<'a> Context<'a> {
         id
     }
 
+    // Declares the C ABI entrypoint for threads spawned by `std:thread`, if it's used.
+    //
+    // The thread is given a pointer to a packet whose first field is the Lumina function to run,
+    // which is then called with that same packet once the thread-local vals are initialised.
+    fn declare_thread_start(&mut self) {
+        const SYMBOL: &str = "__lumina_thread_start";
+
+        if self.objmodule.get_name(SYMBOL).is_none() {
+            return;
+        }
+
+        let tls_inits_id =
+            self.declare_val_run_and_store("__lumina_thread_local_initialiser__", true);
+
+        let size_t = self.size_t();
+        let mut func_builder_ctx = FunctionBuilderContext::new();
+        let mut clfunc = ir::Function::new();
+        let mut builder = FunctionBuilder::new(&mut clfunc, &mut func_builder_ctx);
+        builder.func.signature = Signature::new(isa::CallConv::SystemV);
+        builder.func.signature.params = vec![AbiParam::new(size_t)];
+        builder.func.signature.returns = vec![AbiParam::new(size_t)];
+
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.seal_block(entry);
+        builder.switch_to_block(entry);
+
+        let id = self
+            .objmodule
+            .declare_function(SYMBOL, Linkage::Export, &builder.func.signature)
+            .unwrap();
+
+        let tls_inits = self
+            .objmodule
+            .declare_func_in_func(tls_inits_id, builder.func);
+        builder.ins().call(tls_inits, &[]);
+
+        let packet = builder.block_params(entry)[0];
+        let entrypoint = builder.ins().load(size_t, MemFlags::trusted(), packet, 0);
+
+        let mut sig = Signature::new(isa::CallConv::Tail);
+        sig.params.push(AbiParam::new(size_t));
+        let sig = builder.import_signature(sig);
+        builder.ins().call_indirect(sig, entrypoint, &[packet]);
+
+        let null = builder.ins().iconst(size_t, 0);
+        builder.ins().return_(&[null]);
+
+        info!("thread entrypoint:\n{}", builder.func);
+
+        if let Err(err) = cranelift_codegen::verify_function(&clfunc, self.isa.as_ref()) {
+            error!("cranelift_codegen verifier error:\n{err:#?}");
+        }
+
+        let mut fctx = codegen::Context::for_function(clfunc);
+        self.objmodule.define_function(id, &mut fctx).unwrap();
+
+        self.unwindinfo.add_function(id, &fctx, &*self.isa);
+    }
+
     fn declare_entrypoint(&mut self, target: Target) -> FuncId {
-        let val_inits_id = self.declare_val_run_and_store();
+        let val_inits_id = self.declare_val_run_and_store("__lumina_val_initialiser__", false);
 
         let mut func_builder_ctx = FunctionBuilderContext::new();
         let mut clfunc = ir::Function::new();
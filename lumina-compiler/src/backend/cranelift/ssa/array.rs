@@ -5,18 +5,66 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
     pub(super) fn indice_of_entry(&mut self, entry: VLayout, indice: Value) -> VLayout {
         match entry {
             Layout::SpecialPointer(kind, ptr) => match kind {
-                SpecialPointer::StackArray(inner, len) => {
-                    let (_, elem_size, _) = self.structs.size_and_align_of_array(&inner, len);
-                    let indice_offset = self.ins().imul_imm(indice, elem_size as i64);
-                    let nptr = self.ins().iadd(ptr, indice_offset);
-                    self.deref_type(nptr, ByteOffset(0), &inner)
+                SpecialPointer::StackArray(inner, len) | SpecialPointer::HeapArray(inner, len) => {
+                    self.indice_of_arrayptr(&inner, len, ptr, indice)
                 }
                 _ => panic!("attempted indice of non-array"),
             },
+            Layout::AutoBoxed(MonoType::Array(len, inner), ptr) => {
+                self.indice_of_arrayptr(&inner, len, ptr, indice)
+            }
+            // Since the indice isn't known statically we spill the elements to the stack
+            Layout::ArrayFlat(inner, elems) => {
+                let ptr = self.stack_alloc_array(&inner, elems.len() as u64);
+                self.write_elems_to_arrayptr(&inner, &elems, ptr);
+                self.indice_of_arrayptr(&inner, elems.len() as u64, ptr, indice)
+            }
             _ => panic!("cannot access indice of: {entry:?}"),
         }
     }
 
+    fn indice_of_arrayptr(
+        &mut self,
+        inner: &MonoType,
+        len: u64,
+        ptr: Value,
+        indice: Value,
+    ) -> VLayout {
+        let (_, elem_size, _) = self.structs.size_and_align_of_array(inner, len);
+        let indice_offset = self.ins().imul_imm(indice, elem_size as i64);
+        let nptr = self.ins().iadd(ptr, indice_offset);
+        self.deref_type(nptr, ByteOffset(0), inner, MemFlags::trusted())
+    }
+
+    /// Reads the elements of an array stored at `offset` from `ptr`
+    pub(super) fn get_elems_from_arrayptr(
+        &mut self,
+        inner: &MonoType,
+        len: u64,
+        ptr: Value,
+        offset: ByteOffset,
+        flags: MemFlags,
+    ) -> Vec<VLayout> {
+        let (_, elem_size, _) = self.structs.size_and_align_of_array(inner, len);
+        (0..len as u32)
+            .map(|i| {
+                let offset = ByteOffset(offset.0 + elem_size * i);
+                self.deref_type(ptr, offset, inner, flags)
+            })
+            .collect()
+    }
+
+    pub(super) fn stack_alloc_array(&mut self, inner: &MonoType, len: u64) -> Value {
+        let (slot, _) = self.alloc_array(inner, len as usize);
+        let size_t = self.size_t;
+        self.ins().stack_addr(size_t, slot, 0)
+    }
+
+    pub(super) fn heap_alloc_array(&mut self, inner: &MonoType, len: u64) -> Value {
+        let (size, _, _) = self.structs.size_and_align_of_array(inner, len);
+        self.heap_alloc(size as i128)
+    }
+
     fn alloc_array(&mut self, inner: &MonoType, len: usize) -> (ir::StackSlot, u32) {
         let (size, elem_size, align) = self.structs.size_and_align_of_array(inner, len as u64);
         (self.create_struct_stack_slot(size, align as u8), elem_size)
@@ -57,7 +105,8 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
             let entry = self.value_to_vlayout(*value);
             let slot_addr = self.cins().stack_addr(size_t, slot, offset);
 
-            self.ins().write_vlayout_to_ptr(slot_addr, &entry);
+            self.ins()
+                .write_vlayout_to_ptr(slot_addr, &entry, MemFlags::trusted());
             offset += elem_size as i32;
         }
 
@@ -82,12 +82,21 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
         let clblock = self.f.blockmap[jump.id].0;
         let mut buf = Vec::with_capacity(self.f.builder.block_params(clblock).len());
 
+        // Stack slots are reused for every iteration of a loop, so values carried over to the next
+        // iteration can't point to them.
+        let promote_stack = self.f.func.ssa.reaches(jump.id, self.f.block);
+
         for (i, p) in jump.params.iter().enumerate() {
             let got = self.value_to_vlayout(*p);
 
             let v = self.f.func.ssa.get_block_param(jump.id, i as u32);
             let ty = self.f.func.ssa.type_of(v);
             let exp = self.ctx.structs.type_to_layout(ty, Stability::F);
+            let exp = if promote_stack {
+                exp.promote_all_stack_to_heap()
+            } else {
+                exp
+            };
 
             let unified = self.ins().make_compatible(&exp, got);
             self.ins().layout_into_raw_values(&unified, &mut buf);
@@ -156,7 +165,12 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
         let rlayout = &self.ctx.flayouts[id].ret;
         let current_rlayout = &self.ctx.flayouts[self.f.id].ret;
 
-        if rlayout == current_rlayout {
+        if self.reaches_alloca(cparams) {
+            info!("refusing tail call to {fname} in {cname} as it may reference the current stack");
+
+            let layout = self.call_func_id(id, cparams);
+            self.return_(false, layout);
+        } else if rlayout == current_rlayout {
             info!("performing a ret-tail call to {fname} in {cname}");
 
             let _out_pointer = self.copy_tail_rptr(&mut params);
@@ -201,4 +215,11 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
     fn has_references_to_current_stack(&self, params: &[Layout<Type>]) -> bool {
         params.iter().any(Layout::has_stack_pointers)
     }
+
+    // Pointers from `alloca` have ordinary pointer layouts, so they can't be promoted like the
+    // stack pointers of structs can.
+    fn reaches_alloca(&self, cparams: &[lir::Value]) -> bool {
+        let ssa = &self.f.func.ssa;
+        cparams.iter().any(|&p| ssa.reaches_alloca(p))
+    }
 }
@@ -61,6 +61,13 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
         match want {
             Layout::Scalar(kind, _) => match have {
                 Layout::Scalar(k, v) if k == *kind => Layout::Scalar(k.clone(), v),
+                // Function pointers are compatible regardless of calling convention, such as when
+                // giving a pointer to an FFI function as a callback to FFI.
+                Layout::Scalar(k @ Scalar::FuncPointer(_), v)
+                    if matches!(kind, Scalar::FuncPointer(_)) =>
+                {
+                    Layout::Scalar(k, v)
+                }
                 _ => panic!("want `Scalar`, have {:?}", have),
             },
             Layout::ZST => expect!(Layout::ZST, "ZST" => Layout::ZST),
@@ -82,11 +89,7 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
                         Layout::SpecialPointer(SpecialPointer::HeapSumPayload { sum }, ptr)
                     }
                     Layout::SpecialPointer(SpecialPointer::StackSumPayload { sum }, ptr) => {
-                        let largest = self.structs.sum_payload_alloca_size(sum);
-                        let size_t = self.size_t;
-                        // TODO: figure out how we're meant to get the alignment here
-                        let nptr =
-                            self.memcpy_to_heap(ptr, largest as u64, size_t.bytes() as u8, true);
+                        let nptr = self.heaplift_sum_payload(ptr, sum);
                         Layout::SpecialPointer(SpecialPointer::HeapSumPayload { sum }, nptr)
                     }
                     _ => panic!("want `SumPayload`, have {have:?}"),
@@ -99,20 +102,45 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
                     let ptr = self.layout_to_struct_heap_pointer(*mk, &have);
                     Layout::SpecialPointer(SpecialPointer::HeapStruct(*mk), ptr)
                 }
-                SpecialPointer::StackArray(_, _) => todo!("array repr"),
+                SpecialPointer::StackArray(inner, len) => {
+                    let ptr = self.layout_to_array_stack_pointer(inner, *len, have);
+                    Layout::SpecialPointer(SpecialPointer::StackArray(inner.clone(), *len), ptr)
+                }
+                SpecialPointer::HeapArray(inner, len) => {
+                    let ptr = self.layout_to_array_heap_pointer(inner, *len, have);
+                    Layout::SpecialPointer(SpecialPointer::HeapArray(inner.clone(), *len), ptr)
+                }
             },
             Layout::AutoBoxed(ty, _) => {
                 let ptr = self.layout_to_autoboxed(&ty, &have);
                 Layout::AutoBoxed(ty.clone(), ptr)
                 // buf.push(ptr);
             }
-            Layout::ArrayFlat(inner, fields) => {
-                expect!(Layout::ArrayFlat(ginner, gfields), "ArrayFlat" => {
+            Layout::ArrayFlat(inner, fields) => match have {
+                Layout::ArrayFlat(ginner, gfields) => {
                     assert_eq!((inner, fields.len()), (&ginner, gfields.len()));
-                    let fields = fields.iter().zip(gfields).map(|(e, g)| self.make_compatible_plus(out, e, g)).collect();
+                    let fields = fields
+                        .iter()
+                        .zip(gfields)
+                        .map(|(e, g)| self.make_compatible_plus(out, e, g))
+                        .collect();
                     Layout::ArrayFlat(ginner, fields)
-                })
-            }
+                }
+                _ => {
+                    let Some((ginner, len, ptr)) = as_array_pointer(&have) else {
+                        panic!("want `ArrayFlat`, have `{have:?}`");
+                    };
+                    assert_eq!((inner, fields.len() as u64), (&ginner, len));
+                    let flags = MemFlags::trusted();
+                    let elems = self.get_elems_from_arrayptr(inner, len, ptr, ByteOffset(0), flags);
+                    let fields = fields
+                        .iter()
+                        .zip(elems)
+                        .map(|(e, g)| self.make_compatible_plus(out, e, g))
+                        .collect();
+                    Layout::ArrayFlat(ginner, fields)
+                }
+            },
             Layout::StructFlat(mk, fields) => match have {
                 Layout::StructFlat(gmk, gfields) => {
                     assert_eq!((mk, fields.len()), (&gmk, gfields.len()));
@@ -154,8 +182,14 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
                             Layout::OutPointer(kind.clone(), dst)
                             // self.write_special_pointer_to_out_pointer(outptr, src, ByteOffset(0), kind)
                         }
-                        Layout::StructFlat(_, _) => todo!("TODO: write fields to outptr"),
-                        Layout::ArrayFlat(_, _) => todo!(),
+                        Layout::StructFlat(mk, fields) => {
+                            self.write_fields_to_structptr(mk, &fields, dst);
+                            Layout::OutPointer(kind.clone(), dst)
+                        }
+                        Layout::ArrayFlat(inner, elems) => {
+                            self.write_elems_to_arrayptr(&inner, &elems, dst);
+                            Layout::OutPointer(kind.clone(), dst)
+                        }
                         _ => unreachable!(),
                     }
                 }
@@ -207,12 +241,14 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
         mk: MonoTypeKey,
         fields: &Map<layout::Field, Layout<Type>>,
     ) -> Map<layout::Field, VLayout> {
+        let flags = self.structs.mem_flags(mk);
+
         fields
             .iter()
             .map(|(field, flayout)| {
                 let mut foffset = self.structs.offset_of(mk, field);
                 foffset.0 += offset.0;
-                self.get_field_from_structptr(out, flayout, ptr, foffset)
+                self.get_field_from_structptr(out, flayout, ptr, foffset, flags)
             })
             .collect()
     }
@@ -225,30 +261,40 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
         flayout: &Layout<Type>,
         ptr: Value,
         offset: ByteOffset,
+        flags: MemFlags,
     ) -> VLayout {
         match flayout {
             Layout::Scalar(kind, clty) => {
-                let v = self
-                    .ins()
-                    .load(*clty, MemFlags::trusted(), ptr, offset.0 as i32);
+                let v = self.ins().load(*clty, flags, ptr, offset.0 as i32);
                 VLayout::Scalar(kind.clone(), v)
             }
             Layout::AutoBoxed(ty, clty) => {
-                let v = self
-                    .ins()
-                    .load(*clty, MemFlags::trusted(), ptr, offset.0 as i32);
+                let v = self.ins().load(*clty, flags, ptr, offset.0 as i32);
                 VLayout::AutoBoxed(ty.clone(), v)
             }
             Layout::ZST => VLayout::ZST,
-            Layout::ArrayFlat(_, _) => todo!(),
+            Layout::ArrayFlat(inner, elems) => {
+                let (_, elem_size, _) = self
+                    .structs
+                    .size_and_align_of_array(inner, elems.len() as u64);
+
+                let elems = elems
+                    .iter()
+                    .enumerate()
+                    .map(|(i, elayout)| {
+                        let offset = ByteOffset(offset.0 + elem_size * i as u32);
+                        self.get_field_from_structptr(out, elayout, ptr, offset, flags)
+                    })
+                    .collect();
+
+                Layout::ArrayFlat(inner.clone(), elems)
+            }
             Layout::StructFlat(mk, fields) => {
                 let fields = self.get_fields_from_structptr(out, ptr, offset, *mk, fields);
                 Layout::StructFlat(*mk, fields)
             }
             Layout::SpecialPointer(kind, clty) => {
-                let v = self
-                    .ins()
-                    .load(*clty, MemFlags::trusted(), ptr, offset.0 as i32);
+                let v = self.ins().load(*clty, flags, ptr, offset.0 as i32);
                 VLayout::SpecialPointer(kind.clone(), v)
             }
             Layout::OutPointer(kind, _) => {
@@ -312,10 +358,78 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
         }
     }
 
+    fn layout_to_array_stack_pointer(&mut self, inner: &MonoType, len: u64, got: VLayout) -> Value {
+        match got {
+            Layout::SpecialPointer(
+                SpecialPointer::StackArray(ginner, glen) | SpecialPointer::HeapArray(ginner, glen),
+                ptr,
+            ) => {
+                assert_eq!((inner, len), (&ginner, glen));
+                ptr
+            }
+            Layout::AutoBoxed(MonoType::Array(glen, ginner), ptr) => {
+                assert_eq!((inner, len), (&*ginner, glen));
+                ptr
+            }
+            Layout::ArrayFlat(ginner, elems) => {
+                assert_eq!((inner, len), (&ginner, elems.len() as u64));
+                let dst = self.stack_alloc_array(inner, len);
+                self.write_elems_to_arrayptr(inner, &elems, dst);
+                dst
+            }
+            _ => panic!("not an array: {got:?}"),
+        }
+    }
+
+    fn layout_to_array_heap_pointer(&mut self, inner: &MonoType, len: u64, got: VLayout) -> Value {
+        match got {
+            Layout::SpecialPointer(SpecialPointer::HeapArray(ginner, glen), ptr) => {
+                assert_eq!((inner, len), (&ginner, glen));
+                ptr
+            }
+            Layout::AutoBoxed(MonoType::Array(glen, ginner), ptr) => {
+                assert_eq!((inner, len), (&*ginner, glen));
+                ptr
+            }
+            Layout::SpecialPointer(SpecialPointer::StackArray(ginner, glen), ptr) => {
+                assert_eq!((inner, len), (&ginner, glen));
+                let (size, _, align) = self.structs.size_and_align_of_array(inner, len);
+                self.memcpy_to_heap(ptr, size as u64, align as u8, false)
+            }
+            Layout::ArrayFlat(ginner, elems) => {
+                assert_eq!((inner, len), (&ginner, elems.len() as u64));
+                let dst = self.heap_alloc_array(inner, len);
+                self.write_elems_to_arrayptr(inner, &elems, dst);
+                dst
+            }
+            _ => panic!("not an array: {got:?}"),
+        }
+    }
+
     fn layout_to_autoboxed(&mut self, ty: &MonoType, got: &VLayout) -> Value {
         match ty {
             MonoType::Monomorphised(mk) => self.layout_to_struct_heap_pointer(*mk, got),
-            _ => unimplemented!("autoboxing of non-structs"),
+            MonoType::Array(len, inner) => match got {
+                Layout::ZST => {
+                    let size_t = self.size_t;
+                    self.ins().iconst(size_t, 0)
+                }
+                _ => self.layout_to_array_heap_pointer(inner, *len, got.clone()),
+            },
+            _ => unimplemented!("autoboxing of {ty:?}"),
+        }
+    }
+}
+
+fn as_array_pointer(layout: &VLayout) -> Option<(MonoType, u64, Value)> {
+    match layout {
+        Layout::SpecialPointer(
+            SpecialPointer::StackArray(inner, len) | SpecialPointer::HeapArray(inner, len),
+            ptr,
+        ) => Some((inner.clone(), *len, *ptr)),
+        Layout::AutoBoxed(MonoType::Array(len, inner), ptr) => {
+            Some(((**inner).clone(), *len, *ptr))
         }
+        _ => None,
     }
 }
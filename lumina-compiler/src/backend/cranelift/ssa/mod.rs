@@ -203,7 +203,10 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
                 _ => panic!("cannot construct: {ty:?}"),
             },
             lir::Entry::Replicate(value, times) => match ty {
-                MonoType::Monomorphised(_) => todo!("replicating to construct struct/tuple"),
+                MonoType::Monomorphised(mk) => {
+                    let values = vec![*value; *times as usize];
+                    self.construct_record(*mk, &values)
+                }
                 MonoType::Array(len, inner) => {
                     assert_eq!(*times, *len);
                     self.replicate_array(*value, inner, *times)
@@ -259,8 +262,11 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
                 VLayout::pointer((**innert).clone(), ptr)
             }
             lir::Entry::Alloca => {
-                let ptr = self.ins().stack_alloc_type(ty);
-                VLayout::pointer(ty.clone(), ptr)
+                let MonoType::Pointer(inner) = ty else {
+                    panic!("alloca of non-pointer type: {ty:?}");
+                };
+                let ptr = self.ins().stack_alloc_type(inner);
+                VLayout::pointer((**inner).clone(), ptr)
             }
             lir::Entry::Dealloc { .. } => {
                 panic!("dealloc should be removed");
@@ -268,7 +274,8 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
             lir::Entry::WritePtr { ptr, value } => {
                 let [ptr, value] = [*ptr, *value].map(|v| self.value_to_vlayout(v));
                 let ptr = ptr.as_pointer().1;
-                self.ins().write_vlayout_to_ptr(ptr, &value);
+                self.ins()
+                    .write_vlayout_to_ptr(ptr, &value, MemFlags::trusted());
                 Layout::ZST
             }
             lir::Entry::MemCpy { dst, src, count } => {
@@ -281,7 +288,36 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
             lir::Entry::Deref(ptr) => {
                 let ptr = self.value_to_vlayout(*ptr);
                 let (ty, ptr) = ptr.as_pointer();
-                self.ins().deref_type(ptr, ByteOffset(0), ty)
+                let value = self
+                    .ins()
+                    .deref_type(ptr, ByteOffset(0), ty, MemFlags::trusted());
+                if self.f.func.ssa.deref_needs_copy(v) {
+                    self.ins().snapshot(value)
+                } else {
+                    value
+                }
+            }
+
+            lir::Entry::AtomicLoad(ptr) => {
+                let ptr = self.value_to_vlayout(*ptr).as_pointer().1;
+                self.ins().atomic_load(ptr, ty)
+            }
+            lir::Entry::AtomicStore { ptr, value } => {
+                let [ptr, value] = [*ptr, *value].map(|v| self.value_to_vlayout(v));
+                let ptr = ptr.as_pointer().1;
+                self.ins().atomic_store(ptr, value.as_scalar());
+                Layout::ZST
+            }
+            lir::Entry::AtomicCas { ptr, expected, replacement } => {
+                let ptr = self.value_to_vlayout(*ptr).as_pointer().1;
+                let [expected, replacement] =
+                    [*expected, *replacement].map(|v| self.value_to_vlayout(v).as_scalar());
+                self.ins().atomic_cas(ptr, expected, replacement, ty)
+            }
+            lir::Entry::AtomicRmw(op, [ptr, value]) => {
+                let ptr = self.value_to_vlayout(*ptr).as_pointer().1;
+                let value = self.value_to_vlayout(*value).as_scalar();
+                self.ins().atomic_rmw(*op, ptr, value, ty)
             }
 
             lir::Entry::JmpFunc(mfunc, params) => {
@@ -383,7 +419,10 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
                 let n = self.cins().iconst(ty, n as i64);
                 Layout::direct(n)
             }
-            lir::Value::Float(_) => todo!(),
+            lir::Value::Float(n) => {
+                let n = self.cins().f64const(n);
+                Layout::direct(n)
+            }
         }
     }
 
@@ -469,7 +508,8 @@ pub(super) struct InstHelper<'f, 's, 'a> {
 
 impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
     pub fn create_struct_stack_slot(&mut self, size: u32, align: u8) -> ir::StackSlot {
-        let slotdata = StackSlotData::new(StackSlotKind::ExplicitSlot, size, align as u8);
+        let align_shift = align.max(1).trailing_zeros() as u8;
+        let slotdata = StackSlotData::new(StackSlotKind::ExplicitSlot, size, align_shift);
         self.builder.create_sized_stack_slot(slotdata)
     }
 
@@ -488,6 +528,20 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
                         // int and uint have the same representation so we don't need to do anything
                         VLayout::direct(v)
                     }
+                    // A pointer to the first element is reinterpreted as the array itself
+                    MonoType::Array(len, inner) => match self
+                        .structs
+                        .type_to_layout(ty, Stability::F)
+                    {
+                        Layout::ZST => Layout::ZST,
+                        Layout::SpecialPointer(kind, _) => Layout::SpecialPointer(kind, v),
+                        _ => {
+                            let flags = MemFlags::trusted();
+                            let elems =
+                                self.get_elems_from_arrayptr(inner, *len, v, ByteOffset(0), flags);
+                            Layout::ArrayFlat((**inner).clone(), elems)
+                        }
+                    },
                     other => unimplemented!("transmuting {other:?} into {ty:?}"),
                 }
             }
@@ -498,6 +552,34 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
     fn tag_of_sum(&mut self, v: Layout<Value>) -> VLayout {
         let find = |key| self.structs.get(key).field_map[key::Field(0)];
         match v {
+            Layout::StructFlat(key, entries) if self.structs.get(key).niche.is_some() => {
+                let ptr = match &entries[find(key)] {
+                    &Layout::Scalar(Scalar::SumPayloadInline, inline) => {
+                        let niche = self.structs.get(key).niche.unwrap();
+                        let inline = if niche.offset == 0 {
+                            inline
+                        } else {
+                            self.ins().ushr_imm(inline, niche.offset as i64 * 8)
+                        };
+                        self.resize_uint(inline, self.size_t)
+                    }
+                    Layout::SpecialPointer(_, ptr) => *ptr,
+                    other => unreachable!("{other:?}"),
+                };
+                let tag = self.tag_from_niche(key, ptr);
+                VLayout::direct(tag)
+            }
+            Layout::SpecialPointer(SpecialPointer::StackStruct(key), ptr)
+                if self.structs.get(key).niche.is_some() =>
+            {
+                let niche = self.structs.get(key).niche.unwrap();
+                let size_t = self.size_t;
+                let ptr = self
+                    .ins()
+                    .load(size_t, MemFlags::trusted(), ptr, niche.offset as i32);
+                let tag = self.tag_from_niche(key, ptr);
+                VLayout::direct(tag)
+            }
             Layout::StructFlat(key, mut entries) => {
                 let i = find(key);
                 let tag = entries.as_mut_vec().remove(i.into()).as_direct();
@@ -522,6 +604,36 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
         }
     }
 
+    // The niche optimised sum is the empty variant if its pointer is null
+    fn tag_from_niche(&mut self, key: MonoTypeKey, ptr: Value) -> Value {
+        let (tag, _, _) = self.structs.records[key].as_sum();
+        let null = self.structs.get(key).niche.unwrap().null;
+
+        let cc = if null.0 == 0 {
+            IntCC::NotEqual
+        } else {
+            IntCC::Equal
+        };
+        let is_set = self.ins().icmp_imm(cc, ptr, 0);
+
+        let tagt = Type::int(tag.bits() as u16).unwrap();
+        if tagt == types::I8 {
+            is_set
+        } else {
+            self.ins().uextend(tagt, is_set)
+        }
+    }
+
+    fn resize_uint(&mut self, n: Value, to: Type) -> Value {
+        let has = self.builder.func.dfg.value_type(n).bytes();
+
+        match has.cmp(&to.bytes()) {
+            std::cmp::Ordering::Equal => n,
+            std::cmp::Ordering::Less => self.ins().uextend(to, n),
+            std::cmp::Ordering::Greater => self.ins().ireduce(to, n),
+        }
+    }
+
     fn size_of(&mut self, ty: &MonoType) -> VLayout {
         let size = self.structs.size_of(ty);
         let size_t = self.size_t;
@@ -542,7 +654,11 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
             .declare_data_in_func(dataid, &mut self.builder.func);
 
         let size_t = self.size_t;
-        self.ins().symbol_value(size_t, data)
+        if self.objmodule.declarations().get_data_decl(dataid).tls {
+            self.ins().tls_value(size_t, data)
+        } else {
+            self.ins().symbol_value(size_t, data)
+        }
     }
 
     pub fn ins(&mut self) -> FuncInstBuilder<'_, 'a> {
@@ -605,15 +721,10 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
         self.builder.func.dfg.value_type(value)
     }
 
-    fn heaplift_sum_payload(&mut self, sptr: Value, tag_size: u8, largest: u32) -> Value {
-        let size_t = self.size_t;
-        let align = if largest < size_t.bytes() {
-            (largest as u8).max(tag_size)
-        } else {
-            size_t.bytes() as u8
-        };
-        let nptr = self.memcpy_to_heap(sptr, largest as u64, align, true);
-        nptr
+    fn heaplift_sum_payload(&mut self, sptr: Value, sum: MonoTypeKey) -> Value {
+        let largest = self.structs.sum_payload_alloca_size(sum);
+        let align = self.structs.sum_payload_align(sum);
+        self.memcpy_to_heap(sptr, largest as u64, align as u8, true)
     }
 }
 
@@ -40,9 +40,15 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
         self.ins().stack_addr(size_t, slot, 0)
     }
 
-    pub(super) fn deref_type(&mut self, ptr: Value, offset: ByteOffset, ty: &MonoType) -> VLayout {
+    pub(super) fn deref_type(
+        &mut self,
+        ptr: Value,
+        offset: ByteOffset,
+        ty: &MonoType,
+        flags: MemFlags,
+    ) -> VLayout {
         let layout = self.structs.type_to_layout(ty, Stability::S);
-        self.deref(ptr, offset, layout)
+        self.deref(ptr, offset, layout, flags)
     }
 
     /// Reads `layout` at `offset` from `ptr`
@@ -51,6 +57,7 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
         ptr: Value,
         offset: ByteOffset,
         layout: Layout<Type>,
+        flags: MemFlags,
     ) -> VLayout {
         match layout {
             Layout::AutoBoxed(_, _) => panic!("???"),
@@ -62,13 +69,17 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
                     let kind = SpecialPointer::StackStruct(mk);
                     Layout::SpecialPointer(kind, innerp)
                 }
-                SpecialPointer::StackArray(_, _) => todo!(),
+                SpecialPointer::HeapArray(inner, len) | SpecialPointer::StackArray(inner, len) => {
+                    let innerp = self.ptr_offset(ptr, offset);
+                    let kind = SpecialPointer::StackArray(inner, len);
+                    Layout::SpecialPointer(kind, innerp)
+                }
                 _ => panic!("direct read from opaque sum data"),
             },
-            Layout::ArrayFlat(_, _) => {
-                todo!("properly find the corresponding `fields` instead");
-                // let innerp = self.ptr_offset(ptr, offset);
-                // Layout::ArrayStackPointer(inner, fields.len() as u64, innerp)
+            Layout::ArrayFlat(inner, elems) => {
+                let elems =
+                    self.get_elems_from_arrayptr(&inner, elems.len() as u64, ptr, offset, flags);
+                Layout::ArrayFlat(inner, elems)
             }
 
             Layout::StructFlat(mk, fields) => {
@@ -79,14 +90,83 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
             Layout::ZST => Layout::ZST,
             Layout::OutPointer(..) => panic!("cant read from OutPointer"),
             Layout::Scalar(kind, clty) => {
-                let v = self
-                    .ins()
-                    .load(clty, MemFlags::trusted(), ptr, offset.0 as i32);
+                let v = self.ins().load(clty, flags, ptr, offset.0 as i32);
                 Layout::Scalar(kind.clone(), v)
             }
         }
     }
 
+    /// Copies structs and arrays which `deref` left pointing into the original memory onto the
+    /// stack, so that later writes to or frees of that memory aren't observed through the result
+    pub(super) fn snapshot(&mut self, v: VLayout) -> VLayout {
+        match v {
+            Layout::SpecialPointer(SpecialPointer::StackStruct(mk), src) => {
+                let (size, align) = self.structs.size_and_align_of_mk(mk);
+                let dst = self.stack_alloc(size, align as u8);
+                self.memcpy_struct(dst, src, size as u64, align as u8);
+                Layout::SpecialPointer(SpecialPointer::StackStruct(mk), dst)
+            }
+            Layout::SpecialPointer(SpecialPointer::StackArray(inner, len), src) => {
+                let (size, _, align) = self.structs.size_and_align_of_array(&inner, len);
+                let dst = self.stack_alloc(size, align as u8);
+                self.memcpy_struct(dst, src, size as u64, align as u8);
+                Layout::SpecialPointer(SpecialPointer::StackArray(inner, len), dst)
+            }
+            Layout::ArrayFlat(inner, elems) => {
+                let elems = elems.into_iter().map(|elem| self.snapshot(elem)).collect();
+                Layout::ArrayFlat(inner, elems)
+            }
+            v => v,
+        }
+    }
+
+    pub(super) fn atomic_load(&mut self, ptr: Value, ty: &MonoType) -> VLayout {
+        let (kind, clty) = self.atomic_scalar(ty);
+        let v = self.ins().atomic_load(clty, MemFlags::trusted(), ptr);
+        Layout::Scalar(kind, v)
+    }
+
+    pub(super) fn atomic_store(&mut self, ptr: Value, value: Value) {
+        self.ins().atomic_store(MemFlags::trusted(), value, ptr);
+    }
+
+    /// Returns the previous value, which equals `expected` if the swap succeeded
+    pub(super) fn atomic_cas(
+        &mut self,
+        ptr: Value,
+        expected: Value,
+        replacement: Value,
+        ty: &MonoType,
+    ) -> VLayout {
+        let (kind, _) = self.atomic_scalar(ty);
+        let v = self
+            .ins()
+            .atomic_cas(MemFlags::trusted(), ptr, expected, replacement);
+        Layout::Scalar(kind, v)
+    }
+
+    /// Returns the previous value
+    pub(super) fn atomic_rmw(
+        &mut self,
+        op: ir::AtomicRmwOp,
+        ptr: Value,
+        value: Value,
+        ty: &MonoType,
+    ) -> VLayout {
+        let (kind, clty) = self.atomic_scalar(ty);
+        let v = self
+            .ins()
+            .atomic_rmw(clty, MemFlags::trusted(), op, ptr, value);
+        Layout::Scalar(kind, v)
+    }
+
+    fn atomic_scalar(&self, ty: &MonoType) -> (Scalar, Type) {
+        match self.structs.type_to_layout(ty, Stability::S) {
+            Layout::Scalar(kind, clty) => (kind, clty),
+            _ => panic!("atomic operation on non-scalar type: {ty:?}"),
+        }
+    }
+
     pub(super) fn ptr_offset(&mut self, ptr: Value, offset: ByteOffset) -> Value {
         if offset == ByteOffset(0) {
             ptr
@@ -96,37 +176,44 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
     }
 
     // S_Stable write of layout to pointer
-    pub fn write_vlayout_to_ptr(&mut self, dst: Value, vlayout: &VLayout) {
+    //
+    // `flags` should come from the struct `dst` is a field of, since fields of packed structs
+    // may be unaligned.
+    pub fn write_vlayout_to_ptr(&mut self, dst: Value, vlayout: &VLayout, flags: MemFlags) {
         match vlayout {
             Layout::SpecialPointer(kind, ptr) => match kind {
                 &SpecialPointer::StackSumPayload { sum } => {
-                    let largest = self.structs.sum_payload_alloca_size(sum);
-                    let (tag_size, _, _) = self.structs.records[sum].as_sum();
-                    let nptr = self.heaplift_sum_payload(*ptr, tag_size.bytes(), largest);
-                    self.ins().store(MemFlags::trusted(), nptr, dst, 0);
+                    let nptr = self.heaplift_sum_payload(*ptr, sum);
+                    self.ins().store(flags, nptr, dst, 0);
                 }
                 SpecialPointer::HeapSumPayload { .. } => {
-                    self.ins().store(MemFlags::trusted(), *ptr, dst, 0);
+                    self.ins().store(flags, *ptr, dst, 0);
                 }
                 SpecialPointer::HeapStruct(key) | SpecialPointer::StackStruct(key) => {
                     let (size, align) = self.structs.size_and_align_of_mk(*key);
+                    let align = if flags.aligned() { align } else { 1 };
                     self.memcpy_struct(dst, *ptr, size as u64, align as u8);
                 }
-                SpecialPointer::StackArray(inner, len) => {
+                SpecialPointer::StackArray(inner, len) | SpecialPointer::HeapArray(inner, len) => {
                     let (size, _, align) = self.structs.size_and_align_of_array(inner, *len);
+                    let align = if flags.aligned() { align } else { 1 };
                     self.memcpy_struct(dst, *ptr, size as u64, align as u8)
                 }
             },
 
             Layout::StructFlat(key, flat) => self.write_fields_to_structptr(*key, &flat, dst),
-            Layout::ArrayFlat(inner, flat) => self.write_elems_to_arrayptr(inner, flat, dst),
+            Layout::ArrayFlat(inner, flat) => {
+                self.write_elems_to_arrayptr_with(inner, flat, dst, flags)
+            }
 
             Layout::ZST => {}
             Layout::Scalar(_, v) => {
-                self.ins().store(MemFlags::trusted(), *v, dst, 0);
+                self.ins().store(flags, *v, dst, 0);
             }
 
-            Layout::AutoBoxed(_, _) => todo!("memcpy underlying type of autoboxed value"),
+            Layout::AutoBoxed(_, ptr) => {
+                self.ins().store(flags, *ptr, dst, 0);
+            }
             Layout::OutPointer(..) => panic!("attempted to write out pointer"),
         }
     }
@@ -137,17 +224,23 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
         flat: &[VLayout],
         ptr: Value,
     ) {
-        let (_, elem_size, align) = self
+        self.write_elems_to_arrayptr_with(inner, flat, ptr, MemFlags::trusted())
+    }
+
+    fn write_elems_to_arrayptr_with(
+        &mut self,
+        inner: &MonoType,
+        flat: &[VLayout],
+        ptr: Value,
+        flags: MemFlags,
+    ) {
+        let (_, elem_size, _) = self
             .structs
             .size_and_align_of_array(inner, flat.len() as u64);
 
-        let mut offset = 0;
-
-        for flayout in flat {
-            let ptr = self.ptr_offset(ptr, ByteOffset(offset));
-            self.write_vlayout_to_ptr(ptr, flayout);
-            let padding = (align - offset % align) % align;
-            offset += elem_size + padding;
+        for (i, flayout) in flat.iter().enumerate() {
+            let ptr = self.ptr_offset(ptr, ByteOffset(elem_size * i as u32));
+            self.write_vlayout_to_ptr(ptr, flayout, flags);
         }
     }
 
@@ -157,10 +250,12 @@ impl<'a, 's, 'f> InstHelper<'a, 's, 'f> {
         fields: &Map<layout::Field, VLayout>,
         ptr: Value,
     ) {
+        let flags = self.structs.mem_flags(key);
+
         for (field, flayout) in fields.iter() {
             let offset = self.structs.offset_of(key, field);
             let ptr = self.ptr_offset(ptr, offset);
-            self.write_vlayout_to_ptr(ptr, flayout);
+            self.write_vlayout_to_ptr(ptr, flayout, flags);
         }
     }
 }
@@ -1,5 +1,7 @@
 use super::*;
+use crate::TRAIT_OBJECT_DATA_FIELD;
 use layout::SpecialPointer;
+use lir::MonoTypeData;
 
 impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
     pub(super) fn construct_record(&mut self, key: MonoTypeKey, values: &[lir::Value]) -> VLayout {
@@ -41,6 +43,7 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
 
         let slot = self.ins().create_struct_stack_slot(size, align as u8);
         let size_t = self.ctx.size_t();
+        let flags = self.ctx.structs.mem_flags(key);
 
         for (field, value) in values.iter().enumerate() {
             let field = key::Field(field as u32);
@@ -51,7 +54,7 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
 
             let slot_addr = self.cins().stack_addr(size_t, slot, offset.0 as i32);
 
-            self.ins().write_vlayout_to_ptr(slot_addr, &vlayout);
+            self.ins().write_vlayout_to_ptr(slot_addr, &vlayout, flags);
         }
 
         self.cins().stack_addr(size_t, slot, 0)
@@ -102,7 +105,14 @@ impl<'f, 's, 'a> InstHelper<'f, 's, 'a> {
         let rfield = self.structs.get_real_field(mk, field);
         let offset = self.structs.offset_of(mk, rfield);
 
-        let fty = &self.structs.records[mk].as_record()[field].clone();
-        self.deref_type(ptr, offset, fty)
+        let fty = match &self.structs.records[mk] {
+            MonoTypeData::DynTraitObject { vtable, .. } => match field {
+                TRAIT_OBJECT_DATA_FIELD => MonoType::u8_pointer(),
+                _ => vtable.clone(),
+            },
+            data => data.as_record()[field].clone(),
+        };
+        let flags = self.structs.mem_flags(mk);
+        self.deref_type(ptr, offset, &fty, flags)
     }
 }
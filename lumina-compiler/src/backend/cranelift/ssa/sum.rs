@@ -15,15 +15,23 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
         let param_tuple = variants[var];
 
         let tagt = Type::int(tag.bits() as u16).unwrap();
-        let tag = self.cins().iconst(tagt, var.0 as i64);
-        let tagfield = Layout::direct(tag);
-
         let sum_struct = self.ctx.structs.get(key);
 
         let layout = match sum_struct.field_map.as_slice() {
-            [_tagi] => Layout::StructFlat(key, [tagfield].into()),
-            [_tagi, payloadi] => {
-                let payload = match &sum_struct.fields[*payloadi] {
+            [_tagi] => {
+                let tag = self.cins().iconst(tagt, var.0 as i64);
+                Layout::StructFlat(key, [Layout::direct(tag)].into())
+            }
+            &[tagi, payloadi] => {
+                let is_null = sum_struct.niche.is_some_and(|niche| niche.null == var);
+
+                let payload = match &sum_struct.fields[payloadi] {
+                    &layout::StructField::SumPayloadPointer { sum } if is_null => {
+                        let size_t = self.ctx.size_t();
+                        let null = self.cins().iconst(size_t, 0);
+                        let kind = layout::SpecialPointer::StackSumPayload { sum };
+                        Layout::SpecialPointer(kind, null)
+                    }
                     &layout::StructField::SumPayloadPointer { sum } => {
                         let largest = self.ctx.structs.sum_payload_alloca_size(sum);
                         let ptr =
@@ -37,7 +45,14 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
                     }
                     other => unreachable!("{other:?}"),
                 };
-                Layout::StructFlat(key, [payload, tagfield].into())
+
+                // The niche stands in for the tag
+                if tagi == payloadi {
+                    return Layout::StructFlat(key, [payload].into());
+                }
+
+                let tag = self.cins().iconst(tagt, var.0 as i64);
+                Layout::StructFlat(key, [payload, Layout::direct(tag)].into())
             }
             other => unreachable!("bad field count for sum type: {other:?}"),
         };
@@ -46,6 +61,9 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
     }
 
     // Creates a payload compressed scalar from a list of values
+    //
+    // The payload has the same bytes as the parameter tuple would in memory, so that it can be
+    // read back by pointing into the sum.
     fn compress_into_inline_payload(
         &mut self,
         clty: Type,
@@ -54,29 +72,36 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
     ) -> Value {
         let fields = self.construct_record_in_regs(param_tuple, values);
         match fields.as_slice() {
-            [] | [Layout::ZST] => self.cins().iconst(clty, 0),
+            [] | [Layout::ZST] => {
+                // `iconst` doesn't support i128
+                let zero = self.cins().iconst(types::I8, 0);
+                self.resize_uint(zero, clty)
+            }
             [Layout::Scalar(_, v)] => {
-                let has = self.f.type_of_value(*v);
-                if has == types::F64 {
-                    todo!("we need to cast as well?");
-                } else {
-                    assert!(has.is_int());
-                    self.resize_uint(*v, clty)
-                }
+                let v = self.float_as_int(*v);
+                self.resize_uint(v, clty)
             }
-            [_, _] => todo!("compress multiple small variants into one sum payload"),
-            _ => panic!("cannot compress variants with 3 or more fields into inline payload"),
+            _ => {
+                let size = clty.bytes();
+                let ptr = self.construct_record_on_stack(Some(size), param_tuple, values);
+                let flags = MemFlags::new().with_notrap();
+                self.cins().load(clty, flags, ptr, 0)
+            }
+        }
+    }
+
+    fn float_as_int(&mut self, v: Value) -> Value {
+        if self.f.type_of_value(v) == types::F64 {
+            self.cins().bitcast(types::I64, MemFlags::new(), v)
+        } else {
+            v
         }
     }
 
     pub(super) fn cast_from_sum(&mut self, sum: VLayout, ty: &MonoType) -> VLayout {
         match sum {
-            // Sum types without a payload
-            Layout::StructFlat(key, params) if params.len() == 1 => {
-                assert_eq!(self.ctx.structs.get(key).fields.len(), 1);
-                assert_eq!(self.ctx.structs.size_of(ty), 0);
-                Layout::ZST
-            }
+            // Variants without a payload
+            Layout::StructFlat(..) if self.ctx.structs.size_of(ty) == 0 => Layout::ZST,
             Layout::StructFlat(key, params) => {
                 let payi = self.ctx.structs.get_real_field(key, key::Field(1));
                 let payload = &params[payi];
@@ -98,9 +123,7 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
                         Layout::SpecialPointer(kind, *ptr)
                     }
                     &Layout::Scalar(Scalar::SumPayloadInline, point) => {
-                        let clty = self.f.type_of_value(point);
-                        let fields = self.decompress_inline_payload(clty, point, ty.as_key());
-                        Layout::StructFlat(ty.as_key(), fields)
+                        self.decompress_inline_payload(point, ty.as_key())
                     }
                     _ => panic!("casting non-opaque sum payload"),
                 }
@@ -122,37 +145,33 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
         heap: bool,
         requested: &MonoType,
     ) -> VLayout {
-        match self.ctx.structs.get(key).fields.len() {
-            1 => Layout::ZST,
-            2 => {
-                let struct_ = self.ctx.structs.get(key);
+        if self.ctx.structs.size_of(requested) == 0 {
+            return Layout::ZST;
+        }
 
-                let payi = self.ctx.structs.get_real_field(key, key::Field(1));
-                let poffset = self.ctx.structs.offset_of(key, payi);
-                let flags = MemFlags::trusted();
+        let payi = self.ctx.structs.get_real_field(key, key::Field(1));
+        let poffset = self.ctx.structs.offset_of(key, payi);
 
-                match &struct_.fields[payi] {
-                    layout::StructField::SumPayloadPointer { .. } => {
-                        let size_t = self.ctx.size_t();
-                        let payload_pointer =
-                            self.cins().load(size_t, flags, ptr, poffset.0 as i32);
+        match &self.ctx.structs.get(key).fields[payi] {
+            layout::StructField::SumPayloadPointer { .. } => {
+                let size_t = self.ctx.size_t();
+                let flags = MemFlags::trusted();
+                let payload_pointer = self.cins().load(size_t, flags, ptr, poffset.0 as i32);
 
-                        let kind = if heap {
-                            SpecialPointer::HeapStruct
-                        } else {
-                            SpecialPointer::StackStruct
-                        }(requested.as_key());
+                let kind = if heap {
+                    SpecialPointer::HeapStruct
+                } else {
+                    SpecialPointer::StackStruct
+                }(requested.as_key());
 
-                        Layout::SpecialPointer(kind, payload_pointer)
-                    }
-                    &layout::StructField::SumPayloadInline(clty) => {
-                        let inline = self.cins().load(clty, flags, ptr, poffset.0 as i32);
-                        let fields =
-                            self.decompress_inline_payload(clty, inline, requested.as_key());
-                        Layout::StructFlat(requested.as_key(), fields)
-                    }
-                    _ => unreachable!(),
-                }
+                Layout::SpecialPointer(kind, payload_pointer)
+            }
+            // Inline payloads share their bytes with the parameter tuple so we can point directly
+            // into the sum.
+            layout::StructField::SumPayloadInline(_) => {
+                let ptr = self.ins().ptr_offset(ptr, poffset);
+                let kind = SpecialPointer::StackStruct(requested.as_key());
+                Layout::SpecialPointer(kind, ptr)
             }
             _ => unreachable!("invalid sum type"),
         }
@@ -160,27 +179,39 @@ impl<'c, 'a, 'f> Translator<'c, 'a, 'f> {
 
     pub(super) fn decompress_inline_payload(
         &mut self,
-        _from: Type,
         inline: Value,
         param_tuple: MonoTypeKey,
-    ) -> Map<layout::Field, VLayout> {
+    ) -> VLayout {
         let struct_ = self.ctx.structs.get(param_tuple).clone();
         match struct_.fields.as_slice() {
-            [] => Map::new(),
-            [x] => match x {
-                layout::StructField::Flat(ty) => {
-                    // TODO: perform a proper fold which would support multi-scalar decompression
-                    let layout = self.ctx.structs.type_to_layout(ty, Stability::S);
-                    let field = layout.map_layout(
-                        &mut |clty| self.resize_uint(inline, clty),
-                        &mut |_, _| unreachable!(),
-                    );
-                    [field].into()
-                }
-                _ => unreachable!(),
-            },
-
-            _ => panic!("cannot compress variants with 2 or more fields into inline payload"),
+            [] => Layout::ZST,
+            [layout::StructField::Flat(ty)]
+                if matches!(
+                    self.ctx.structs.type_to_layout(ty, Stability::S),
+                    Layout::Scalar(..)
+                ) =>
+            {
+                let layout = self.ctx.structs.type_to_layout(ty, Stability::S);
+                let field = layout.map_layout(
+                    &mut |clty| {
+                        let v = self.resize_uint(inline, clty.as_int());
+                        if clty == types::F64 {
+                            self.cins().bitcast(types::F64, MemFlags::new(), v)
+                        } else {
+                            v
+                        }
+                    },
+                    &mut |_, _| unreachable!(),
+                );
+                Layout::StructFlat(param_tuple, [field].into())
+            }
+            _ => {
+                let clty = self.f.type_of_value(inline);
+                let ptr = self.ins().stack_alloc(clty.bytes(), clty.bytes() as u8);
+                self.cins().store(MemFlags::trusted(), inline, ptr, 0);
+                let kind = SpecialPointer::StackStruct(param_tuple);
+                Layout::SpecialPointer(kind, ptr)
+            }
         }
     }
 }
@@ -2,7 +2,9 @@ use super::pat::Pattern;
 use super::ty::{TypeAnnotation, TypeLower};
 use super::{FuncLower, ToAnnotate};
 use crate::prelude::*;
-use ast::{Entity, Mod, NFunc};
+use crate::{LISTABLE_CONS, LISTABLE_FOLD_RIGHT, TO_STRING_SHOW};
+use crate::{MAPPABLE_INSERT, MAPPABLE_WITH_CAPACITY};
+use ast::{Entity, Mod, NFunc, Visibility};
 use derive_more::Display;
 use derive_more::From;
 use either::Either;
@@ -73,6 +75,7 @@ pub enum Literal<'s> {
     Int(bool, u128, Var),
     Float(f64),
     String(&'s str),
+    RawString(&'s str),
     Char(&'s str),
 }
 
@@ -97,6 +100,9 @@ impl<'t, 'a, 's> FuncLower<'t, 'a, 's> {
             }
             parser::Expr::Lit(parser::Literal::Float(n)) => Expr::Lit(Literal::Float(*n)),
             parser::Expr::Lit(parser::Literal::String(str)) => Expr::Lit(Literal::String(*str)),
+            parser::Expr::Lit(parser::Literal::RawString(str)) => {
+                Expr::Lit(Literal::RawString(str))
+            }
             parser::Expr::Lit(parser::Literal::Char(c)) => Expr::Lit(Literal::Char(*c)),
             parser::Expr::Call(apath, params) => self.callable(apath.as_ref(), params, Expr::Call),
             parser::Expr::Lambda(patterns, params, body) => {
@@ -166,10 +172,15 @@ impl<'t, 'a, 's> FuncLower<'t, 'a, 's> {
                     .map(|generic| Expr::GenericArray(elems, generic.tr(name.span)))
                     .unwrap_or(Expr::Poison)
             }
+            parser::Expr::Comprehension(elem, qualifiers) => {
+                self.comprehension(expr.span, (**elem).as_ref(), qualifiers)
+            }
+            parser::Expr::Map(entries) => self.map(expr.span, entries),
             parser::Expr::Tuple(elems) => {
                 let elems = self.exprs(elems);
                 Expr::Tuple(elems)
             }
+            parser::Expr::Interpolated(parts) => self.interpolated(parts),
             parser::Expr::Record { init, fields } => self.desugar_record(expr.span, init, fields),
             parser::Expr::If(elems) => {
                 let cond = self.expr(elems[0].as_ref());
@@ -413,15 +424,27 @@ impl<'t, 'a, 's> FuncLower<'t, 'a, 's> {
             }
         };
 
+        self.lambda_with(body.span, typing, |this| {
+            let patterns = this.patterns(apatterns);
+            let expr = this.expr(body);
+            (patterns, expr)
+        })
+    }
+
+    fn lambda_with(
+        &mut self,
+        span: Span,
+        typing: hir::Typing<IType>,
+        f: impl FnOnce(&mut Self) -> (Vec<Tr<Pattern<'s>>>, Tr<Expr<'s>>),
+    ) -> key::Lambda {
         let lkey = self
             .lambdas
-            .create_placeholder(body.span, Forall::new(0), typing);
+            .create_placeholder(span, Forall::new(0), typing);
 
         self.bindings.enter();
         self.type_info.enter_lambda(lkey, Forall::new(0));
 
-        let patterns = self.patterns(apatterns);
-        let expr = self.expr(body);
+        let (patterns, expr) = f(self);
 
         let (captures, lcaptures) = self.bindings.leave();
         let forall = self.type_info.leave_function();
@@ -689,6 +712,177 @@ impl<'t, 'a, 's> FuncLower<'t, 'a, 's> {
         }
     }
 
+    // "a {b} c"
+    // ---------
+    // ("a" <++> ToString:show b) <++> " c"
+    //
+    // The literal parts are constructed through `Stringable:from_raw_parts` like any other string
+    fn interpolated(&mut self, parts: &[Tr<parser::Expr<'s>>]) -> Expr<'s> {
+        let func =
+            |M(module, key)| Callable::Func(Mod { visibility: Visibility::Public, module, key });
+        let show = func(
+            self.pinfo
+                .to_string
+                .map(|trait_| NFunc::Method(trait_, TO_STRING_SHOW)),
+        );
+        let concat = func(self.pinfo.string_concat.map(NFunc::Key));
+
+        parts
+            .iter()
+            .map(|part| match &part.value {
+                parser::Expr::Lit(parser::Literal::String(_)) => self.expr(part.as_ref()),
+                _ => {
+                    let value = self.expr(part.as_ref());
+                    Expr::Call(show.clone(), TypeAnnotation::new(), vec![value]).tr(part.span)
+                }
+            })
+            .reduce(|left, right| {
+                let span = left.span.extend(right.span);
+                Expr::Call(concat.clone(), TypeAnnotation::new(), vec![left, right]).tr(span)
+            })
+            .map(|expr| expr.value)
+            .unwrap_or(Expr::Lit(Literal::String("")))
+    }
+
+    // [e | x <- xs, p x, let y = v]
+    // ---------
+    // Listable:fold_right #(\x acc -> if p x then let y = v in e : acc else acc) [] xs
+    fn comprehension(
+        &mut self,
+        span: Span,
+        elem: Tr<&parser::Expr<'s>>,
+        qualifiers: &[parser::Qualifier<'s>],
+    ) -> Expr<'s> {
+        let ivar = self.vars().var(span);
+        let tail = Expr::List(vec![], ivar).tr(span);
+
+        self.bindings.enter();
+        let expr = self.qualifiers(ivar, elem, qualifiers, tail);
+        self.bindings.leave();
+
+        expr.value
+    }
+
+    fn qualifiers(
+        &mut self,
+        ivar: Var,
+        elem: Tr<&parser::Expr<'s>>,
+        qualifiers: &[parser::Qualifier<'s>],
+        tail: Tr<Expr<'s>>,
+    ) -> Tr<Expr<'s>> {
+        let listable = self.pinfo.listable;
+        let method = |method| {
+            let key = NFunc::Method(listable.1, method);
+            Callable::Func(Mod { visibility: Visibility::Public, module: listable.0, key })
+        };
+
+        match qualifiers {
+            [] => {
+                let elem = self.expr(elem);
+                let span = elem.span;
+
+                // The element type can't be inferred from the implementation of `Listable`, so
+                // we annotate it to be the element type of the resulting list.
+                let mut tanot = TypeAnnotation::new();
+                let params = &self.ast.entities.traits[listable].header.type_params;
+                if let Some(name) = params.values().next() {
+                    tanot.for_type.push(((*name).tr(span), IType::infer(ivar)));
+                }
+
+                Expr::Call(method(LISTABLE_CONS), tanot, vec![elem, tail]).tr(span)
+            }
+            [parser::Qualifier::Filter(cond), rest @ ..] => {
+                let cond = self.expr(cond.as_ref());
+                let truthy = self.qualifiers(ivar, elem, rest, tail.clone());
+
+                let true_ = Pattern::Bool(true).tr(truthy.span);
+                let false_ = Pattern::Bool(false).tr(tail.span);
+                let span = cond.span;
+
+                Expr::Match(Box::new(cond), vec![(true_, truthy), (false_, tail)]).tr(span)
+            }
+            [parser::Qualifier::Let(pat, value), rest @ ..] => {
+                let value = self.expr(value.as_ref());
+                let pat = self.pat(pat.as_ref());
+                let then = self.qualifiers(ivar, elem, rest, tail);
+                let span = pat.span;
+                Expr::let_bind(value, pat, then).tr(span)
+            }
+            [parser::Qualifier::Generator(pat, source), rest @ ..] => {
+                let source = self.expr(source.as_ref());
+                let span = pat.span.extend(source.span);
+
+                let mut tvar =
+                    |span| IType::infer(self.type_info.inference_mut().unwrap().var(span)).tr(span);
+                let params = [tvar(pat.span), tvar(span)].into_iter().collect();
+                let typing = hir::Typing::new(params, tvar(span));
+
+                let lambda = self.lambda_with(span, typing, |this| {
+                    let pat = this.pat(pat.as_ref());
+                    let acc = this.bindings.declare_nameless();
+                    let acc_pat = Pattern::Bind(acc, Box::new(Pattern::Any)).tr(span);
+                    let body = this.qualifiers(ivar, elem, rest, Expr::callable(acc).tr(span));
+                    (vec![pat, acc_pat], body)
+                });
+
+                let f = Expr::Pass(lambda.into(), TypeAnnotation::new(), vec![]).tr(span);
+                let params = vec![f, tail, source];
+                Expr::Call(method(LISTABLE_FOLD_RIGHT), TypeAnnotation::new(), params).tr(span)
+            }
+        }
+    }
+
+    // { k => v, k2 => v2 }
+    // ---------
+    // Mappable:insert (Mappable:insert (Mappable:with_capacity 2) k v) k2 v2
+    fn map(
+        &mut self,
+        span: Span,
+        entries: &[(Tr<parser::Expr<'s>>, Tr<parser::Expr<'s>>)],
+    ) -> Expr<'s> {
+        let mappable = self.pinfo.mappable;
+        let method = |method| {
+            let key = NFunc::Method(mappable.1, method);
+            Callable::Func(Mod { visibility: Visibility::Public, module: mappable.0, key })
+        };
+
+        let [map, k, v] = [span; 3].map(|span| self.vars().var(span));
+
+        // Unless the map is inferred to be something else, we default to the `HashMap` from std
+        let default = IType::defined(
+            self.pinfo.global_map_default,
+            vec![IType::infer(k), IType::infer(v)],
+        );
+        self.vars().set_default(map, default);
+
+        // None of the calls can infer their type parameters on their own, so all of them are
+        // annotated to refer to the same map.
+        let mut tanot = TypeAnnotation::new();
+        tanot.for_type.push(("self".tr(span), IType::infer(map)));
+        let params = &self.ast.entities.traits[mappable].header.type_params;
+        for (name, var) in params.values().zip([k, v]) {
+            tanot.for_type.push(((*name).tr(span), IType::infer(var)));
+        }
+
+        let n = entries.len() as u128;
+        let capacity = Expr::Lit(Literal::Int(false, n, self.vars().int(span, 0, n as u64)));
+        let init = Expr::Call(
+            method(MAPPABLE_WITH_CAPACITY),
+            tanot.clone(),
+            vec![capacity.tr(span)],
+        );
+
+        entries
+            .iter()
+            .fold(init.tr(span), |map, (k, v)| {
+                let k = self.expr(k.as_ref());
+                let v = self.expr(v.as_ref());
+                let span = k.span.extend(v.span);
+                Expr::Call(method(MAPPABLE_INSERT), tanot.clone(), vec![map, k, v]).tr(span)
+            })
+            .value
+    }
+
     fn expr_boxed<const N: usize>(
         &mut self,
         elems: &[Tr<parser::Expr<'s>>; N],
@@ -19,8 +19,8 @@ use ast::{Mod, AST};
 use derive_new::new;
 use lumina_parser as parser;
 use lumina_typesystem::{
-    Constraint, Downgrade, Forall, Generic, GenericKind, IType, ImplIndex, Inference, Static, TEnv,
-    Transformer, Ty, Type,
+    Constraint, Container, Downgrade, Forall, Generic, GenericKind, GenericMapper, IType,
+    ImplIndex, Inference, Static, TEnv, Transformer, Ty, Type,
 };
 use lumina_util::{Highlighting, Identifier};
 use std::cell::RefCell;
@@ -65,6 +65,7 @@ pub struct HIR<'s> {
     pub vnames: MMap<key::Sum, Map<key::Variant, Tr<&'s str>>>,
     pub func_names: MMap<key::Func, Tr<&'s str>>,
     pub val_initializers: MMap<key::Val, M<key::Func>>,
+    pub thread_locals: HashSet<M<key::Val>>,
     pub sources: ast::Sources,
     pub lookups: ast::Lookups<'s>, // Still needed because of type-dependent lookups
     pub methods: MMap<key::Trait, Map<key::Method, key::Func>>,
@@ -163,9 +164,10 @@ pub fn run<'a, 's>(
 
         for impl_ in ast.entities.impls.iter_module(module) {
             let parts = lower_impl(info, impl_);
-            let Ok(trait_) = parts.0.value.as_trait() else {
+            let Ok(mut trait_) = parts.0.value.as_trait() else {
                 todo!();
             };
+            include_associations(&ast, &assoc[trait_.0], &parts.2, &parts.3, &mut trait_);
             impls.push_as(impl_, parts.1);
             impltors.push_as(impl_, parts.2);
             iassoc.push_as(impl_, parts.3);
@@ -178,7 +180,7 @@ pub fn run<'a, 's>(
         let info = Info { langitems: &langitems[module], ..info };
 
         ast.entities.fheaders.iter_module(module).for_each(|func| {
-            let (fdef, tenv) = lower_func(info, &traits, &impls, &impltors, func);
+            let (fdef, tenv) = lower_func(info, &traits, &impls, &impltors, &itraits, func);
             funcs.push_as(func, fdef);
             tenvs.push_as(func, tenv);
         });
@@ -190,7 +192,8 @@ pub fn run<'a, 's>(
         for ikey in impls.iter_module(module) {
             let impltor = &impltors[ikey];
             let trait_ = itraits[ikey].0;
-            iquery.insert(trait_, &impltor, ikey);
+            let default = ast.entities.impls[ikey].header.default;
+            iquery.insert(trait_, &impltor, ikey, default);
         }
     }
 
@@ -206,6 +209,7 @@ pub fn run<'a, 's>(
         HIR {
             fnames: ast.entities.field_names,
             val_initializers: ast.entities.vals,
+            thread_locals: ast.entities.thread_locals,
             func_names,
             assoc_names,
             sources: ast.sources,
@@ -286,6 +290,7 @@ pub struct FuncLower<'t, 'a, 's> {
     where_binds: &'a [parser::func::Declaration<'s>],
 
     pub target: Target,
+    pub pinfo: &'a ProjectInfo,
 
     #[new(default)]
     pub bindings: Bindings<'s>,
@@ -298,12 +303,14 @@ fn lower_func<'a, 's>(
     tforalls: &MMap<key::Trait, (Tr<&'s str>, Forall<'s, Static>)>,
     iforalls: &MMap<key::Impl, Forall<'s, Static>>,
     impltors: &MMap<key::Impl, Tr<Type>>,
+    itraits: &MMap<key::Impl, (M<key::Trait>, Vec<Type>)>,
     func: M<key::Func>,
 ) -> (FuncDefKind<'s>, TEnv<'s>) {
     let module = func.0;
     let header = &ast.entities.fheaders[func];
     let attributes = &ast.entities.fattributes[func];
     let no_mangle = attributes.no_mangle;
+    let inline = attributes.inline;
 
     let _span = info_span!(
         "lowering func",
@@ -329,16 +336,19 @@ fn lower_func<'a, 's>(
         }
         ast::FuncBody::Val(body, _) | ast::FuncBody::Func(body) => {
             let mut tinfo = tinfo.inference(TEnv::new());
-            let (fdef, env) = FuncLower::new(module, ast, &mut tinfo, &body.where_binds, target)
-                .lower_func(&header, &body, no_mangle);
+            let (fdef, env) =
+                FuncLower::new(module, ast, &mut tinfo, &body.where_binds, target, pinfo)
+                    .lower_func(header, body, no_mangle, inline);
             (FuncDefKind::Defined(fdef), env)
         }
         ast::FuncBody::TraitMethod(Some(body), tr) => {
             let mut tinfo = tinfo.inference(TEnv::new());
             tinfo.enter_type_or_impl_or_method(tforalls[*tr].1.clone(), GenericKind::Parent);
             tinfo.self_handler = SelfHandler::Direct;
-            let (fdef, env) = FuncLower::new(module, ast, &mut tinfo, &body.where_binds, target)
-                .lower_func(&header, &body, no_mangle);
+            tinfo.associated = trait_associations(ast, *tr);
+            let (fdef, env) =
+                FuncLower::new(module, ast, &mut tinfo, &body.where_binds, target, pinfo)
+                    .lower_func(header, body, no_mangle, inline);
 
             let kind = disallow_inference_in_trait_default(module, ast, *tr, fdef);
             (kind, env)
@@ -350,12 +360,15 @@ fn lower_func<'a, 's>(
             let mut tinfo = tinfo.inference(env);
             tinfo.enter_type_or_impl_or_method(iforalls[*imp].clone(), GenericKind::Parent);
             tinfo.self_handler = SelfHandler::Direct;
-            let (fdef, env) = FuncLower::new(module, ast, &mut tinfo, &body.where_binds, target)
-                .lower_func(&header, &body, no_mangle);
+            tinfo.associated = impl_associations(ast, &itraits[*imp]);
+            let (fdef, env) =
+                FuncLower::new(module, ast, &mut tinfo, &body.where_binds, target, pinfo)
+                    .lower_func(header, body, no_mangle, inline);
             (FuncDefKind::ImplMethod(*imp, fdef), env)
         }
         ast::FuncBody::TraitMethod(None, trait_) => {
             tinfo.self_handler = SelfHandler::Direct;
+            tinfo.associated = trait_associations(ast, *trait_);
             let tforall = tforalls[*trait_].clone().1;
             tinfo.enter_type_or_impl_or_method(tforall, GenericKind::Parent);
             tinfo.enter_type_or_impl_or_method(Forall::new(0), GenericKind::Entity);
@@ -480,9 +493,13 @@ fn lower_trait<'a, 's>(
     let list = list_from_langs(&tlangs, langitems, pinfo);
     let string = pinfo.string.map(key::TypeKind::Record);
     let mut tinfo = TypeEnvInfo::new(false, string, list);
-    let forall = Forall::from_names(ty.header.type_params.values().copied());
+    let mut forall = Forall::from_names(ty.header.type_params.values().copied());
+    for assoc in ast.entities.associated_types[trait_].values() {
+        forall.push(assoc.name);
+    }
     tinfo.enter_type_or_impl_or_method(forall, GenericKind::Parent);
     tinfo.self_handler = SelfHandler::Direct;
+    tinfo.associated = trait_associations(ast, trait_);
 
     tinfo.declare_generics = true;
 
@@ -500,6 +517,63 @@ fn lower_trait<'a, 's>(
     (associations, tinfo.leave_type_or_impl_or_method())
 }
 
+/// Associated types are represented as trailing parameters of the trait, so inside the trait
+/// `self:Name` refers to those generics.
+fn trait_associations<'s>(ast: &AST<'s>, trait_: M<key::Trait>) -> Vec<(&'s str, Type)> {
+    let n = ast.entities.traits[trait_].header.type_params.len();
+
+    ast.entities.associated_types[trait_]
+        .values()
+        .enumerate()
+        .map(|(i, assoc)| {
+            let generic = Generic::new(key::Generic((n + i) as u32), GenericKind::Parent);
+            (assoc.name, Ty::Generic(generic))
+        })
+        .collect()
+}
+
+fn impl_associations<'s>(
+    ast: &AST<'s>,
+    (trait_, params): &(M<key::Trait>, Vec<Type>),
+) -> Vec<(&'s str, Type)> {
+    let n = ast.entities.traits[*trait_].header.type_params.len();
+
+    ast.entities.associated_types[*trait_]
+        .values()
+        .zip(&params[n..])
+        .map(|(assoc, ty)| (assoc.name, ty.clone()))
+        .collect()
+}
+
+/// Append the associated types assigned by an implementation to its trait parameters, falling
+/// back to the trait's defaults.
+///
+/// Missing associations are poisoned here and reported when verifying the implementation.
+fn include_associations<'s>(
+    ast: &AST<'s>,
+    defaults: &Map<key::AssociatedType, Option<Tr<Type>>>,
+    impltor: &Type,
+    assigned: &[(Tr<&'s str>, Tr<Type>)],
+    (trait_, params): &mut (M<key::Trait>, Vec<Type>),
+) {
+    let mut tinst = GenericMapper::from_types(GenericKind::Parent, params.iter().cloned());
+    tinst.self_ = Some(impltor.clone());
+
+    for (assoc, decl) in ast.entities.associated_types[*trait_].iter() {
+        let ty = match assigned.iter().find(|(name, _)| **name == decl.name) {
+            Some((_, ty)) => ty.value.clone(),
+            None => match &defaults[assoc] {
+                Some(ty) => (&tinst).transform(ty),
+                None => Ty::poison(),
+            },
+        };
+
+        let generic = Generic::new(key::Generic(params.len() as u32), GenericKind::Parent);
+        tinst.push(generic, ty.clone());
+        params.push(ty);
+    }
+}
+
 // TODO: we ended up needing more of the ones containing `FuncDef` than we thought. It's probably a
 // good idea to split out those tags into a separate enum instead.
 #[derive(Debug)]
@@ -511,7 +585,6 @@ pub enum FuncDefKind<'s> {
     Defined(FuncDef<'s>),
     TraitDefaultMethod(M<key::Trait>, Forall<'s, Static>, Typing<Type>, FuncDef<'s>),
     ImplMethod(M<key::Impl>, FuncDef<'s>),
-    InheritedDefault(M<key::Trait>, key::Func),
     TraitHeader(M<key::Trait>, Forall<'s, Static>, Typing<Type>),
 }
 
@@ -537,6 +610,9 @@ pub struct FuncDef<'s> {
 
     pub no_mangle: bool,
 
+    #[new(default)]
+    pub inline: Option<ast::Inline>,
+
     #[new(default)]
     pub lambdas: Lambdas<'s>,
 }
@@ -663,6 +739,7 @@ impl<'t, 'a, 's> FuncLower<'t, 'a, 's> {
         header: &parser::func::Header<'s>,
         body: &parser::func::Body<'s>,
         no_mangle: bool,
+        inline: Option<ast::Inline>,
     ) -> (FuncDef<'s>, TEnv<'s>) {
         let forall = generics_from_con(&header.when);
         self.type_info.enter_function(forall);
@@ -695,6 +772,7 @@ impl<'t, 'a, 's> FuncLower<'t, 'a, 's> {
         let list = self.type_info.list;
 
         let mut func = FuncDef::new(RefCell::new(forall), typing, list, params, expr, no_mangle);
+        func.inline = inline;
         func.lambdas = self.lambdas;
 
         // Copy the captures of where-bindings to lambdas which use them
@@ -953,9 +1031,14 @@ fn lower_impl<'a, 's>(
 
     let mut tlower = ty::TypeLower::new(module, ast, target.int_size(), &mut tinfo);
 
+    tlower.type_info.constructor = true;
     let impltor = tlower.ty_spanned(imp.header.impltor.as_ref());
     let trait_ = tlower.ty_spanned(imp.header.trait_.as_ref());
 
+    if let Ok((trkey, _)) = trait_.value.clone().as_trait() {
+        check_impltor_arity(ast, module, &impltor, trkey);
+    }
+
     tinfo.self_handler = SelfHandler::Direct;
 
     include_constraints(
@@ -985,6 +1068,64 @@ fn lower_impl<'a, 's>(
     (trait_, forall, impltor, associations)
 }
 
+/// Traits which apply `self` to type parameters are implemented for type constructors, so the
+/// implementor needs to be missing exactly that many type parameters.
+fn check_impltor_arity(ast: &AST, module: key::Module, impltor: &Tr<Type>, trait_: M<key::Trait>) {
+    let missing = match &impltor.value {
+        Ty::Container(Container::App, params) => match params.as_slice() {
+            [Ty::Container(Container::Defined(kind, _), params)] => {
+                ast.entities.header_of_ty(*kind).header.type_params.len() - params.len()
+            }
+            _ => 0,
+        },
+        _ => 0,
+    };
+
+    let arity = ast.entities.methods[trait_]
+        .values()
+        .filter_map(|func| ast.entities.fheaders[func.inside(trait_.0)].typing.as_ref())
+        .flat_map(|typing| typing.ptypes.iter().chain(std::iter::once(&typing.returns)))
+        .filter_map(|ty| self_arity(ty))
+        .max()
+        .unwrap_or(0);
+
+    if missing != arity {
+        ast.sources
+            .error("invalid implementor")
+            .m(module)
+            .eline(
+                impltor.span,
+                format!(
+                    "expected a type missing {arity} type parameters, but {missing} are missing"
+                ),
+            )
+            .text(format!(
+                "`self` is applied to {arity} type parameters in the methods of `{}`",
+                ast.entities.traits[trait_].header.name
+            ))
+            .emit();
+    }
+}
+
+// The amount of type parameters `self` is applied to in a type signature
+fn self_arity(ty: &parser::Type) -> Option<usize> {
+    match ty {
+        parser::Type::Defined(apath, params) if apath.path.as_slice() == ["self"] => {
+            Some(params.len())
+        }
+        parser::Type::Defined(_, params)
+        | parser::Type::Tuple(params)
+        | parser::Type::List(params, _) => params.iter().filter_map(|ty| self_arity(ty)).max(),
+        parser::Type::Closure(params, ret) | parser::Type::FnPointer(params, ret) => params
+            .iter()
+            .chain(std::iter::once(&**ret))
+            .filter_map(|ty| self_arity(ty))
+            .max(),
+        parser::Type::Pointer(inner) => self_arity(inner),
+        parser::Type::Poison => None,
+    }
+}
+
 enum ToAnnotate {
     Some(Option<M<key::TypeKind>>),
     None,
@@ -1058,7 +1199,17 @@ fn include_constraints<'s, T: ty::FromVar>(
                     ty::TypeLower::new(module, ast, default_int_size, tinfo).ty(ty.as_ref());
 
                 match con_ty.as_trait() {
-                    Ok((trait_, params)) => Some(Constraint { span: ty.span, trait_, params }),
+                    Ok((trait_, mut params)) => {
+                        // Each associated type of the trait becomes an implicit generic. They're
+                        // anonymous since different traits may share the names of their associated
+                        // types, and are instead reached through the constraint.
+                        for _ in ast.entities.associated_types[trait_].values() {
+                            let generic = tinfo.declare_anonymous_generic().unwrap();
+                            params.push(Ty::Generic(generic));
+                        }
+
+                        Some(Constraint { span: ty.span, trait_, params })
+                    }
                     Err(_) => {
                         ast.sources
                             .error("invalid constraint")
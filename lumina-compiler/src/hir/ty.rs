@@ -3,7 +3,8 @@ use ast::Entity;
 use lumina_parser as parser;
 use lumina_parser::func::{Header as FuncHeader, Typing as ParserTyping};
 use lumina_typesystem::{
-    ConstGeneric, Forall, Generic, GenericKind, IType, Inference, IntSize, Static, TEnv, Ty, Var,
+    ConstGeneric, Constraint, Downgrade, Forall, Generic, GenericKind, IType, Inference, IntSize,
+    Static, TEnv, Transformer, Ty, Type, Var,
 };
 use lumina_util::{Spanned, Tr};
 use smallvec::SmallVec;
@@ -25,6 +26,10 @@ pub struct TypeEnvInfo<'s> {
     pub list: M<key::TypeKind>,
     pub string: M<key::TypeKind>,
     pub self_handler: SelfHandler,
+    /// Associated types reachable through `self:Name`
+    pub associated: Vec<(&'s str, Type)>,
+    /// Whether the next type lowered may be a type constructor missing trailing type parameters
+    pub constructor: bool,
     inference: Option<TEnv<'s>>,
 }
 
@@ -36,18 +41,25 @@ pub enum SelfHandler {
 
 pub trait FromVar: Sized + Clone {
     fn var(var: Var) -> Ty<Self>;
+    fn from_static(ty: &Type) -> Ty<Self>;
 }
 
 impl FromVar for Inference {
     fn var(var: Var) -> Ty<Self> {
         Ty::infer(var)
     }
+    fn from_static(ty: &Type) -> Ty<Self> {
+        Downgrade.transform(ty)
+    }
 }
 
 impl FromVar for Static {
     fn var(_: Var) -> Ty<Self> {
         unreachable!();
     }
+    fn from_static(ty: &Type) -> Ty<Self> {
+        ty.clone()
+    }
 }
 
 impl<'s> TypeEnvInfo<'s> {
@@ -57,6 +69,8 @@ impl<'s> TypeEnvInfo<'s> {
             string,
             declare_generics,
             self_handler: SelfHandler::Disallowed,
+            associated: vec![],
+            constructor: false,
             iforalls: SmallVec::new(),
             cforalls: SmallVec::new(),
             inference: None,
@@ -100,17 +114,31 @@ impl<'s> TypeEnvInfo<'s> {
         self.iforalls.pop().unwrap().0
     }
 
-    pub fn declare_generic(&mut self, name: &'s str, con: Option<ConstGeneric>) -> Option<Generic> {
+    /// Declare a generic which can't be referred to by name
+    ///
+    /// `_` is always parsed as type inference, so the name can't collide with the user's generics.
+    pub fn declare_anonymous_generic(&mut self) -> Option<Generic> {
+        self.declare_generic("_", None, 0)
+    }
+
+    pub fn declare_generic(
+        &mut self,
+        name: &'s str,
+        con: Option<ConstGeneric>,
+        arity: usize,
+    ) -> Option<Generic> {
         match self.iforalls.last_mut() {
             Some((forall, kind)) => {
                 let gkey = forall.push(name);
                 forall[gkey].const_ = con;
+                forall[gkey].arity = Some(arity);
                 let generic = Generic::new(gkey, *kind);
                 return Some(generic);
             }
             None => {
                 if let Some((forall, kind)) = self.cforalls.last_mut() {
                     let gkey = forall.push(name);
+                    forall[gkey].arity = Some(arity);
                     let generic = Generic::new(gkey, *kind);
                     return Some(generic);
                 }
@@ -188,6 +216,8 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
     pub fn ty<T: FromVar>(&mut self, ty: Tr<&parser::Type<'s>>) -> Ty<T> {
         trace!("lowering type {ty}");
 
+        let constructor = std::mem::take(&mut self.type_info.constructor);
+
         match *ty {
             parser::Type::Closure(ptypes, returns) => {
                 let ptypes = self.tys(ptypes);
@@ -230,7 +260,9 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
                 }
             }
             parser::Type::Poison => Ty::poison(),
-            parser::Type::Defined(path, params) => self.defined(path.tr(ty.span), params),
+            parser::Type::Defined(path, params) => {
+                self.defined(path.tr(ty.span), params, constructor)
+            }
         }
     }
 
@@ -266,6 +298,7 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
         &mut self,
         apath: Tr<&parser::AnnotatedPath<'s>>,
         params: &[Tr<parser::Type<'s>>],
+        constructor: bool,
     ) -> Ty<T> {
         if !apath.for_segments.is_empty() {
             self.ast.sources
@@ -321,7 +354,10 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
                     let params = forall.to_types(*gkind);
                     return Ty::defined(kind, params);
                 }
-                SelfHandler::Direct => return Ty::Simple("self"),
+                SelfHandler::Direct => {
+                    let params = self.tys(params);
+                    return Ty::app(Ty::Simple("self"), params);
+                }
                 SelfHandler::Disallowed => {
                     self.ast
                         .sources
@@ -354,7 +390,8 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
             }
             [name] => match self.try_generic(name, params.len(), None) {
                 Ok(gen) => {
-                    return self.forbid_params(span, Ty::Generic(gen), params);
+                    let params = self.tys(params);
+                    return Ty::app(Ty::Generic(gen), params);
                 }
                 Err(GenericError::InconsistentHKT(exp)) => {
                     self.emit_inconsistent_hkt(span, exp, params.len());
@@ -362,6 +399,39 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
                 }
                 Err(GenericError::NotFound) => {}
             },
+            ["self", name] => {
+                if let SelfHandler::Disallowed = self.type_info.self_handler {
+                    self.ast
+                        .sources
+                        .error("invalid type")
+                        .m(self.module)
+                        .eline(
+                            span,
+                            "`self` is only allowed in type and implementation declarations",
+                        )
+                        .emit();
+
+                    return Ty::poison();
+                }
+
+                let ty = match self.type_info.associated.iter().find(|(n, _)| n == name) {
+                    Some((_, ty)) => T::from_static(ty),
+                    None => {
+                        self.emit_associated_not_found(span, "`self`", name);
+                        return Ty::poison();
+                    }
+                };
+
+                return self.forbid_params(span, ty, params);
+            }
+            [generic, name] => match self.find_generic_associated(generic, name) {
+                Some(Some(gen)) => return self.forbid_params(span, Ty::Generic(gen), params),
+                Some(None) => {
+                    self.emit_associated_not_found(span, generic, name);
+                    return Ty::poison();
+                }
+                None => {}
+            },
             _ => {}
         }
 
@@ -371,8 +441,18 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
                 Entity::Type(tkey) => {
                     let key = M(entity.module, tkey);
                     let mut tparams = self.tys(params);
-                    let expected_type_params =
-                        &self.ast.entities.header_of_ty(key).header.type_params;
+                    let mut expected_type_params =
+                        self.ast.entities.header_of_ty(key).header.type_params.len();
+
+                    // Trait objects also take the trait's associated types as trailing
+                    // parameters, while constraints and implementations leave them out.
+                    if let key::TypeKind::Trait(trait_) = tkey {
+                        let associated =
+                            self.ast.entities.associated_types[trait_.inside(key.0)].len();
+                        if params.len() == expected_type_params + associated {
+                            expected_type_params += associated;
+                        }
+                    }
 
                     let err = |span, msg| {
                         self.ast
@@ -385,14 +465,17 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
 
                     let header = || &self.ast.entities.header_of_ty(key).header;
 
-                    match (params.len(), expected_type_params.len()) {
+                    match (params.len(), expected_type_params) {
+                        (x, y) if x < y && constructor => {
+                            return Ty::constructor(Ty::defined(key, tparams));
+                        }
                         (x, y) if x < y => {
                             err(
                                 span,
                                 format!("missing {} type parameters for `{}`", y - x, header()),
                             );
 
-                            while tparams.len() != expected_type_params.len() {
+                            while tparams.len() != expected_type_params {
                                 tparams.push(Ty::poison());
                             }
                         }
@@ -402,7 +485,7 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
                                 format!("excess parameter for {}", header()),
                             );
 
-                            tparams.truncate(expected_type_params.len());
+                            tparams.truncate(expected_type_params);
                         }
                         (_, _) => {}
                     }
@@ -461,6 +544,50 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
             .emit();
     }
 
+    fn emit_associated_not_found(&self, span: Span, of: &str, name: &str) {
+        self.ast
+            .sources
+            .error("associated type not found")
+            .m(self.module)
+            .eline(span, format!("{of} has no associated type named `{name}`"))
+            .emit();
+    }
+
+    /// Find the associated type of a generic by looking through its trait constraints
+    ///
+    /// Returns `None` if there's no generic in scope with that name
+    fn find_generic_associated(&self, generic: &str, name: &str) -> Option<Option<Generic>> {
+        for (forall, _) in self.type_info.iforalls.iter().rev() {
+            if let Some(key) = forall.find(generic) {
+                return Some(self.associated_param(&forall[key].trait_constraints, name));
+            }
+        }
+
+        for (forall, _) in self.type_info.cforalls.iter().rev() {
+            if let Some(key) = forall.find(generic) {
+                return Some(self.associated_param(&forall[key].trait_constraints, name));
+            }
+        }
+
+        None
+    }
+
+    fn associated_param<U>(&self, constraints: &[Constraint<U>], name: &str) -> Option<Generic> {
+        constraints.iter().find_map(|con| {
+            let associations = &self.ast.entities.associated_types[con.trait_];
+            let i = associations.values().position(|assoc| assoc.name == name)?;
+            let n = self.ast.entities.traits[con.trait_]
+                .header
+                .type_params
+                .len();
+            match con.params.get(n + i)? {
+                Ty::Generic(generic) => Some(*generic),
+                _ => None,
+            }
+        })
+    }
+
+    /// Find a generic in scope, inferring its arity from the first time it's applied
     pub fn try_generic(
         &mut self,
         name: &'s str,
@@ -469,33 +596,37 @@ impl<'t, 'a, 's> TypeLower<'t, 'a, 's> {
     ) -> Result<Generic, GenericError> {
         for (forall, kind) in self.type_info.iforalls.iter_mut().rev() {
             if let Some(generic) = forall.find(name) {
-                return if plen != 0 {
-                    Err(GenericError::InconsistentHKT(plen))
-                } else {
-                    Ok(Generic::new(generic, *kind))
-                };
+                return Self::check_arity(&mut forall[generic].arity, plen)
+                    .map(|_| Generic::new(generic, *kind));
             }
         }
 
         for (forall, kind) in self.type_info.cforalls.iter_mut().rev() {
             if let Some(generic) = forall.find(name) {
-                return if plen != 0 {
-                    Err(GenericError::InconsistentHKT(plen))
-                } else {
-                    Ok(Generic::new(generic, *kind))
-                };
+                return Self::check_arity(&mut forall[generic].arity, plen)
+                    .map(|_| Generic::new(generic, *kind));
             }
         }
 
         // Implicitly declare if able
-        if name.len() == 1 && self.type_info.declare_generics && plen == 0 {
-            if let Some(generic) = self.type_info.declare_generic(name, const_) {
+        if name.len() == 1 && self.type_info.declare_generics {
+            if let Some(generic) = self.type_info.declare_generic(name, const_, plen) {
                 return Ok(generic);
             }
         }
 
         Err(GenericError::NotFound)
     }
+
+    fn check_arity(arity: &mut Option<usize>, plen: usize) -> Result<(), GenericError> {
+        match *arity {
+            Some(n) if n != plen => Err(GenericError::InconsistentHKT(n)),
+            _ => {
+                *arity = Some(plen);
+                Ok(())
+            }
+        }
+    }
 }
 
 pub enum GenericError {
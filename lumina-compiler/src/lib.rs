@@ -5,6 +5,8 @@ pub mod hir;
 pub mod lir;
 pub mod mir;
 mod prelude;
+pub mod profile;
+pub use profile::{OptLevel, Pass, Profile};
 pub mod target;
 pub use target::Target;
 
@@ -18,9 +20,15 @@ pub const LISTABLE_CONS: key::Method = key::Method(0);
 pub const LISTABLE_NEW: key::Method = key::Method(1);
 pub const LISTABLE_WITH_CAPACITY: key::Method = key::Method(2);
 pub const LISTABLE_SPLIT: key::Method = key::Method(3);
+pub const LISTABLE_FOLD_RIGHT: key::Method = key::Method(4);
+
+pub const MAPPABLE_WITH_CAPACITY: key::Method = key::Method(0);
+pub const MAPPABLE_INSERT: key::Method = key::Method(1);
 
 pub const STRINGABLE_FROM_RAW_PARTS: key::Method = key::Method(4);
 
+pub const TO_STRING_SHOW: key::Method = key::Method(0);
+
 pub const LIST_CONCAT: key::Variant = key::Variant(1);
 pub const LIST_SINGLETON: key::Variant = key::Variant(2);
 pub const LIST_NIL: key::Variant = key::Variant(3);
@@ -33,6 +41,18 @@ pub const CLOSURE_CAPTURES: key::Param = key::Param(0);
 
 pub const SIZE_OF: key::Method = key::Method(0);
 
+pub const TYPEINFO_INT: key::Variant = key::Variant(0);
+pub const TYPEINFO_FLOAT: key::Variant = key::Variant(1);
+pub const TYPEINFO_BOOL: key::Variant = key::Variant(2);
+pub const TYPEINFO_POINTER: key::Variant = key::Variant(3);
+pub const TYPEINFO_FN_POINTER: key::Variant = key::Variant(4);
+pub const TYPEINFO_ARRAY: key::Variant = key::Variant(5);
+pub const TYPEINFO_RECORD: key::Variant = key::Variant(6);
+pub const TYPEINFO_SUM: key::Variant = key::Variant(7);
+pub const TYPEINFO_TUPLE: key::Variant = key::Variant(8);
+pub const TYPEINFO_OBJECT: key::Variant = key::Variant(9);
+pub const TYPEINFO_OTHER: key::Variant = key::Variant(10);
+
 pub const TRAIT_OBJECT_DATA_FIELD: key::Field = key::Field(0);
 pub const VTABLE_FIELD: key::Field = key::Field(1);
 
@@ -42,10 +62,14 @@ pub struct ProjectInfo {
     sys_init: M<key::Func>,
     closure: M<key::Trait>,
     allocator: (M<key::Func>, M<key::Func>),
-    reflect_type: M<key::Trait>,
+    reflect_type: M<key::Sum>,
     listable: M<key::Trait>,
     global_list_default: M<key::TypeKind>,
+    mappable: M<key::Trait>,
+    global_map_default: M<key::TypeKind>,
     stringable: M<key::Trait>,
+    to_string: M<key::Trait>,
+    string_concat: M<key::Func>,
     string: M<key::Record>,
     maybe: M<key::Sum>,
 }
@@ -0,0 +1,343 @@
+//! Compile-time evaluation of `val` initialisers
+//!
+//! Initialisers which are pure are interpreted after optimisation so that the backend can emit
+//! their result as initialised data instead of running them at startup. Anything we can't
+//! reason about (FFI, heap memory, traps) makes the evaluation bail, and the val falls back to
+//! being initialised at runtime.
+
+use super::{BinOp, Block, Entry, MonoFunc, MonoType, MonoTypeData, Value, LIR, V};
+use crate::prelude::*;
+use lumina_typesystem::IntSize;
+use std::cmp::Ordering;
+
+/// A value known at compile time
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+    Int(i128, IntSize),
+    Float(f64),
+    ReadOnly(M<key::ReadOnly>),
+    FuncPtr(MonoFunc),
+    ExternFuncPtr(M<key::Func>),
+    ValPtr(M<key::Val>),
+    /// Records, tuples, trait objects and arrays
+    Aggregate(Vec<Constant>),
+    Variant(key::Variant, Vec<Constant>),
+}
+
+/// Amount of entries a single val initialiser may evaluate before we give up
+const FUEL: u32 = 1_000_000;
+const MAX_DEPTH: u32 = 256;
+const MAX_REPLICATE: u64 = 1 << 16;
+
+struct Interpreter<'a> {
+    lir: &'a LIR,
+    evaluated: HashMap<M<key::Val>, Option<Constant>>,
+    fuel: u32,
+}
+
+impl LIR {
+    /// Evaluate all val initialisers which can be evaluated at compile-time
+    pub(super) fn evaluate_vals(&self) -> HashMap<M<key::Val>, Constant> {
+        let mut interpreter = Interpreter { lir: self, evaluated: HashMap::new(), fuel: FUEL };
+
+        for &val in self.val_initialisers.keys() {
+            interpreter.val(val, 0);
+        }
+
+        interpreter
+            .evaluated
+            .into_iter()
+            .filter_map(|(val, c)| c.map(|c| (val, c)))
+            .collect()
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    fn val(&mut self, val: M<key::Val>, depth: u32) -> Option<Constant> {
+        if let Some(c) = self.evaluated.get(&val) {
+            return c.clone();
+        }
+
+        // Inserted before evaluation so that cyclic vals bail instead of recursing
+        self.evaluated.insert(val, None);
+
+        let outer_fuel = std::mem::replace(&mut self.fuel, FUEL);
+        let mfunc = self.lir.val_initialisers[&val];
+        let c = self.call(mfunc, vec![], depth);
+        self.fuel = outer_fuel;
+
+        self.evaluated.insert(val, c.clone());
+        c
+    }
+
+    fn call(
+        &mut self,
+        mut mfunc: MonoFunc,
+        mut params: Vec<Constant>,
+        depth: u32,
+    ) -> Option<Constant> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        'func: loop {
+            let ssa = &self.lir.functions[mfunc].ssa;
+            let mut env = HashMap::new();
+            let mut block = Block::entry();
+
+            loop {
+                let info = ssa.block_info(block);
+
+                if params.len() != info.params as usize {
+                    return None;
+                }
+
+                for (i, p) in params.drain(..).enumerate() {
+                    env.insert(V(info.start.0 + i as u32), p);
+                }
+
+                for v in (info.start.0 + info.params..info.end.0).map(V) {
+                    self.fuel = self.fuel.checked_sub(1)?;
+                    let c = self.entry(&env, ssa.entry_of(v), ssa.type_of(v), depth)?;
+                    env.insert(v, c);
+                }
+
+                match info.tail {
+                    Entry::Return(v) => return self.value(&env, *v),
+                    Entry::JmpBlock(jump) => {
+                        block = jump.id;
+                        params = self.values(&env, &jump.params)?;
+                    }
+                    Entry::Select { value, on_true, on_false } => {
+                        let jump = if self.int(&env, *value)? != 0 {
+                            on_true
+                        } else {
+                            on_false
+                        };
+                        block = jump.id;
+                        params = self.values(&env, &jump.params)?;
+                    }
+                    Entry::JmpTable(v, blocks) => {
+                        let n = usize::try_from(self.int(&env, *v)?).ok()?;
+                        block = *blocks.get(n)?;
+                    }
+                    Entry::JmpFunc(f, ps) => {
+                        params = self.values(&env, ps)?;
+                        mfunc = *f;
+                        continue 'func;
+                    }
+                    _ => return None,
+                }
+            }
+        }
+    }
+
+    fn entry(
+        &mut self,
+        env: &HashMap<V, Constant>,
+        entry: &Entry,
+        ty: &MonoType,
+        depth: u32,
+    ) -> Option<Constant> {
+        match entry {
+            Entry::CallStatic(mfunc, params) => {
+                let params = self.values(env, params)?;
+                self.call(*mfunc, params, depth + 1)
+            }
+            Entry::CallValue(f, params) => match self.value(env, *f)? {
+                Constant::FuncPtr(mfunc) => {
+                    let params = self.values(env, params)?;
+                    self.call(mfunc, params, depth + 1)
+                }
+                _ => None,
+            },
+            Entry::Construct(params) => self.values(env, params).map(Constant::Aggregate),
+            Entry::Replicate(v, times) if *times <= MAX_REPLICATE => {
+                let c = self.value(env, *v)?;
+                Some(Constant::Aggregate(vec![c; *times as usize]))
+            }
+            Entry::Variant(var, params) => {
+                let params = self.values(env, params)?;
+                Some(Constant::Variant(*var, params))
+            }
+            Entry::RefStaticVal(val) => Some(Constant::ValPtr(*val)),
+            Entry::Field { of, field, .. } => match self.value(env, *of)? {
+                Constant::Aggregate(fields) => fields.get(field.0 as usize).cloned(),
+                _ => None,
+            },
+            Entry::CastFromSum { of } => match self.value(env, *of)? {
+                Constant::Variant(_, params) => Some(Constant::Aggregate(params)),
+                _ => None,
+            },
+            Entry::TagFromSum { of } => match (self.value(env, *of)?, ty) {
+                (Constant::Variant(var, _), MonoType::Int(size)) => {
+                    Some(Constant::Int(wrap(var.0 as i128, *size), *size))
+                }
+                _ => None,
+            },
+            Entry::Indice { of, indice } => match self.value(env, *of)? {
+                Constant::Aggregate(elems) => {
+                    let i = usize::try_from(self.int(env, *indice)?).ok()?;
+                    elems.get(i).cloned()
+                }
+                _ => None,
+            },
+            Entry::BinOp(op, [lhs, rhs]) => {
+                let lhs = self.int(env, *lhs)?;
+                let rhs = self.int(env, *rhs)?;
+                self.binop(*op, lhs, rhs, ty)
+            }
+            Entry::IntCmpInclusive([lhs, rhs], ord, size) => {
+                let holds = int_cmp(self.int(env, *lhs)?, self.int(env, *rhs)?, *ord, *size);
+                Some(Constant::Int(holds as i128, IntSize::new(false, 8)))
+            }
+            Entry::IntAbs(v) => {
+                let (n, size) = self.sized_int(env, *v)?;
+                let n = wrap(n, IntSize::new(true, size.bits()));
+                Some(Constant::Int(wrap(n.abs(), size), size))
+            }
+            Entry::Reduce(v) | Entry::ExtendSigned(v) | Entry::ExtendUnsigned(v) => {
+                let (n, from) = self.sized_int(env, *v)?;
+                let MonoType::Int(to) = ty else { return None };
+                let n = match entry {
+                    Entry::ExtendSigned(_) => wrap(n, IntSize::new(true, from.bits())),
+                    Entry::ExtendUnsigned(_) => wrap(n, IntSize::new(false, from.bits())),
+                    _ => n,
+                };
+                Some(Constant::Int(wrap(n, *to), *to))
+            }
+            Entry::Transmute(v) => match (self.value(env, *v)?, ty) {
+                (Constant::Int(n, _), MonoType::Int(size)) => {
+                    Some(Constant::Int(wrap(n, *size), *size))
+                }
+                (Constant::Int(n, _), MonoType::Pointer(_) | MonoType::FnPointer(..)) => {
+                    let size = IntSize::new(false, self.lir.mono.types.pointer_bits as u8);
+                    Some(Constant::Int(wrap(n, size), size))
+                }
+                (
+                    c @ (Constant::ReadOnly(_)
+                    | Constant::FuncPtr(_)
+                    | Constant::ExternFuncPtr(_)
+                    | Constant::ValPtr(_)),
+                    MonoType::Pointer(_) | MonoType::FnPointer(..),
+                ) => Some(c),
+                _ => None,
+            },
+            Entry::IntToFloat(v, size) => {
+                let n = wrap(self.int(env, *v)?, *size);
+                Some(Constant::Float(n as f64))
+            }
+            Entry::FloatToInt(v, size) => match self.value(env, *v)? {
+                Constant::Float(f) => {
+                    let n = (f as i128).clamp(size.min_value() as i128, size.max_value() as i128);
+                    Some(Constant::Int(n, *size))
+                }
+                _ => None,
+            },
+            Entry::BitNot(v) => {
+                let (n, size) = self.sized_int(env, *v)?;
+                Some(Constant::Int(wrap(!n, size), size))
+            }
+            Entry::Deref(v) => match self.value(env, *v)? {
+                Constant::ValPtr(val) if !self.lir.exposed_vals.contains(&val) => {
+                    self.val(val, depth + 1)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn binop(&mut self, op: BinOp, lhs: i128, rhs: i128, ty: &MonoType) -> Option<Constant> {
+        // Operands of up to 64 bits can still overflow an i128 when multiplied. Wrapping keeps
+        // the low bits intact, and a wrapped result never fits the operand size.
+        let apply = |lhs: i128, rhs: i128| match op {
+            BinOp::Add => Some(lhs.wrapping_add(rhs)),
+            BinOp::Sub => Some(lhs.wrapping_sub(rhs)),
+            BinOp::Mul => Some(lhs.wrapping_mul(rhs)),
+            BinOp::Div if rhs == 0 => None,
+            BinOp::Div => Some(lhs / rhs),
+            BinOp::And => Some(lhs & rhs),
+        };
+
+        match ty {
+            MonoType::Int(size) => {
+                let size = *size;
+                let n = apply(wrap(lhs, size), wrap(rhs, size))?;
+
+                // Signed division overflow traps
+                if op == BinOp::Div && wrap(n, size) != n {
+                    return None;
+                }
+
+                Some(Constant::Int(wrap(n, size), size))
+            }
+            // Checked arithmetic returning the result together with whether it overflowed
+            MonoType::Monomorphised(mk) => match &self.lir.mono.types[*mk] {
+                MonoTypeData::Record { fields, .. } if fields.len() == 2 => {
+                    let MonoType::Int(size) = fields[key::Field(0)] else {
+                        return None;
+                    };
+                    let n = apply(wrap(lhs, size), wrap(rhs, size))?;
+                    let overflowed = wrap(n, size) != n;
+                    Some(Constant::Aggregate(vec![
+                        Constant::Int(wrap(n, size), size),
+                        Constant::Int(overflowed as i128, IntSize::new(false, 8)),
+                    ]))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn values(&mut self, env: &HashMap<V, Constant>, values: &[Value]) -> Option<Vec<Constant>> {
+        values.iter().map(|v| self.value(env, *v)).collect()
+    }
+
+    fn value(&mut self, env: &HashMap<V, Constant>, value: Value) -> Option<Constant> {
+        Some(match value {
+            Value::V(v) => return env.get(&v).cloned(),
+            Value::Int(n, size) => Constant::Int(wrap(n, size), size),
+            Value::Float(f) => Constant::Float(f),
+            Value::ReadOnly(ro) => Constant::ReadOnly(ro),
+            Value::FuncPtr(mfunc) => Constant::FuncPtr(mfunc),
+            Value::ExternFuncPtr(key) => Constant::ExternFuncPtr(key),
+        })
+    }
+
+    fn sized_int(&mut self, env: &HashMap<V, Constant>, value: Value) -> Option<(i128, IntSize)> {
+        match self.value(env, value)? {
+            Constant::Int(n, size) => Some((n, size)),
+            _ => None,
+        }
+    }
+
+    fn int(&mut self, env: &HashMap<V, Constant>, value: Value) -> Option<i128> {
+        self.sized_int(env, value).map(|(n, _)| n)
+    }
+}
+
+/// Evaluate an integer comparison the same way the backend does, as a strict signed comparison
+pub(super) fn int_cmp(lhs: i128, rhs: i128, ord: Ordering, size: IntSize) -> bool {
+    let signed = IntSize::new(true, size.bits());
+    wrap(lhs, signed).cmp(&wrap(rhs, signed)) == ord
+}
+
+/// Truncate an integer to the bit width of `size` and interpret it with its signedness
+pub(super) fn wrap(n: i128, size: IntSize) -> i128 {
+    let bits = size.bits() as u32;
+    if bits == 0 {
+        return 0;
+    }
+    if bits >= 128 {
+        return n;
+    }
+
+    let n = n & ((1 << bits) - 1);
+    if size.signed && (n >> (bits - 1)) & 1 == 1 {
+        n - (1 << bits)
+    } else {
+        n
+    }
+}
@@ -323,6 +323,40 @@ impl<'a> Debugger<'a> {
                 let inner = self.as_ptr(&ty);
                 assert_eq!(exp, inner);
             }
+            Entry::AtomicLoad(ptr) => {
+                self.check_declared(at, *ptr);
+                let ty = self.lir.type_of_value(self.mfunc, *ptr);
+                let inner = self.as_ptr(&ty);
+                assert_eq!(exp, inner);
+            }
+            Entry::AtomicStore { ptr, value } => {
+                self.check_declared(at, *ptr);
+                self.check_declared(at, *value);
+                let ty = self.lir.type_of_value(self.mfunc, *ptr);
+                let inner = self.as_ptr(&ty);
+                let ty = self.lir.type_of_value(self.mfunc, *value);
+                assert_eq!(ty, *inner);
+                self.as_unit(exp);
+            }
+            Entry::AtomicCas { ptr, expected, replacement } => {
+                self.check_declared(at, *ptr);
+                self.check_declared(at, *expected);
+                self.check_declared(at, *replacement);
+                let ty = self.lir.type_of_value(self.mfunc, *ptr);
+                let inner = self.as_ptr(&ty);
+                assert_eq!(exp, inner);
+                for v in [expected, replacement] {
+                    assert_eq!(self.lir.type_of_value(self.mfunc, *v), *inner);
+                }
+            }
+            Entry::AtomicRmw(_, [ptr, value]) => {
+                self.check_declared(at, *ptr);
+                self.check_declared(at, *value);
+                let ty = self.lir.type_of_value(self.mfunc, *ptr);
+                let inner = self.as_ptr(&ty);
+                assert_eq!(exp, inner);
+                assert_eq!(self.lir.type_of_value(self.mfunc, *value), *inner);
+            }
             Entry::JmpFunc(mfunc, params) => {
                 let expected = self.lir.functions[*mfunc]
                     .ssa
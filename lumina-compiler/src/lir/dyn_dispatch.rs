@@ -2,6 +2,7 @@
 
 use super::{ssa, ty_fmt, FuncLower, Item, MonoFunc, MonoType, MonoTypeKey, Value, SSA, V};
 use crate::prelude::*;
+use crate::{TRAIT_OBJECT_DATA_FIELD, VTABLE_FIELD};
 use either::Either;
 
 impl<'a> FuncLower<'a> {
@@ -205,6 +206,7 @@ impl<'a> FuncLower<'a> {
         let self_positions = self.mir.trait_objects[trait_].as_ref().unwrap();
 
         let dataptr = self.heap_alloc(impltorv, impltor.clone());
+        let dataptr = self.ssa().transmute(dataptr, MonoType::u8_pointer());
 
         let target = match self.lir.memo_trait_objects.get(&ikey) {
             Some(either) => *either,
@@ -212,7 +214,8 @@ impl<'a> FuncLower<'a> {
                 let method = key::Method(0);
                 let param = self_positions[method].0;
                 let target = methods[method];
-                let mfunc = self.create_method_funcwrapper(trait_, param, target, impltor.clone());
+                let mfunc =
+                    self.create_method_funcwrapper(trait_, method, param, target, impltor.clone());
 
                 self.lir
                     .memo_trait_objects
@@ -225,8 +228,13 @@ impl<'a> FuncLower<'a> {
                     .iter()
                     .map(|(method, target)| {
                         let param = self_positions[method].0;
-                        let mfunc =
-                            self.create_method_funcwrapper(trait_, param, *target, impltor.clone());
+                        let mfunc = self.create_method_funcwrapper(
+                            trait_,
+                            method,
+                            param,
+                            *target,
+                            impltor.clone(),
+                        );
                         let fn_ty = self.lir.mfunc_to_fnpointer_type(mfunc);
                         (Value::FuncPtr(mfunc), fn_ty)
                     })
@@ -285,9 +293,105 @@ impl<'a> FuncLower<'a> {
         }
     }
 
+    // Calls a method of the trait object's vtable with the object's data pointer in place of `self`
+    //
+    // let {data, vtable} = object in
+    //   vtable.method data b c
+    pub fn call_dynamic(
+        &mut self,
+        objty: MonoTypeKey,
+        trait_: M<key::Trait>,
+        method: key::Method,
+        mut params: Vec<Value>,
+    ) -> Value {
+        let self_ = self.mir.trait_objects[trait_].as_ref().unwrap()[method].0 as usize;
+        let (vtable, fnptr_type) = self.dynamic_method_type(objty, method);
+        let ret = fnptr_type.as_fnptr().1.clone();
+
+        let ssa = self.ssa();
+        let object = params[self_];
+        params[self_] = ssa.field(
+            object,
+            objty,
+            TRAIT_OBJECT_DATA_FIELD,
+            MonoType::u8_pointer(),
+        );
+        let fnptr = load_dynamic_method(ssa, objty, object, &vtable, method, fnptr_type);
+        ssa.call(fnptr, params, ret)
+    }
+
+    /// The vtable of a trait object alongside the function pointer type of one of its methods
+    fn dynamic_method_type(&self, objty: MonoTypeKey, method: key::Method) -> (MonoType, MonoType) {
+        let vtable = self.types()[objty].as_dyn_trait().1.clone();
+        let fnptr_type = match &vtable {
+            MonoType::Pointer(methods) => {
+                self.types()[methods.as_key()].as_record()[key::Field(method.0)].clone()
+            }
+            _ => vtable.clone(),
+        };
+        (vtable, fnptr_type)
+    }
+
+    // #Source:size // where `Source:size` is dynamically dispatched on the trait object
+    //
+    //  where
+    //   fn dispatch obj as Source -> int =
+    //     (obj.vtable.size) obj.data
+    //
+    /// Wrapper function performing dynamic dispatch, used when a method of a trait object is
+    /// referred to without being directly called.
+    pub fn dynamic_method_wrapper(
+        &mut self,
+        objty: MonoTypeKey,
+        trait_: M<key::Trait>,
+        method: key::Method,
+    ) -> MonoFunc {
+        if let Some(mfunc) = self.lir.memo_dynamic_methods.get(&(objty, method)) {
+            return *mfunc;
+        }
+
+        let self_ = self.mir.trait_objects[trait_].as_ref().unwrap()[method].0 as usize;
+        let (vtable, fnptr_type) = self.dynamic_method_type(objty, method);
+        let (ptypes, ret) = fnptr_type.as_fnptr();
+        let (mut ptypes, ret) = (ptypes.to_vec(), ret.clone());
+        ptypes[self_] = objty.into();
+
+        let symbol = {
+            let tname = &self.mir.trait_names[trait_];
+            let mname = self.mir.name_of_method(trait_, method);
+            format!(
+                "__DynDispatch_{tname}:{mname}_for_{}",
+                self.ty_symbol(&objty.into())
+            )
+        };
+
+        let construct = |_: &mut Self, ssa: &mut SSA| {
+            let mut params = ssa
+                .block_params(lir::Block::entry())
+                .map(V::value)
+                .collect::<Vec<_>>();
+            let object = params[self_];
+            params[self_] = ssa.field(
+                object,
+                objty,
+                TRAIT_OBJECT_DATA_FIELD,
+                MonoType::u8_pointer(),
+            );
+            let fnptr = load_dynamic_method(ssa, objty, object, &vtable, method, fnptr_type);
+            let v = ssa.call(fnptr, params, ret.clone());
+            ssa.return_(v);
+        };
+
+        let item = Item::Autogenerated(trait_.0, self.lir.functions.next_key());
+        let mfunc = self.create_deref_funcwrapper(symbol, item, construct, ptypes, ret.clone());
+        self.lir.memo_dynamic_methods.insert((objty, method), mfunc);
+        mfunc
+    }
+
     fn create_method_funcwrapper(
         &mut self,
         trait_: M<key::Trait>,
+        method: key::Method,
         self_: u32,
         target: MonoFunc,
         impltor: MonoType,
@@ -304,7 +408,7 @@ impl<'a> FuncLower<'a> {
 
         let symbol = {
             let tname = &self.mir.trait_names[trait_];
-            let mname = self.mir.name_of_method(trait_, key::Method(0));
+            let mname = self.mir.name_of_method(trait_, method);
             format!("__Dyn_{tname}:{mname}_for_{}", self.ty_symbol(&impltor))
         };
 
@@ -354,3 +458,21 @@ impl<'a> FuncLower<'a> {
         mfunc
     }
 }
+
+fn load_dynamic_method(
+    ssa: &mut SSA,
+    objty: MonoTypeKey,
+    object: Value,
+    vtable: &MonoType,
+    method: key::Method,
+    fnptr_type: MonoType,
+) -> Value {
+    match vtable {
+        MonoType::Pointer(methods) => {
+            let vtableptr = ssa.field(object, objty, VTABLE_FIELD, vtable.clone());
+            let methods_ = ssa.deref(vtableptr, (**methods).clone());
+            ssa.field(methods_, methods.as_key(), key::Field(method.0), fnptr_type)
+        }
+        _ => ssa.field(object, objty, VTABLE_FIELD, vtable.clone()),
+    }
+}
@@ -56,11 +56,15 @@ impl<'a> FuncLower<'a> {
                     Value::FuncPtr(mfunc)
                 }
                 Callable::Local(value) => value,
+                Callable::Dynamic(objty, trait_, method) => {
+                    Value::FuncPtr(self.dynamic_method_wrapper(objty, trait_, method))
+                }
             },
             mir::Expr::ValToRef(val) => match &**val {
                 mir::Expr::Call(mir::Callable::Func(M(module, ast::NFunc::Val(key)), _), _) => {
                     let key = key.inside(*module);
                     let ty = self.lir.vals[key].clone();
+                    self.lir.exposed_vals.insert(key);
                     self.ssa().val_to_ref(key, ty)
                 }
                 other => panic!("non-val given to val_to_ref builtin: {other}"),
@@ -205,6 +209,30 @@ impl<'a> FuncLower<'a> {
                 let [ptr, value] = self.params_to_values(&**elems).try_into().unwrap();
                 self.ssa().write(ptr, value)
             }
+            // Cranelift's atomic instructions are all sequentially consistent
+            mir::Expr::AtomicLoad(params) => {
+                let [ptr] = self.params_to_values(&**params).try_into().unwrap();
+                let ty = self.type_of_value(ptr).deref();
+                self.ssa().atomic_load(ptr, ty)
+            }
+            mir::Expr::AtomicStore(params) => {
+                let [ptr, value] = self.params_to_values(&**params).try_into().unwrap();
+                self.ssa().atomic_store(ptr, value)
+            }
+            mir::Expr::AtomicCas(params) => {
+                let [ptr, expected, replacement] =
+                    self.params_to_values(&**params).try_into().unwrap();
+                let ty = self.type_of_value(ptr).deref();
+                self.ssa().atomic_cas(ptr, expected, replacement, ty)
+            }
+            mir::Expr::AtomicRmw(name, params) => {
+                let [ptr, value] = self.params_to_values(&**params).try_into().unwrap();
+                let ty = self.type_of_value(ptr).deref();
+                let op = name
+                    .parse()
+                    .unwrap_or_else(|_| panic!("unknown atomic operation: {name}"));
+                self.ssa().atomic_rmw(op, ptr, value, ty)
+            }
             mir::Expr::MemCpy(params) => {
                 let [dst, src, count] = self.params_to_values(&**params).try_into().unwrap();
                 self.ssa().memcpy(dst, src, count)
@@ -214,6 +242,17 @@ impl<'a> FuncLower<'a> {
                 let expr = self.expr_to_value(expr);
                 let impltor = self.type_of_value(expr);
 
+                // Casting a trait object to its own trait does nothing
+                if let MonoType::Monomorphised(mk) = impltor {
+                    if let lir::MonoTypeData::DynTraitObject { trait_: otrait, .. } =
+                        self.types()[mk]
+                    {
+                        if otrait == trait_ {
+                            return expr;
+                        }
+                    }
+                }
+
                 let mut morph = to_morphization!(self.lir, self.mir, &mut self.current.tmap);
                 let weak_impltor = morph.apply_weak(weak_impltor);
                 let weak_trait_params = morph.applys_weak::<Vec<_>>(trait_params);
@@ -226,24 +265,7 @@ impl<'a> FuncLower<'a> {
                     impltor.clone(),
                 );
 
-                let methods = self.mir.imethods[impl_]
-                    .keys()
-                    .map(|method| match self.mir.imethods[impl_][method] {
-                        None => todo!("default method instantiation"),
-                        Some(func) => {
-                            // If the methods have generics then this isn't trait-safe therefore
-                            // this would've already been stopped.
-                            let mut tmap = tmap.clone();
-                            let mut morph = to_morphization!(self.lir, self.mir, &mut tmap);
-                            let typing = self.mir.funcs[func].as_typing();
-                            let typing = morph.apply_typing(Item::Method(impl_, method), typing);
-                            self.lir
-                                .func(self.mir, self.iquery, self.info, tmap, typing, None)
-                        }
-                    })
-                    .collect();
-
-                self.dyn_object(impl_, trait_params, expr, methods)
+                self.dyn_object_from_impl(impl_, tmap, trait_params, expr)
             }
             mir::Expr::Match(on, tree, branches, pred) => {
                 let on = self.expr_to_value(on);
@@ -254,6 +276,30 @@ impl<'a> FuncLower<'a> {
                     to_morphization!(self.lir, self.mir, &mut self.current.tmap).apply_weak(ty);
                 self.create_reflection(ty)
             }
+            mir::Expr::ReflectFieldName(ty, n) => {
+                let ty =
+                    to_morphization!(self.lir, self.mir, &mut self.current.tmap).apply_weak(ty);
+                let n = self.expr_to_value(n);
+                self.reflect_field_name(ty, n)
+            }
+            mir::Expr::ReflectVariant(v) => {
+                let v = self.expr_to_value(v);
+                self.reflect_variant(v)
+            }
+            mir::Expr::ReflectField(params, ty, object) => {
+                let [v, n] = [0, 1].map(|i| self.expr_to_value(&params[i]));
+                let morph = to_morphization!(self.lir, self.mir, &mut self.current.tmap);
+                let ty = morph.apply_weak(ty);
+                let object = morph.apply_weak(object);
+                self.reflect_field(v, ty, n, object)
+            }
+            mir::Expr::ReflectImplementation(v, ty, object) => {
+                let v = self.expr_to_value(v);
+                let morph = to_morphization!(self.lir, self.mir, &mut self.current.tmap);
+                let ty = morph.apply_weak(ty);
+                let object = morph.apply_weak(object);
+                self.reflect_implementation(v, ty, object)
+            }
             mir::Expr::SizeOf(ty) => {
                 let ty = to_morphization!(self.lir, self.mir, &mut self.current.tmap).apply(ty);
                 let intsize = self.lir.target.int_size();
@@ -361,7 +407,7 @@ impl<'a> FuncLower<'a> {
         }
     }
 
-    fn int_cast(&mut self, v: Value, [from, to]: [IntSize; 2]) -> Value {
+    pub fn int_cast(&mut self, v: Value, [from, to]: [IntSize; 2]) -> Value {
         let ty = MonoType::Int(to);
 
         match from.bits().cmp(&to.bits()) {
@@ -396,6 +442,9 @@ impl<'a> FuncLower<'a> {
                 self.ssa().deref(v, ty)
             }
             Callable::Sum { var, ty, .. } => self.ssa().variant(var, params, ty),
+            Callable::Dynamic(objty, trait_, method) => {
+                self.call_dynamic(objty, trait_, method, params)
+            }
             Callable::Local(to_call) => {
                 let ty = self.type_of_value(to_call);
                 match ty {
@@ -433,6 +482,10 @@ impl<'a> FuncLower<'a> {
                 };
                 self.partially_applicate_closure(cap, partials)
             }
+            Callable::Dynamic(objty, trait_, method) => {
+                let mfunc = self.dynamic_method_wrapper(objty, trait_, method);
+                self.partially_applicate_func(mfunc, partials)
+            }
         }
     }
 
@@ -466,6 +519,34 @@ impl<'a> FuncLower<'a> {
         self.ssa().call(fnptr, call_method_params, ret)
     }
 
+    /// Monomorphise the methods of an implementation and put them in a trait object with the value
+    pub fn dyn_object_from_impl(
+        &mut self,
+        impl_: M<key::Impl>,
+        tmap: TypeMap,
+        trait_params: Vec<MonoType>,
+        value: Value,
+    ) -> Value {
+        let methods = self.mir.imethods[impl_]
+            .keys()
+            .map(|method| match self.mir.imethods[impl_][method] {
+                None => unreachable!("missing methods are rejected by the type checker"),
+                Some(func) => {
+                    // If the methods have generics then this isn't trait-safe therefore
+                    // this would've already been stopped.
+                    let mut tmap = self.method_tmap(impl_, method, tmap.clone());
+                    let mut morph = to_morphization!(self.lir, self.mir, &mut tmap);
+                    let typing = self.mir.funcs[func].as_typing();
+                    let typing = morph.apply_typing(Item::Method(impl_, method), typing);
+                    self.lir
+                        .func(self.mir, self.iquery, self.info, tmap, typing, None)
+                }
+            })
+            .collect();
+
+        self.dyn_object(impl_, trait_params, value, methods)
+    }
+
     pub fn find_implementation(
         &mut self,
         trait_: M<key::Trait>,
@@ -473,10 +554,17 @@ impl<'a> FuncLower<'a> {
         weak_impltor: Type,
         impltor: MonoType,
     ) -> (M<key::Impl>, TypeMap) {
-        warn!(
-            "conflicting implementations is not fully implemented. Weird auto-selections may occur"
-        );
+        self.try_find_implementation(trait_, trtp, weak_impltor, impltor)
+            .unwrap()
+    }
 
+    pub fn try_find_implementation(
+        &mut self,
+        trait_: M<key::Trait>,
+        trtp: &[Type],
+        weak_impltor: Type,
+        impltor: MonoType,
+    ) -> Option<(M<key::Impl>, TypeMap)> {
         let concrete_impltor = (&weak_impltor).try_into().ok();
 
         info!(
@@ -492,18 +580,30 @@ impl<'a> FuncLower<'a> {
                 let (_, trait_params) = &self.mir.itraits[imp];
                 let iimpltor = &self.mir.impltors[imp];
 
+                let get_impl_data = &|ikey: M<key::Impl>| {
+                    let (trait_, trait_params) = &self.mir.itraits[ikey];
+                    let impltor = &*self.mir.impltors[ikey];
+                    (
+                        *trait_,
+                        &self.mir.impls[ikey],
+                        impltor,
+                        trait_params.as_slice(),
+                    )
+                };
+
                 let mut comp = lumina_typesystem::Compatibility::new(
                     &self.iquery,
                     &|_| panic!("un-monomorphised generic in LHS"),
                     &iforall,
-                    &|_| unreachable!(),
+                    get_impl_data,
                 );
 
                 let valid = trtp
                     .iter()
                     .zip(trait_params)
                     .all(|(ty, ttp)| comp.cmp(ty, ttp))
-                    && comp.cmp(&weak_impltor, iimpltor);
+                    && comp.cmp(&weak_impltor, iimpltor)
+                    && comp.check_all();
 
                 valid.then(|| {
                     let mut tmap = TypeMap::new();
@@ -516,7 +616,33 @@ impl<'a> FuncLower<'a> {
                     (imp, tmap)
                 })
             })
-            .unwrap()
+    }
+
+    /// Methods an implementation inherits from the trait's default are typed against the trait's
+    /// forall, so the implementation's mapping is turned into one for the trait's parameters.
+    pub fn method_tmap(
+        &mut self,
+        impl_: M<key::Impl>,
+        method: key::Method,
+        mut itmap: TypeMap,
+    ) -> TypeMap {
+        let (trait_, trait_params) = &self.mir.itraits[impl_];
+        let default = self.mir.methods[*trait_][method].inside(trait_.0);
+
+        if self.mir.imethods[impl_][method] != Some(default) {
+            return itmap;
+        }
+
+        let mut morph = to_morphization!(self.lir, self.mir, &mut itmap);
+        let params = trait_params
+            .iter()
+            .map(|ty| (morph.apply_weak(ty), morph.apply(ty)))
+            .collect::<Vec<_>>();
+
+        let mut tmap = TypeMap::new();
+        tmap.set_self(itmap.weak.self_.clone().unwrap(), itmap.self_.unwrap());
+        tmap.extend(GenericKind::Parent, params);
+        tmap
     }
 
     pub fn call_to_mfunc(&mut self, func: Item, mut tmap: TypeMap) -> MonoFunc {
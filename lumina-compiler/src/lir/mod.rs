@@ -35,14 +35,16 @@ macro_rules! to_morphization {
     };
 }
 
+mod comptime;
 mod mono;
 mod reflect;
 mod ssa;
+pub use comptime::Constant;
 pub use mono::{
     fmt as ty_fmt, MonoFormatter, MonoType, MonoTypeData, MonoTypeKey, MonomorphisedTypes,
     Monomorphization, TypeMap, Types,
 };
-pub use ssa::{BinOp, Block, BlockJump, Entry, Value, SSA, V};
+pub use ssa::{BinOp, Block, BlockJump, Entry, OptSettings, Value, SSA, V};
 mod dyn_dispatch;
 mod expr;
 mod pat;
@@ -58,6 +60,11 @@ pub struct Output {
     pub extern_funcs: HashMap<M<key::Func>, ExternFunction>,
     pub val_initializers: HashMap<M<key::Val>, MonoFunc>,
     pub val_types: MMap<key::Val, MonoType>,
+    /// Vals whose initialisers were evaluated at compile-time
+    pub val_constants: HashMap<M<key::Val>, Constant>,
+    /// Vals whose address is exposed through `builtin:val_to_ref`
+    pub exposed_vals: HashSet<M<key::Val>>,
+    pub thread_locals: HashSet<M<key::Val>>,
 
     pub read_only_table: MMap<key::ReadOnly, (mir::ReadOnlyBytes, MonoType)>,
 
@@ -86,6 +93,10 @@ struct LIR {
     memo_trait_objects: HashMap<M<key::Impl>, Either<M<key::Val>, MonoFunc>>,
     #[new(default)]
     memo_closures: HashMap<(MonoFunc, Vec<MonoType>), MonoFunc>,
+    #[new(default)]
+    memo_dynamic_methods: HashMap<(MonoTypeKey, key::Method), MonoFunc>,
+    #[new(default)]
+    memo_specialisations: HashMap<(MonoFunc, u32, MonoFunc), MonoFunc>,
 
     read_only_table: MMap<key::ReadOnly, (mir::ReadOnlyBytes, MonoType)>,
 
@@ -94,6 +105,8 @@ struct LIR {
     vals: MMap<key::Val, MonoType>,
     #[new(default)]
     val_initialisers: HashMap<M<key::Val>, MonoFunc>,
+    #[new(default)]
+    exposed_vals: HashSet<M<key::Val>>,
 
     #[new(default)]
     alloc: Option<MonoFunc>,
@@ -109,6 +122,9 @@ pub struct MonoTypesKey {
     origin: Item,
     generics: Vec<(Generic, MonoType)>,
     self_: Option<MonoType>,
+    // Types such as `bool` and `u8` share their monomorphised representation, so the weak types
+    // are included to keep reflection from being shared between them.
+    weak: Vec<(Generic, Type)>,
 }
 
 #[derive(PartialEq, Eq, Hash)]
@@ -158,6 +174,8 @@ pub struct Function {
     pub directly_recursive: bool,
     #[new(default)]
     pub pointed_to_by_func_pointer: bool,
+    #[new(default)]
+    pub inline: Option<ast::Inline>,
 }
 
 impl Function {
@@ -188,11 +206,23 @@ impl Item {
             Item::Defined(key) => mir.func_names[*key].clone(),
             Item::Autogenerated(_, mfunc) => mfunc.to_string(),
             Item::Method(impl_, method) => {
-                mir.func_names[mir.imethods[*impl_][*method].unwrap().inside(impl_.0)].clone()
+                mir.func_names[mir.imethods[*impl_][*method].unwrap()].clone()
             }
             Item::Lambda(parent, lkey) => format!("{}:{lkey}", parent.name(mir)),
         }
     }
+
+    fn inline(&self, mir: &mir::MIR) -> Option<ast::Inline> {
+        match self {
+            Item::Defined(key) => mir.funcs[*key].as_done().inline,
+            Item::Method(impl_, method) => {
+                mir.funcs[mir.imethods[*impl_][*method].unwrap()]
+                    .as_done()
+                    .inline
+            }
+            _ => None,
+        }
+    }
 }
 
 enum Callable {
@@ -206,9 +236,16 @@ enum Callable {
         ty: MonoTypeKey,
     },
     Local(Value),
+    Dynamic(MonoTypeKey, M<key::Trait>, key::Method),
 }
 
-pub fn run<'s>(info: ProjectInfo, target: Target, iquery: &ImplIndex, mut mir: mir::MIR) -> Output {
+pub fn run(
+    info: ProjectInfo,
+    target: Target,
+    iquery: &ImplIndex,
+    mut mir: mir::MIR,
+    settings: OptSettings,
+) -> Output {
     info!("starting LIR lower");
 
     let mainfunc = &mir.funcs[info.main].as_done();
@@ -322,16 +359,21 @@ pub fn run<'s>(info: ProjectInfo, target: Target, iquery: &ImplIndex, mut mir: m
     #[cfg(debug_assertions)]
     Debugger::new(&lir, &mir).run();
 
-    lir.perform_optimizations();
+    lir.perform_optimizations(&settings);
 
     #[cfg(debug_assertions)]
     Debugger::new(&lir, &mir).run();
 
+    let val_constants = lir.evaluate_vals();
+
     Output {
         functions: lir.functions,
         extern_funcs: lir.extern_funcs,
         val_initializers: lir.val_initialisers,
         val_types: lir.vals,
+        val_constants,
+        exposed_vals: lir.exposed_vals,
+        thread_locals: mir.thread_locals,
         read_only_table: lir.read_only_table,
         func_names: mir.func_names,
         module_names: mir.module_names,
@@ -367,7 +409,12 @@ impl LIR {
         mut typing: MonoTyping,
         captures: Option<Map<key::Capture, (key::Bind, MonoType)>>,
     ) -> MonoFunc {
-        let key = MonoTypesKey::new(typing.origin, tmap.generics, tmap.self_);
+        let key = MonoTypesKey::new(
+            typing.origin,
+            tmap.generics,
+            tmap.self_,
+            tmap.weak.generics.clone(),
+        );
 
         match self.mono_resolve.get(&key) {
             Some(key) => {
@@ -425,11 +472,13 @@ impl LIR {
 
                 let symbol = func_symbol(mir, self.functions.next_key(), &origin);
                 let mfkey = self.push_function(symbol, typing.origin.clone(), ssa, returns);
+                self.functions[mfkey].inline = origin.inline(mir);
 
                 let key = MonoTypesKey::new(
                     typing.origin.clone(),
                     tmap.generics.clone(),
                     tmap.self_.clone(),
+                    tmap.weak.generics.clone(),
                 );
                 self.mono_resolve.insert(key, mfkey);
 
@@ -609,7 +658,7 @@ impl<'a> FuncLower<'a> {
 
         // Use the sum-type monomorphic key in the hashing for mono_resolve
         let ty = MonoType::Monomorphised(ty);
-        let key = MonoTypesKey::new(origin.clone(), vec![], Some(ty.clone()));
+        let key = MonoTypesKey::new(origin.clone(), vec![], Some(ty.clone()), vec![]);
 
         match self.lir.mono_resolve.get(&key) {
             Some(mfunc) => *mfunc,
@@ -828,7 +877,7 @@ impl<'a> FuncLower<'a> {
             Item::Method(imp, m) => {
                 let origin = Item::Lambda(Box::new(origin.clone()), lambda);
                 let fkey = self.mir.imethods[*imp][*m].unwrap();
-                let func = self.mir.funcs[fkey.inside(imp.0)].as_done();
+                let func = self.mir.funcs[fkey].as_done();
                 let captures = &func.lcaptures[lambda];
                 (origin, captures)
             }
@@ -868,10 +917,29 @@ impl<'a> FuncLower<'a> {
                         .map(|ty| morph.apply_weak(ty))
                         .collect::<Vec<_>>();
 
+                    // The method's own generics are shared between the trait and implementations
+                    let mgenerics = mapper
+                        .types(GenericKind::Entity)
+                        .map(|ty| (morph.apply_weak(ty), morph.apply(ty)))
+                        .collect::<Vec<_>>();
+
+                    // Trait objects implement their own trait by dispatching through the vtable
+                    if let MonoType::Monomorphised(mk) = impltor {
+                        if let MonoTypeData::DynTraitObject { trait_: otrait, .. } =
+                            self.types()[mk]
+                        {
+                            if otrait == trait_ && trait_ != self.info.closure {
+                                return Callable::Dynamic(mk, trait_, *method);
+                            }
+                        }
+                    }
+
                     let (ikey, itmap) =
                         self.find_implementation(trait_, &trtp, weak_impltor, impltor);
 
                     let forigin = Item::Method(ikey, *method);
+                    let mut itmap = self.method_tmap(ikey, *method, itmap);
+                    itmap.extend(GenericKind::Entity, mgenerics);
 
                     let mfunc = self.call_to_mfunc(forigin, itmap);
 
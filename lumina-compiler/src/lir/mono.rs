@@ -25,6 +25,9 @@ pub enum MonoType {
     Const(ConstValue),
     Array(u64, Box<Self>),
     Monomorphised(MonoTypeKey),
+    // A type constructor given to a higher-kinded generic. It's never instantiated by itself and
+    // only keeps apart the monomorphisations of functions generic over it.
+    Constructor(M<key::TypeKind>, Vec<Self>),
 }
 
 impl From<MonoTypeKey> for MonoType {
@@ -42,6 +45,35 @@ pub struct Types {
     pub pointer_bits: u32,
 }
 
+impl Types {
+    /// Size and alignment of types whose layout doesn't depend on the backend
+    ///
+    /// Records and sums are laid out by the backend, so `None` is returned for anything
+    /// containing them.
+    pub fn scalar_size_and_align_of(&self, ty: &MonoType) -> Option<(u32, u32)> {
+        let pointer = self.pointer_bits / 8;
+
+        match ty {
+            MonoType::Int(intsize) => Some((intsize.bytes() as u32, intsize.bytes() as u32)),
+            MonoType::Float | MonoType::FnPointer(..) | MonoType::Pointer(_) => {
+                Some((pointer, pointer))
+            }
+            MonoType::Const(ConstValue::Usize(_)) => Some((pointer, pointer)),
+            MonoType::Const(ConstValue::Bool(_)) => Some((1, 1)),
+            MonoType::Const(ConstValue::Char(_)) => Some((4, 4)),
+            MonoType::Array(0, _) => Some((0, 0)),
+            MonoType::Array(len, inner) => {
+                let (size, align) = self.scalar_size_and_align_of(inner)?;
+                let padding = (align - size % align.max(1)) % align.max(1);
+                Some(((size + padding) * *len as u32, align))
+            }
+            MonoType::Unreachable => Some((0, 0)),
+            MonoType::Monomorphised(_) => None,
+            MonoType::Constructor(..) => unreachable!("type constructors are never instantiated"),
+        }
+    }
+}
+
 pub struct MonomorphisedTypes {
     resolve: HashMap<(M<key::TypeKind>, Vec<MonoType>), MonoTypeKey>,
     tuples: HashMap<Vec<MonoType>, MonoTypeKey>,
@@ -160,6 +192,12 @@ impl<'a, 't> fmt::Display for MonoFormatter<'a, &'t MonoType> {
             MonoType::Unreachable => "!".fmt(f),
             MonoType::Array(len, inner) => write!(f, "[{}; {len}]", self.fork(&**inner)),
             MonoType::Monomorphised(key) => self.fork(*key).fmt(f),
+            MonoType::Constructor(kind, params) if params.is_empty() => kind.fmt(f),
+            MonoType::Constructor(kind, params) => write!(
+                f,
+                "({kind} {})",
+                params.iter().map(|t| self.fork(t)).format(" ")
+            ),
         }
     }
 }
@@ -240,6 +278,16 @@ impl MonomorphisedTypes {
         MonoFormatter { v, types: &self.types.records, funcs: None }
     }
 
+    pub fn is_closure_object(&self, ty: &MonoType) -> bool {
+        match ty {
+            MonoType::Monomorphised(mk) => matches!(
+                &self.types[*mk],
+                MonoTypeData::DynTraitObject { trait_, .. } if *trait_ == self.closure
+            ),
+            _ => false,
+        }
+    }
+
     pub fn get_or_make_tuple(&mut self, elems: Vec<MonoType>) -> MonoTypeKey {
         if let Some(key) = self.tuples.get(&elems).copied() {
             return key;
@@ -597,6 +645,11 @@ impl<'a> Monomorphization<'a> {
                     };
                     MonoType::Array(len, Box::new(inner))
                 }
+                Container::App if params.len() == 1 => self.constructor(&params[0]),
+                Container::App => {
+                    let ty = self.apply_weak(ty);
+                    self.apply(&ty)
+                }
                 &Container::Defined(M(module, key), _) => match key {
                     key::TypeKind::Record(rkey) => {
                         let mk = self.record(rkey.inside(module), params);
@@ -625,6 +678,17 @@ impl<'a> Monomorphization<'a> {
         }
     }
 
+    // Type constructors are never instantiated by themselves, but functions generic over them
+    // still need to be monomorphised separately for each constructor.
+    fn constructor(&mut self, ty: &Type) -> MonoType {
+        match ty {
+            Ty::Container(Container::Defined(kind, _), params) => {
+                MonoType::Constructor(*kind, self.applys(params))
+            }
+            _ => MonoType::Unreachable,
+        }
+    }
+
     pub fn apply_weak(&self, ty: &Type) -> Type {
         (&self.tmap.weak).transform(ty)
     }
@@ -726,6 +790,10 @@ impl fmt::Debug for MonoType {
             MonoType::Unreachable => write!(f, "!"),
             MonoType::Array(len, inner) => write!(f, "[{:?}; {len}]", inner),
             MonoType::Monomorphised(key) => write!(f, "{key}"),
+            MonoType::Constructor(kind, params) if params.is_empty() => write!(f, "{kind}"),
+            MonoType::Constructor(kind, params) => {
+                write!(f, "({kind} {:?})", params.iter().format(" "))
+            }
         }
     }
 }
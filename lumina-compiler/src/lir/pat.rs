@@ -530,7 +530,6 @@ impl<'f, 'v, 'a> PatLower<'f, 'v, 'a> {
         let on_mk = self.f.type_of_value(on).as_key();
 
         let (tag_size, _, _) = self.f.types()[on_mk].as_sum();
-        let tag = self.ssa().tag_of(on, tag_size);
 
         assert!(
             v.branches
@@ -545,13 +544,40 @@ impl<'f, 'v, 'a> PatLower<'f, 'v, 'a> {
             .map(|(..)| self.ssa().new_block())
             .collect::<Vec<_>>();
 
-        self.ssa().jump_table(tag, jmp_table_blocks.clone());
+        // Sums constructed in this function, such as those created by reflection, have a known
+        // variant. We still lower the other branches to keep the predecessor count of the tails
+        // intact, but since nothing jumps to them they'll be purged.
+        let known = match on {
+            Value::V(v) => match self.ssa().entry_of(v) {
+                ssa::Entry::Variant(var, params) => Some((*var, params.clone())),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match &known {
+            Some((var, _)) => {
+                let i = v.branches.iter().position(|(bvar, _)| bvar == var).unwrap();
+                self.ssa().jump(jmp_table_blocks[i], vec![]);
+            }
+            None => {
+                let tag = self.ssa().tag_of(on, tag_size);
+                self.ssa().jump_table(tag, jmp_table_blocks.clone());
+            }
+        }
 
         for (vblock, (var, next)) in jmp_table_blocks.into_iter().zip(&v.branches) {
             self.ssa().switch_to_block(vblock);
 
             let resetpoint = self.make_reset();
 
+            if let Some((_, params)) = known.as_ref().filter(|(kvar, _)| kvar == var) {
+                self.constructors.push(params.iter().copied().collect());
+                self.next(next);
+                self.reset(oblock, resetpoint);
+                continue;
+            }
+
             let finst = GenericMapper::from_types(GenericKind::Entity, params.iter().cloned());
             let raw_var_types = &self.f.mir.variant_types[sum][*var];
 
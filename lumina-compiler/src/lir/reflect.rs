@@ -1,41 +1,305 @@
-use super::{FuncLower, Value};
-use lumina_typesystem::Type;
-use tracing::error;
+//! Lowering for the reflection builtins
+//!
+//! Reflection is performed after monomorphisation, so everything known about the type is known at
+//! compile-time and can be constructed as constants.
+
+use super::{FuncLower, MonoType, MonoTypeData, MonoTypeKey, Value};
+use crate::prelude::*;
+use crate::{
+    MAYBE_JUST, MAYBE_NONE, TYPEINFO_ARRAY, TYPEINFO_BOOL, TYPEINFO_FLOAT, TYPEINFO_FN_POINTER,
+    TYPEINFO_INT, TYPEINFO_OBJECT, TYPEINFO_OTHER, TYPEINFO_POINTER, TYPEINFO_RECORD, TYPEINFO_SUM,
+    TYPEINFO_TUPLE,
+};
+use lumina_typesystem::{Container, GenericKind, GenericMapper, IntSize, Transformer, Ty, Type};
 
 impl<'a> FuncLower<'a> {
     pub fn create_reflection(&mut self, weak: Type) -> Value {
-        error!("{}", &weak);
+        let (var, params) = match &weak {
+            Ty::Int(intsize) => {
+                let bits = Value::Int(intsize.bits() as i128, IntSize::new(false, 8));
+                (TYPEINFO_INT, vec![Value::bool(intsize.signed), bits])
+            }
+            Ty::Simple("f64") => (TYPEINFO_FLOAT, vec![]),
+            Ty::Simple("bool") => (TYPEINFO_BOOL, vec![]),
+            Ty::Container(con, params) => match con {
+                Container::Pointer => (TYPEINFO_POINTER, vec![]),
+                Container::FnPointer => (
+                    TYPEINFO_FN_POINTER,
+                    vec![self.uint(params.len() as i128 - 1).0],
+                ),
+                Container::Tuple => (TYPEINFO_TUPLE, vec![self.uint(params.len() as i128).0]),
+                Container::Array => {
+                    let ty =
+                        to_morphization!(self.lir, self.mir, &mut self.current.tmap).apply(&weak);
+                    let (len, _) = ty.as_array();
+                    (TYPEINFO_ARRAY, vec![self.uint(len as i128).0])
+                }
+                Container::Closure => {
+                    let mir = self.mir;
+                    let name = self.string_constant(&mir.trait_names[self.info.closure]);
+                    (TYPEINFO_OBJECT, vec![name])
+                }
+                Container::Defined(kind, _) => {
+                    let mir = self.mir;
+                    let name = self.string_constant(mir.name_of_type(*kind));
+                    match kind.1 {
+                        key::TypeKind::Record(record) => {
+                            let fields = self.mir.field_types[record.inside(kind.0)].len();
+                            (TYPEINFO_RECORD, vec![name, self.uint(fields as i128).0])
+                        }
+                        key::TypeKind::Sum(sum) => {
+                            let variants = self.mir.variant_types[sum.inside(kind.0)].len();
+                            (TYPEINFO_SUM, vec![name, self.uint(variants as i128).0])
+                        }
+                        key::TypeKind::Trait(_) => (TYPEINFO_OBJECT, vec![name]),
+                    }
+                }
+                Container::App => panic!("type constructor reached reflection: {weak}"),
+            },
+            // Such as `never` and poisoned types
+            _ => (TYPEINFO_OTHER, vec![]),
+        };
+
+        let ty = to_morphization!(self.lir, self.mir, &mut self.current.tmap)
+            .sum(self.info.reflect_type, &[]);
+
+        self.ssa().variant(var, params, ty)
+    }
+
+    pub fn reflect_field_name(&mut self, weak: Type, n: Value) -> Value {
+        let names = match &weak {
+            Ty::Container(Container::Defined(M(module, kind), _), _) => match kind {
+                key::TypeKind::Record(record) => self.mir.field_names[record.inside(*module)]
+                    .values()
+                    .cloned()
+                    .collect(),
+                key::TypeKind::Sum(sum) => self.mir.variant_names[sum.inside(*module)]
+                    .values()
+                    .cloned()
+                    .collect(),
+                key::TypeKind::Trait(_) => vec![],
+            },
+            _ => vec![],
+        };
+
+        let string = self.string_type();
+
+        self.index_switch(
+            n,
+            names.len(),
+            string.into(),
+            |this, i| this.string_constant(&names[i]),
+            |this| this.string_constant(""),
+        )
+    }
+
+    pub fn reflect_variant(&mut self, v: Value) -> Value {
+        let (uint, size) = self.uint(0);
+
+        match self.type_of_value(v) {
+            MonoType::Monomorphised(mk) => match self.types()[mk] {
+                MonoTypeData::Sum { tag, .. } => {
+                    let tag_ = self.ssa().tag_of(v, tag);
+                    self.int_cast(tag_, [tag, size])
+                }
+                _ => uint,
+            },
+            _ => uint,
+        }
+    }
+
+    pub fn reflect_field(&mut self, v: Value, weak: Type, n: Value, object: Type) -> Value {
+        let maybe = self.maybe_type(&object);
+
+        let Some(members) = self.members_of(&weak) else {
+            return self.nothing(maybe);
+        };
+
+        match members {
+            Members::Fields(fields) => {
+                let mk = self.type_of_value(v).as_key();
+                self.field_of(v, mk, fields, n, object, maybe)
+            }
+            Members::Variants(variants) => {
+                let mk = self.type_of_value(v).as_key();
+                let (tagsize, _, payloads) = self.types()[mk].as_sum();
+                let payloads = payloads.values().copied().collect::<Vec<_>>();
+
+                let tag = self.ssa().tag_of(v, tagsize);
+
+                let continuation = self.ssa().new_block();
+                let blocks = variants
+                    .iter()
+                    .map(|_| self.ssa().new_block())
+                    .collect::<Vec<_>>();
+
+                self.ssa().jump_table(tag, blocks.clone());
+
+                for ((block, params), payload) in blocks.into_iter().zip(variants).zip(payloads) {
+                    self.ssa().switch_to_block(block);
+                    let payload_v = self.ssa().cast_payload(v, payload.into());
+                    let field = self.field_of(payload_v, payload, params, n, object.clone(), maybe);
+                    self.ssa().jump(continuation, vec![field]);
+                }
+
+                self.ssa().switch_to_block(continuation);
+                self.ssa()
+                    .add_block_param(continuation, maybe.into())
+                    .value()
+            }
+        }
+    }
+
+    pub fn reflect_implementation(&mut self, v: Value, weak: Type, object: Type) -> Value {
+        let maybe = self.maybe_type(&object);
+        self.as_object(v, weak, object, maybe)
+    }
 
-        // let reflect_weak = to_morphization!(self.lir, self.mir, &mut self.current.tmap)
-        //     .apply_weak(&Type::defined(self.info.reflect_type, vec![]));
+    fn field_of(
+        &mut self,
+        v: Value,
+        mk: MonoTypeKey,
+        fields: Vec<Type>,
+        n: Value,
+        object: Type,
+        maybe: MonoTypeKey,
+    ) -> Value {
+        self.index_switch(
+            n,
+            fields.len(),
+            maybe.into(),
+            |this, i| {
+                let field = key::Field(i as u32);
+                let ty = this.types()[mk].as_record()[field].clone();
+                let fieldv = this.ssa().field(v, mk, field, ty);
+                this.as_object(fieldv, fields[i].clone(), object.clone(), maybe)
+            },
+            |this| this.nothing(maybe),
+        )
+    }
+
+    // Wraps the value in a trait object if its type implements the trait
+    fn as_object(&mut self, v: Value, weak: Type, object: Type, maybe: MonoTypeKey) -> Value {
+        let Ty::Container(Container::Defined(M(module, key::TypeKind::Trait(trait_)), _), params) =
+            &object
+        else {
+            return self.nothing(maybe);
+        };
+
+        let trait_ = trait_.inside(*module);
+
+        if weak == object {
+            return self.ssa().variant(MAYBE_JUST, vec![v], maybe);
+        }
+
+        if trait_ == self.info.closure || self.mir.trait_objects[trait_].is_none() {
+            return self.nothing(maybe);
+        }
+
+        let impltor = self.type_of_value(v);
+        match self.try_find_implementation(trait_, params, weak, impltor) {
+            Some((impl_, tmap)) => {
+                let trait_params =
+                    to_morphization!(self.lir, self.mir, &mut self.current.tmap).applys(params);
+                let obj = self.dyn_object_from_impl(impl_, tmap, trait_params, v);
+                self.ssa().variant(MAYBE_JUST, vec![obj], maybe)
+            }
+            None => self.nothing(maybe),
+        }
+    }
+
+    // The types of the fields of records and tuples, or the parameters of each variant of a sum
+    fn members_of(&self, weak: &Type) -> Option<Members> {
+        match weak {
+            Ty::Container(Container::Tuple, elems) => Some(Members::Fields(elems.clone())),
+            Ty::Container(Container::Defined(M(module, kind), _), params) => {
+                let finst = GenericMapper::from_types(GenericKind::Entity, params.iter().cloned());
+                match kind {
+                    key::TypeKind::Record(record) => {
+                        let fields = self.mir.field_types[record.inside(*module)]
+                            .values()
+                            .map(|ty| (&finst).transform(ty))
+                            .collect();
+                        Some(Members::Fields(fields))
+                    }
+                    key::TypeKind::Sum(sum) => {
+                        let variants = self.mir.variant_types[sum.inside(*module)]
+                            .values()
+                            .map(|params| params.iter().map(|ty| (&finst).transform(ty)).collect())
+                            .collect();
+                        Some(Members::Variants(variants))
+                    }
+                    key::TypeKind::Trait(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // Lower `on_index` for the index `n` if it's lower than `count`, otherwise `otherwise`.
+    fn index_switch(
+        &mut self,
+        n: Value,
+        count: usize,
+        ty: MonoType,
+        mut on_index: impl FnMut(&mut Self, usize) -> Value,
+        otherwise: impl FnOnce(&mut Self) -> Value,
+    ) -> Value {
+        let continuation = self.ssa().new_block();
+
+        if count == 0 {
+            let v = otherwise(self);
+            self.ssa().jump(continuation, vec![v]);
+        } else {
+            let (len, size) = self.uint(count as i128);
+            let in_range = self.ssa().lti([n, len], size);
 
-        todo!("reflection API needs to be redesigned, it's not useful if you can't resolve implementations");
+            let [table, outside] = [self.ssa().new_block(), self.ssa().new_block()];
+            self.ssa()
+                .select(in_range, [(table, vec![]), (outside, vec![])]);
+
+            self.ssa().switch_to_block(outside);
+            let v = otherwise(self);
+            self.ssa().jump(continuation, vec![v]);
+
+            self.ssa().switch_to_block(table);
+            let blocks = (0..count)
+                .map(|_| self.ssa().new_block())
+                .collect::<Vec<_>>();
+            self.ssa().jump_table(n, blocks.clone());
+
+            for (i, block) in blocks.into_iter().enumerate() {
+                self.ssa().switch_to_block(block);
+                let v = on_index(self, i);
+                self.ssa().jump(continuation, vec![v]);
+            }
+        }
+
+        self.ssa().switch_to_block(continuation);
+        self.ssa().add_block_param(continuation, ty).value()
+    }
+
+    fn maybe_type(&mut self, inner: &Type) -> MonoTypeKey {
+        to_morphization!(self.lir, self.mir, &mut self.current.tmap)
+            .sum(self.info.maybe, std::slice::from_ref(inner))
+    }
+
+    fn nothing(&mut self, maybe: MonoTypeKey) -> Value {
+        self.ssa().variant(MAYBE_NONE, vec![], maybe)
+    }
+
+    fn string_constant(&mut self, str: &str) -> Value {
+        let module = self.current.origin.module();
+        let bytes = mir::ReadOnlyBytes(str.as_bytes().into());
+        let ro = self
+            .lir
+            .read_only_table
+            .push(module, (bytes, MonoType::byte()));
+        self.string_from_ro(ro).0
     }
 }
 
-// const INT: Value = Value::UInt(0, mono::TAG_SIZE);
-// const FLOAT: Value = Value::UInt(1, mono::TAG_SIZE);
-// const BOOL: Value = Value::UInt(2, mono::TAG_SIZE);
-// const NEVER: Value = Value::UInt(3, mono::TAG_SIZE);
-// const POISON: Value = Value::UInt(4, mono::TAG_SIZE);
-// const POINTER: Value = Value::UInt(5, mono::TAG_SIZE);
-// const FN_POINTER: Value = Value::UInt(6, mono::TAG_SIZE);
-// const STRUCT: Value = Value::UInt(7, mono::TAG_SIZE);
-// const SUM: Value = Value::UInt(8, mono::TAG_SIZE);
-// const TUPLE: Value = Value::UInt(9, mono::TAG_SIZE);
-// const OBJECT: Value = Value::UInt(10, mono::TAG_SIZE);
-
-// (std:prelude)
-//
-// pub type Type
-//   = Int bool u8
-//   | Float
-//   | Bool
-//   | Never
-//   | Poison
-//   | Pointer   Type
-//   | FnPointer [Type] Type
-//   | Struct string [(string, Type)]
-//   | Sum    string [(string, [Type])]
-//   | Tuple  [Type]
-//   | Object string
+enum Members {
+    Fields(Vec<Type>),
+    Variants(Vec<Vec<Type>>),
+}
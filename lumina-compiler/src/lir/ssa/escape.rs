@@ -0,0 +1,289 @@
+//! Escape analysis for heap allocations
+//!
+//! An `Entry::Alloc` whose pointer provably never outlives the function it's allocated in is
+//! turned into an `Entry::Alloca`. A pointer outlives its function if it's returned, written
+//! through a pointer, handed to a function we can't see the body of, or stored inside a value which
+//! does any of those things.
+
+use super::*;
+use std::collections::HashSet;
+
+/// For every function, whether each of its parameters may outlive a call to it
+pub type ParamEscapes = Map<MonoFunc, Vec<bool>>;
+
+pub fn param_escapes(functions: &Map<MonoFunc, Function>) -> ParamEscapes {
+    let mut escapes: ParamEscapes = functions
+        .values()
+        .map(|func| vec![false; func.ssa.block_params(Block::entry()).count()])
+        .collect();
+
+    // Recursive functions passing a parameter along to themselves shouldn't by itself make it
+    // escape, so we start out assuming nothing escapes and iterate until no more are found.
+    loop {
+        let mut changed = false;
+
+        for (mfunc, func) in functions.iter() {
+            let users = users(&func.ssa);
+
+            for (i, v) in func.ssa.block_params(Block::entry()).enumerate() {
+                if !escapes[mfunc][i] && escapes_function(&func.ssa, &users, &escapes, v, false) {
+                    escapes[mfunc][i] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break escapes;
+        }
+    }
+}
+
+/// Turn the heap allocations which never outlive the function into stack allocations
+pub fn stack_allocate(ssa: &mut SSA, escapes: &ParamEscapes) -> Vec<V> {
+    let users = users(ssa);
+
+    let allocs = ssa
+        .ventries
+        .iter()
+        .filter(|(_, entry)| matches!(entry, Entry::Alloc))
+        .map(|(v, _)| v)
+        .filter(|&v| {
+            // Stack slots are reused for every iteration of a loop, so a pointer from a previous
+            // iteration would be clobbered by the next one.
+            !ssa.in_loop(block_of(ssa, v)) && !escapes_function(ssa, &users, escapes, v, true)
+        })
+        .collect::<Vec<_>>();
+
+    for &v in &allocs {
+        ssa.ventries[v] = Entry::Alloca;
+    }
+
+    allocs
+}
+
+impl SSA {
+    /// Whether the value may hold a pointer from an `Entry::Alloca` of this function
+    ///
+    /// Allocations are only put on the stack if they aren't returned from calls or written through
+    /// pointers, so it's enough to follow the values they're stored in and the blocks they're
+    /// passed to.
+    pub fn reaches_alloca(&self, value: Value) -> bool {
+        let Value::V(root) = value else {
+            return false;
+        };
+
+        let mut sources = vec![root];
+        let mut seen = HashSet::from([root]);
+
+        while let Some(v) = sources.pop() {
+            let mut source = |v: V| {
+                if seen.insert(v) {
+                    sources.push(v);
+                }
+            };
+
+            match self.entry_of(v) {
+                Entry::Alloca => return true,
+                Entry::BlockParam(block, i) => {
+                    for entry in self.ventries.values() {
+                        let jumps = match entry {
+                            Entry::JmpBlock(jump) => [Some(jump), None],
+                            Entry::Select { on_true, on_false, .. } => {
+                                [Some(on_true), Some(on_false)]
+                            }
+                            _ => continue,
+                        };
+
+                        for jump in jumps.into_iter().flatten() {
+                            if jump.id == *block {
+                                if let Value::V(v) = jump.params[*i as usize] {
+                                    source(v);
+                                }
+                            }
+                        }
+                    }
+                }
+                Entry::CallStatic(..)
+                | Entry::CallValue(..)
+                | Entry::CallExtern(..)
+                | Entry::Deref(_)
+                | Entry::AtomicLoad(_)
+                | Entry::TagFromSum { .. }
+                | Entry::IntCmpInclusive(..) => {}
+                entry => rewrite::for_entry(entry, &mut source),
+            }
+        }
+
+        false
+    }
+
+    /// Whether the struct or array read by the `Entry::Deref` at `deref` has to be copied
+    ///
+    /// Aggregates read through a pointer keep pointing into the original memory, which is only
+    /// sound if that memory can't be written to or freed before the value's last use. Values used
+    /// outside the block they're read in are copied whenever anything in the function could.
+    pub fn deref_needs_copy(&self, deref: V) -> bool {
+        let clobbers = |entry: &Entry| {
+            matches!(
+                entry,
+                Entry::WritePtr { .. }
+                    | Entry::MemCpy { .. }
+                    | Entry::Dealloc { .. }
+                    | Entry::AtomicStore { .. }
+                    | Entry::AtomicCas { .. }
+                    | Entry::AtomicRmw(..)
+                    | Entry::CallStatic(..)
+                    | Entry::CallExtern(..)
+                    | Entry::CallValue(..)
+                    | Entry::JmpFunc(..)
+            )
+        };
+
+        if !self.ventries.values().any(clobbers) {
+            return false;
+        }
+
+        let users = users(self);
+        let binfo = self.block_info(block_of(self, deref));
+        let in_block = |v: V| (binfo.start.0..=binfo.end.0).contains(&v.0);
+
+        let mut derived = vec![deref];
+        let mut seen = HashSet::from([deref]);
+        let mut last = deref;
+
+        while let Some(v) = derived.pop() {
+            for &user in &users[v] {
+                if !in_block(user) {
+                    return true;
+                }
+
+                last = V(last.0.max(user.0));
+
+                match &self.ventries[user] {
+                    // Jumping to a block hands the value to a block parameter outside this block
+                    Entry::JmpBlock(_) | Entry::Select { .. } => return true,
+                    Entry::Deref(_)
+                    | Entry::AtomicLoad(_)
+                    | Entry::TagFromSum { .. }
+                    | Entry::IntCmpInclusive(..)
+                    | Entry::JmpTable(..)
+                    | Entry::Return(_) => {}
+                    entry if clobbers(entry) => {}
+                    _ => {
+                        if seen.insert(user) {
+                            derived.push(user);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.ventries
+            .range(V(deref.0 + 1), last)
+            .any(|v| clobbers(&self.ventries[v]))
+    }
+}
+
+fn users(ssa: &SSA) -> Map<V, Vec<V>> {
+    let mut users: Map<V, Vec<V>> = ssa.ventries.keys().map(|_| vec![]).collect();
+    for (v, entry) in ssa.ventries.iter() {
+        rewrite::for_entry(entry, &mut |used| users[used].push(v));
+    }
+    users
+}
+
+/// Whether the pointer `root` or any value containing it may outlive the function
+///
+/// `local` is set when `root` is allocated by this function rather than given to it, in which case
+/// tail calls also let it escape since they replace the current stack frame.
+fn escapes_function(
+    ssa: &SSA,
+    users: &Map<V, Vec<V>>,
+    escapes: &ParamEscapes,
+    root: V,
+    local: bool,
+) -> bool {
+    let mut derived = vec![root];
+    let mut seen = HashSet::from([root]);
+
+    let passed_to_escaping = |mfunc: MonoFunc, params: &[Value], v: V| {
+        params
+            .iter()
+            .enumerate()
+            .any(|(i, p)| *p == Value::V(v) && escapes[mfunc].get(i).copied().unwrap_or(true))
+    };
+
+    while let Some(v) = derived.pop() {
+        let mut derive = |v: V| {
+            if seen.insert(v) {
+                derived.push(v);
+            }
+        };
+
+        for &user in &users[v] {
+            match &ssa.ventries[user] {
+                Entry::Return(_) | Entry::CallExtern(..) => return true,
+                Entry::JmpFunc(mfunc, params) => {
+                    if local || passed_to_escaping(*mfunc, params, v) {
+                        return true;
+                    }
+                }
+                Entry::CallStatic(mfunc, params)
+                | Entry::CallValue(Value::FuncPtr(mfunc), params) => {
+                    if passed_to_escaping(*mfunc, params, v) {
+                        return true;
+                    }
+                }
+                Entry::CallValue(..) => return true,
+                Entry::WritePtr { value, .. } | Entry::AtomicStore { value, .. } => {
+                    if *value == Value::V(v) {
+                        return true;
+                    }
+                }
+                Entry::AtomicCas { expected, replacement, .. } => {
+                    if *expected == Value::V(v) || *replacement == Value::V(v) {
+                        return true;
+                    }
+                }
+                Entry::AtomicRmw(_, [_, value]) => {
+                    if *value == Value::V(v) {
+                        return true;
+                    }
+                }
+                Entry::JmpBlock(jump) => jump_params(ssa, jump, v, &mut derive),
+                Entry::Select { on_true, on_false, .. } => {
+                    jump_params(ssa, on_true, v, &mut derive);
+                    jump_params(ssa, on_false, v, &mut derive);
+                }
+                Entry::Deref(_)
+                | Entry::AtomicLoad(_)
+                | Entry::MemCpy { .. }
+                | Entry::Dealloc { .. }
+                | Entry::TagFromSum { .. }
+                | Entry::IntCmpInclusive(..)
+                | Entry::JmpTable(..) => {}
+                _ => derive(user),
+            }
+        }
+    }
+
+    false
+}
+
+fn jump_params(ssa: &SSA, jump: &BlockJump, v: V, derive: &mut impl FnMut(V)) {
+    for (i, p) in jump.params.iter().enumerate() {
+        if *p == Value::V(v) {
+            derive(ssa.get_block_param(jump.id, i as u32));
+        }
+    }
+}
+
+fn block_of(ssa: &SSA, v: V) -> Block {
+    ssa.blocks()
+        .find(|&block| {
+            let binfo = ssa.block_info(block);
+            (binfo.start.0..=binfo.end.0).contains(&v.0)
+        })
+        .expect("value outside of any block")
+}
@@ -2,6 +2,7 @@ use super::{
     mono::MonoFormatter, Function, MonoFunc, MonoType, MonoTypeKey, TRAP_UNREACHABLE, UNIT,
 };
 use crate::{MAYBE_JUST, MAYBE_NONE};
+use cranelift_codegen::ir::AtomicRmwOp;
 use derive_more::{Add, AddAssign, From};
 use derive_new::new;
 use itertools::Itertools;
@@ -11,10 +12,13 @@ use lumina_key as key;
 use lumina_typesystem::IntSize;
 use lumina_util::{Highlighting, ParamFmt};
 use owo_colors::OwoColorize;
+use std::collections::HashSet;
 use std::fmt;
 use tracing::{info, trace};
 
+mod escape;
 mod opts;
+pub use opts::OptSettings;
 mod rewrite;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -124,6 +128,37 @@ impl SSA {
         }
     }
 
+    pub fn successors(&self, block: Block) -> Vec<Block> {
+        match self.block_info(block).tail {
+            Entry::JmpBlock(jump) => vec![jump.id],
+            Entry::Select { on_true, on_false, .. } => vec![on_true.id, on_false.id],
+            Entry::JmpTable(_, blocks) => blocks.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether `to` can be reached by following the jumps out of `from`
+    pub fn reaches(&self, from: Block, to: Block) -> bool {
+        let mut stack = self.successors(from);
+        let mut seen = HashSet::new();
+
+        while let Some(next) = stack.pop() {
+            if next == to {
+                return true;
+            }
+            if seen.insert(next) {
+                stack.extend(self.successors(next));
+            }
+        }
+
+        false
+    }
+
+    /// Whether the block can be reached from itself
+    pub fn in_loop(&self, block: Block) -> bool {
+        self.reaches(block, block)
+    }
+
     /// Checks whether this blocks values are only used within the block
     pub fn usages_outside_this_block(&self, block: Block) -> usize {
         let binfo = self.block_info(block);
@@ -150,6 +185,15 @@ impl SSA {
             .sum()
     }
 
+    /// Count the usages of every value in the function
+    pub fn use_counts(&self) -> Map<V, u32> {
+        let mut counts: Map<V, u32> = self.ventries.keys().map(|_| 0).collect();
+        for entry in self.ventries.values() {
+            rewrite::for_entry(entry, &mut |v| counts[v] += 1);
+        }
+        counts
+    }
+
     pub fn get_block_param(&self, block: Block, i: u32) -> V {
         let start = self.blocks[block].start;
         assert_ne!(start, V(u32::MAX));
@@ -291,6 +335,33 @@ impl SSA {
         self.assign(entry, ty)
     }
 
+    pub fn atomic_load(&mut self, ptr: Value, ty: MonoType) -> Value {
+        let entry = Entry::AtomicLoad(ptr);
+        self.assign(entry, ty)
+    }
+
+    pub fn atomic_store(&mut self, ptr: Value, value: Value) -> Value {
+        let entry = Entry::AtomicStore { ptr, value };
+        let ty = MonoType::Monomorphised(UNIT);
+        self.assign(entry, ty)
+    }
+
+    pub fn atomic_cas(
+        &mut self,
+        ptr: Value,
+        expected: Value,
+        replacement: Value,
+        ty: MonoType,
+    ) -> Value {
+        let entry = Entry::AtomicCas { ptr, expected, replacement };
+        self.assign(entry, ty)
+    }
+
+    pub fn atomic_rmw(&mut self, op: AtomicRmwOp, ptr: Value, value: Value, ty: MonoType) -> Value {
+        let entry = Entry::AtomicRmw(op, [ptr, value]);
+        self.assign(entry, ty)
+    }
+
     pub fn transmute(&mut self, v: Value, to: MonoType) -> Value {
         let entry = Entry::Transmute(v);
         self.assign(entry, to)
@@ -665,6 +736,19 @@ pub enum Entry {
         count: Value,
     },
     Deref(Value),
+
+    // Atomics
+    AtomicLoad(Value),
+    AtomicStore {
+        ptr: Value,
+        value: Value,
+    },
+    AtomicCas {
+        ptr: Value,
+        expected: Value,
+        replacement: Value,
+    },
+    AtomicRmw(AtomicRmwOp, [Value; 2]),
 }
 
 #[derive(From, Clone, Copy, PartialEq)]
@@ -836,6 +920,26 @@ impl<'a, 'e> fmt::Display for EntryFmt<'a, 'e> {
             Entry::MemCpy { dst, src, count } => {
                 write!(f, "{} {dst} {src} {count}", "memcpy".keyword())
             }
+            Entry::AtomicLoad(ptr) => write!(f, "{} {ptr}", "atomic-load".keyword()),
+            Entry::AtomicStore { ptr, value } => {
+                write!(
+                    f,
+                    "{} {ptr} {} {value}",
+                    "atomic-store".keyword(),
+                    "<-".symbol()
+                )
+            }
+            Entry::AtomicCas { ptr, expected, replacement } => {
+                write!(
+                    f,
+                    "{} {ptr} {expected} {replacement}",
+                    "atomic-cas".keyword()
+                )
+            }
+            Entry::AtomicRmw(op, [ptr, value]) => {
+                let header = format!("atomic-{op}");
+                write!(f, "{} {ptr} {value}", header.keyword())
+            }
             Entry::IntToFloat(v, _) => {
                 write!(f, "{} {v}", "int_to_float".keyword())
             }
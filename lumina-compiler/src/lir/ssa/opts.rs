@@ -1,40 +1,265 @@
 //! Optimizations that are more specific to Lumina and more appropriate for a higher-level IR than
 //! our backends.
 
+use crate::ast::Inline;
+use crate::debuginfo::Item;
 use crate::lir::{
-    ssa::rewrite::{
-        for_entry_mut, for_value_mut, for_values_mut, insert_buf, offset_predecessors, Rewrite,
+    comptime::{int_cmp, wrap},
+    ssa::{
+        escape,
+        rewrite::{
+            for_entry, for_entry_mut, for_value_mut, for_values_mut, insert_buf,
+            offset_predecessors, Rewrite,
+        },
+        BasicBlock,
     },
-    Block, BlockJump, Entry, Function, MonoFunc, MonoType, Value, LIR, SSA, V,
+    BinOp, Block, BlockJump, Entry, Function, MonoFunc, MonoType, Types, Value, LIR, SSA, V,
 };
+use crate::prelude::key;
+use crate::{Pass, Profile};
+use itertools::Itertools;
+use lumina_typesystem::IntSize;
 use smallvec::SmallVec;
+use std::collections::{HashMap, HashSet};
 use std::mem::take;
 use tracing::{info, info_span, trace};
 
-pub const ENABLE_OPTS: bool = true;
+/// Roughly the cost of performing a call, functions cheaper than this are always inlined
+const CALL_COST: u32 = 3;
+
+/// How many times over its own budget a function may grow the program by being inlined everywhere
+const MAX_INLINE_GROWTH: u32 = 8;
+
+pub struct OptSettings {
+    /// Passes to run, the others are skipped entirely
+    pub passes: HashSet<Pass>,
+    /// Largest cost of a function for it to be inlined at all of its call sites
+    pub inline_threshold: u32,
+    /// How many changing iterations all passes may perform in total, used to bisect miscompilations
+    pub fuel: Option<usize>,
+    /// Print every pass and the functions it changed to stderr
+    pub print_passes: bool,
+}
+
+impl OptSettings {
+    pub fn new(profile: &Profile) -> Self {
+        OptSettings {
+            passes: Pass::ALL
+                .into_iter()
+                .filter(|&pass| profile.enabled(pass))
+                .collect(),
+            inline_threshold: profile.inline_threshold(),
+            fuel: None,
+            print_passes: false,
+        }
+    }
+}
+
+impl Default for OptSettings {
+    fn default() -> Self {
+        OptSettings::new(&Profile::default())
+    }
+}
 
 impl LIR {
-    pub fn perform_optimizations(&mut self) {
-        let mut opt = Optimizer { lir: self };
+    pub fn perform_optimizations(&mut self, settings: &OptSettings) {
+        let fuel = Fuel {
+            remaining: settings.fuel,
+            print_passes: settings.print_passes,
+        };
+        let mut opt = Optimizer { lir: self, settings, fuel };
         opt.optimizations();
     }
 }
 
 struct Optimizer<'a> {
     lir: &'a mut LIR,
+    settings: &'a OptSettings,
+    fuel: Fuel,
+}
+
+struct Fuel {
+    remaining: Option<usize>,
+    print_passes: bool,
+}
+
+impl Fuel {
+    fn is_empty(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    fn spend(&mut self) {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+
+            if *remaining == 0 {
+                info!("out of optimization fuel");
+
+                if self.print_passes {
+                    eprintln!("  out of fuel");
+                }
+            }
+        }
+    }
 }
 
 type Changed = bool;
 
 impl<'a> Optimizer<'a> {
     fn optimizations(&mut self) {
-        if !ENABLE_OPTS {
+        self.pass(Pass::Fold, |opt| opt.fold_opts(1000));
+        self.pass(Pass::Values, |opt| opt.value_opts(1000));
+        // Before block optimizations turn self tail calls into loops, so that they're redirected to
+        // the specialisation and keep passing along the closure it rebuilds.
+        self.pass(Pass::Specialise, |opt| opt.specialise_opts(1000));
+        self.pass(Pass::Blocks, |opt| opt.block_opts(1000));
+        // Calls through the specialised lambdas become static calls for the inliner
+        self.pass(Pass::Fold, |opt| opt.fold_opts(1000));
+        self.pass(Pass::Inline, |opt| opt.func_opts(1000));
+        // Inlined functions are merged into the caller first, so that folding can see through them
+        self.pass(Pass::Blocks, |opt| opt.block_opts(1000));
+        self.pass(Pass::Fold, |opt| opt.fold_opts(1000));
+        self.pass(Pass::Escape, |opt| opt.escape_opts());
+        self.pass(Pass::Values, |opt| opt.value_opts(1000));
+        self.pass(Pass::Blocks, |opt| opt.block_opts(5));
+    }
+
+    fn pass(&mut self, pass: Pass, run: impl FnOnce(&mut Self)) {
+        if !self.settings.passes.contains(&pass) || self.fuel.is_empty() {
             return;
         }
 
-        self.block_opts(1000);
-        self.func_opts(1000);
-        self.block_opts(5);
+        if self.settings.print_passes {
+            eprintln!("{pass}");
+        }
+
+        run(self);
+    }
+
+    fn report_change(&self, fkey: MonoFunc) {
+        if self.settings.print_passes {
+            eprintln!("  {fkey} ({})", &self.lir.functions[fkey].symbol);
+        }
+    }
+
+    fn fold_opts(&mut self, fuel: usize) {
+        for fkey in self.lir.functions.keys() {
+            let _span = info_span!(
+                "running constant folding",
+                entity = self.lir.functions[fkey].symbol.clone(),
+            );
+            let _handle = _span.enter();
+
+            let func = &mut self.lir.functions[fkey];
+
+            let mut fuel = fuel;
+            let mut any_change = false;
+            while fuel != 0 && !self.fuel.is_empty() && fold_iter(func, &self.lir.mono.types) {
+                any_change = true;
+                fuel -= 1;
+                self.fuel.spend();
+            }
+
+            if !self.fuel.is_empty() {
+                let called = devirtualise(&mut func.ssa);
+                for &mfunc in &called {
+                    self.lir.functions[mfunc].invocations += 1;
+                }
+
+                if !called.is_empty() {
+                    any_change = true;
+                    self.fuel.spend();
+                }
+            }
+
+            if any_change {
+                self.report_change(fkey);
+                info!(
+                    "post-folding {fkey} ({}):\n{}",
+                    &self.lir.functions[fkey].symbol,
+                    self.lir.mono.fmt(&self.lir.functions[fkey])
+                );
+            }
+        }
+    }
+
+    fn specialise_opts(&mut self, fuel: usize) {
+        // Specialisations are appended to the functions as we go, so that calls inside of them are
+        // specialised as well.
+        let mut i = 0;
+        while i < self.lir.functions.len() {
+            let fkey = MonoFunc::from(i);
+            i += 1;
+
+            let _span = info_span!(
+                "running closure specialisation",
+                entity = self.lir.functions[fkey].symbol.clone(),
+            );
+            let _handle = _span.enter();
+
+            let any_change = self.with_fuel(fuel, |lir| specialise_iter(lir, fkey));
+
+            if any_change {
+                self.report_change(fkey);
+                info!(
+                    "post-specialisation {fkey} ({}):\n{}",
+                    &self.lir.functions[fkey].symbol,
+                    self.lir.mono.fmt(&self.lir.functions[fkey])
+                );
+            }
+        }
+    }
+
+    fn escape_opts(&mut self) {
+        let escapes = escape::param_escapes(&self.lir.functions);
+
+        for fkey in self.lir.functions.keys() {
+            let _span = info_span!(
+                "running escape analysis",
+                entity = self.lir.functions[fkey].symbol.clone(),
+            );
+            let _handle = _span.enter();
+
+            if self.fuel.is_empty() {
+                break;
+            }
+
+            let allocs = escape::stack_allocate(&mut self.lir.functions[fkey].ssa, &escapes);
+
+            if !allocs.is_empty() {
+                self.fuel.spend();
+                self.report_change(fkey);
+                info!(
+                    "moved {} to the stack in {fkey} ({})",
+                    allocs.iter().format(", "),
+                    &self.lir.functions[fkey].symbol,
+                );
+            }
+        }
+    }
+
+    fn value_opts(&mut self, fuel: usize) {
+        for fkey in self.lir.functions.keys() {
+            let _span = info_span!(
+                "running value optimizations",
+                entity = self.lir.functions[fkey].symbol.clone(),
+            );
+            let _handle = _span.enter();
+
+            let any_change = self.with_fuel(fuel, |lir| {
+                let ssa = &mut lir.functions[fkey].ssa;
+                cse_iter(ssa) | dead_value_iter(ssa)
+            });
+
+            if any_change {
+                self.report_change(fkey);
+                info!(
+                    "post-value-optimizations {fkey} ({}):\n{}",
+                    &self.lir.functions[fkey].symbol,
+                    self.lir.mono.fmt(&self.lir.functions[fkey])
+                );
+            }
+        }
     }
 
     fn block_opts(&mut self, fuel: usize) {
@@ -65,6 +290,7 @@ impl<'a> Optimizer<'a> {
             });
 
             if any_change {
+                self.report_change(fkey);
                 info!(
                     "post-optimizations {fkey} ({}):\n{}",
                     &self.lir.functions[fkey].symbol,
@@ -82,12 +308,13 @@ impl<'a> Optimizer<'a> {
             );
             let _handle = _span.enter();
 
+            let settings = self.settings;
             let any_change = self.with_fuel(fuel, |lir| {
                 let ssa = &lir.functions[fkey].ssa;
                 let changed = ssa
                     .blocks
                     .keys()
-                    .any(|block| func_opt_iter(lir, fkey, block));
+                    .any(|block| func_opt_iter(lir, settings, fkey, block));
 
                 if changed {
                     trace!(
@@ -101,6 +328,7 @@ impl<'a> Optimizer<'a> {
             });
 
             if any_change {
+                self.report_change(fkey);
                 info!(
                     "post-optimizations func {fkey} ({}):\n{}",
                     &self.lir.functions[fkey].symbol,
@@ -114,10 +342,14 @@ impl<'a> Optimizer<'a> {
         let mut changed = true;
         let mut any_change = false;
 
-        while fuel != 0 && changed {
+        while fuel != 0 && changed && !self.fuel.is_empty() {
             changed = f(self.lir);
             any_change |= changed;
 
+            if changed {
+                self.fuel.spend();
+            }
+
             fuel -= 1;
         }
 
@@ -125,7 +357,7 @@ impl<'a> Optimizer<'a> {
     }
 }
 
-fn block_opt_iter(func: &mut Function, _: MonoFunc, block: Block) -> Changed {
+fn block_opt_iter(func: &mut Function, fkey: MonoFunc, block: Block) -> Changed {
     if func.ssa.blocks[block].predecessors == 0 {
         info!("purging {block}");
         func.ssa.purge_block(block);
@@ -142,20 +374,12 @@ fn block_opt_iter(func: &mut Function, _: MonoFunc, block: Block) -> Changed {
                 full_block_inline(&mut func.ssa, v, &jump);
                 return true;
             }
-            // Substitute tail call to self with a jump to the entry block instead
-            // TODO: re-add this but fix the cranelift limitation.
-            // Entry::JmpFunc(mfunc, _) if *mfunc == fkey => {
-            //     info!("substiuting call to self with jump to entry block");
-            //     let Entry::JmpFunc(_, params) = &mut func.ssa.ventries[v] else {
-            //         unreachable!()
-            //     };
-            //     // TODO: if we increment predecessors here, we can use that information to work
-            //     // around cranelift optimizations technically not supporting jump to entrypoint.
-            //     let jump = BlockJump::new(Block::entry(), take(params));
-            //     func.ssa.blocks[Block::entry()].predecessors += 1;
-            //     func.ssa.ventries[v] = Entry::JmpBlock(jump);
-            //     return true;
-            // }
+            // Substitute tail calls to self with jumps to a loop header
+            Entry::JmpFunc(mfunc, _) if *mfunc == fkey => {
+                info!("substituting tail calls to self with jumps to a loop header");
+                loop_self_tail_calls(func, fkey);
+                return true;
+            }
             Entry::Select { on_true, on_false, .. } => {
                 let new_on_true = try_inline_blockjump(&func.ssa, on_true);
                 let new_on_false = try_inline_blockjump(&func.ssa, on_false);
@@ -212,7 +436,8 @@ fn block_opt_iter(func: &mut Function, _: MonoFunc, block: Block) -> Changed {
                     .iter()
                     .map(|block| {
                         let jmp = BlockJump::new(*block, vec![]);
-                        try_inline_blockjump(&func.ssa, &jmp)
+                        // Jump tables can't pass parameters
+                        try_inline_blockjump(&func.ssa, &jmp).filter(|jmp| jmp.params.is_empty())
                     })
                     .collect::<SmallVec<[_; 5]>>();
 
@@ -226,6 +451,8 @@ fn block_opt_iter(func: &mut Function, _: MonoFunc, block: Block) -> Changed {
                     if let Some(new) = rejump {
                         changed = true;
                         info!("jump-inlining {} => {} inside jump table", jump, new.id);
+                        func.ssa.blocks[*jump].predecessors -= 1;
+                        func.ssa.blocks[new.id].predecessors += 1;
                         *jump = new.id;
                     }
                 }
@@ -240,6 +467,66 @@ fn block_opt_iter(func: &mut Function, _: MonoFunc, block: Block) -> Changed {
     false
 }
 
+// Turn all tail calls to the function itself into jumps to a loop header.
+//
+// Cranelift doesn't allow jumping to the entry block. So the entry block becomes the loop
+// header, and a new entry block is put in front of it which forwards the function parameters.
+fn loop_self_tail_calls(func: &mut Function, fkey: MonoFunc) {
+    let ssa = &mut func.ssa;
+
+    let params = ssa.block_params(Block::entry()).count() as u32;
+    let forwarded = (0..params).map(|i| V(i).value()).collect();
+    prepend_entry_block(ssa, vec![], forwarded);
+
+    let header = Block(1);
+
+    for v in ssa.ventries.keys() {
+        if let Entry::JmpFunc(mfunc, params) = &mut ssa.ventries[v] {
+            if *mfunc == fkey {
+                let jump = BlockJump::new(header, take(params));
+                ssa.ventries[v] = Entry::JmpBlock(jump);
+                ssa.blocks[header].predecessors += 1;
+                func.invocations -= 1;
+            }
+        }
+    }
+}
+
+// Insert a new entry block taking the same parameters, which runs `prologue` and then jumps to
+// the previous entry block (now `Block(1)`) with `forwarded`.
+//
+// Both refer to values as they'll be numbered in the new block, with the parameters first followed
+// by the prologue.
+fn prepend_entry_block(ssa: &mut SSA, prologue: Vec<(Entry, MonoType)>, forwarded: Vec<Value>) {
+    let types = ssa.param_types(Block::entry()).cloned().collect::<Vec<_>>();
+    let params = types.len() as u32;
+
+    // Make room for the new entry block and its values
+    let mut r = Rewrite::new(V(0), Block(0));
+    r.voff = (params as usize + prologue.len()) as i32 + 1;
+    r.boff = 1;
+    ssa.apply(V(0), &r);
+
+    let (pentries, ptypes): (Vec<_>, Vec<_>) = prologue.into_iter().unzip();
+
+    let entries = (0..params)
+        .map(|i| Entry::BlockParam(Block::entry(), i))
+        .chain(pentries)
+        .chain([Entry::JmpBlock(BlockJump::new(Block(1), forwarded))]);
+    insert_buf(V(0), &mut ssa.ventries, entries, false);
+    insert_buf(
+        V(0),
+        &mut ssa.vtypes,
+        types.into_iter().chain(ptypes).chain([MonoType::unit()]),
+        false,
+    );
+
+    let mut entry = BasicBlock::new();
+    entry.start = V(0);
+    entry.predecessors = 1;
+    ssa.blocks.as_mut_vec().insert(0, entry);
+}
+
 // When blocks contain binds, only single-use or scope-pure blocks are inlineable.
 //
 // TODO: avoid inlining things like jump tables, instead of just going by bind count
@@ -261,7 +548,7 @@ fn full_block_inline(ssa: &mut SSA, atv: V, jump: &BlockJump) {
     r.new_block_params = Some((binfo.start, binfo.end, &jump.params));
 
     let (mut inlinedv, mut inlinedt) =
-        get_inlined_entries(ssa, r.clone(), V(binfo.start.0 + binfo.params), binfo.end);
+        get_inlined_entries(ssa, atv, binfo.start, binfo.params, binfo.end, &jump.params);
 
     offset_predecessors(ssa, binfo.end, 1);
 
@@ -295,6 +582,9 @@ fn full_func_inline(func: &mut SSA, ofunc: &SSA, atv: V, params: Vec<Value>) ->
 
     let mut to_bump = vec![];
 
+    // Tail calls are typed as unit when lowered, while the injected call returns what we return
+    let ret = func.vtypes[atv].clone();
+
     // Copy and compatible-ize the entries from the function we're inlining.
     let mut injected: Vec<V> = vec![];
     let (entries, types): (Vec<_>, Vec<_>) = ofunc
@@ -322,7 +612,7 @@ fn full_func_inline(func: &mut SSA, ofunc: &SSA, atv: V, params: Vec<Value>) ->
 
                 injected.push(v);
 
-                vec![(call, ty.clone()), (con_jump, ty)]
+                vec![(call, ret.clone()), (con_jump, ty)]
             }
             mut entry => {
                 match entry {
@@ -399,24 +689,41 @@ fn full_func_inline(func: &mut SSA, ofunc: &SSA, atv: V, params: Vec<Value>) ->
     to_bump
 }
 
-fn get_inlined_entries<'p>(
+fn get_inlined_entries(
     ssa: &SSA,
-    mut r: Rewrite<'p>,
-    start: V,
+    atv: V,
+    bstart: V,
+    bparams: u32,
     end: V,
+    params: &[Value],
 ) -> (Vec<Entry>, Vec<MonoType>) {
+    let start = V(bstart.0 + bparams);
     let range = start.0 as usize..=end.0 as usize;
+    let len = range.clone().count() as u32;
 
     let mut entries = ssa.ventries.as_slice()[range.clone()].to_vec();
     let types = ssa.vtypes.as_slice()[range].to_vec();
 
     // Offset the new inlined to start at the current V.
     //
-    // since voff is signed, this should work regardless of whether `atv` is before or after
-    // `jump.id` is defined.
-    r.voff = (r.atv.0 as i32 - 1) - start.0 as i32;
+    // The block's own values are moved explicitly rather than through `Rewrite`, since a block
+    // defined before the jump (such as a loop header) would otherwise keep referring to the original.
+    // Values after the jump are offset by the inline size, same as `apply` does for the function.
+    let mut map = |v: V| {
+        if v.0 >= bstart.0 && v.0 < start.0 {
+            params[(v.0 - bstart.0) as usize]
+        } else if v.0 >= start.0 && v.0 <= end.0 {
+            Value::V(V(atv.0 + v.0 - start.0))
+        } else if v.0 > atv.0 {
+            Value::V(V(v.0 + len - 1))
+        } else {
+            Value::V(v)
+        }
+    };
 
-    entries.iter_mut().for_each(|entry| r.entry(false, entry));
+    entries
+        .iter_mut()
+        .for_each(|entry| for_entry_mut(entry, &mut map, &mut |b| b));
 
     (entries, types)
 }
@@ -451,10 +758,274 @@ fn try_inline_blockjump(ssa: &SSA, ijump: &BlockJump) -> Option<BlockJump> {
     None
 }
 
-fn func_opt_iter(lir: &mut LIR, func: MonoFunc, _: Block) -> Changed {
+// Evaluate entries whose operands are known and substitute their uses with the result.
+//
+// Constant `Select` and `JmpTable` are turned into direct jumps. The blocks left without
+// predecessors are then removed by `block_opt_iter`.
+//
+// Folded entries are left in place without users.
+fn fold_iter(func: &mut Function, types: &Types) -> Changed {
+    let mut folded: HashMap<V, Value> = HashMap::new();
+    let mut changed = false;
+
+    let mut incoming: HashMap<Block, Vec<Vec<Value>>> = HashMap::new();
+    for entry in func.ssa.ventries.values() {
+        for jump in jumps(entry) {
+            incoming
+                .entry(jump.id)
+                .or_default()
+                .push(jump.params.clone());
+        }
+    }
+
+    for v in func.ssa.ventries.keys() {
+        let resolve = |value: Value| match value {
+            Value::V(v) => folded.get(&v).copied().unwrap_or(value),
+            _ => value,
+        };
+
+        match &func.ssa.ventries[v] {
+            Entry::Select { value, on_true, on_false } => {
+                let Value::Int(n, _) = resolve(*value) else {
+                    continue;
+                };
+
+                let taken = if n != 0 {
+                    on_true.clone()
+                } else {
+                    on_false.clone()
+                };
+                let targets = [on_true.id, on_false.id];
+
+                info!("folding select into a jump to {}", taken.id);
+                remove_untaken_edges(&mut func.ssa, targets, taken.id);
+                func.ssa.ventries[v] = Entry::JmpBlock(taken);
+                changed = true;
+            }
+            Entry::JmpTable(value, blocks) => {
+                let Value::Int(n, _) = resolve(*value) else {
+                    continue;
+                };
+
+                let Some(taken) = usize::try_from(n).ok().and_then(|n| blocks.get(n).copied())
+                else {
+                    continue;
+                };
+
+                info!("folding jump table into a jump to {taken}");
+                let targets = blocks.clone();
+                remove_untaken_edges(&mut func.ssa, targets, taken);
+                func.ssa.ventries[v] = Entry::JmpBlock(BlockJump::new(taken, vec![]));
+                changed = true;
+            }
+            // A block parameter always given the same value, besides itself when looping
+            //
+            // Values are expected to be declared before they're used, so a value declared further
+            // down is left as a parameter.
+            Entry::BlockParam(block, i) if *block != Block::entry() => {
+                let mut given = incoming
+                    .get(block)
+                    .into_iter()
+                    .flatten()
+                    .map(|params| resolve(params[*i as usize]))
+                    .filter(|value| *value != Value::V(v));
+
+                let Some(first) = given.next() else {
+                    continue;
+                };
+
+                let declared = !matches!(first, Value::V(first) if first.0 > v.0);
+
+                if declared && given.all(|value| value == first) {
+                    trace!("folding {v} into {first}");
+                    folded.insert(v, first);
+                }
+            }
+            entry => {
+                if let Some(value) = fold_entry(&func.ssa, types, v, entry, resolve) {
+                    trace!("folding {v} into {value}");
+                    folded.insert(v, value);
+                }
+            }
+        }
+    }
+
+    substitute(&mut func.ssa, &folded) | changed
+}
+
+// Predecessors are counted per edge, so a block targeted several times by the same terminator
+// has one predecessor per target. When folding it into a jump, only one edge to the taken block
+// remains.
+fn remove_untaken_edges(ssa: &mut SSA, targets: impl IntoIterator<Item = Block>, taken: Block) {
+    let targets = targets.into_iter().collect::<SmallVec<[_; 5]>>();
+
+    for block in targets.iter().copied().unique() {
+        let edges = targets.iter().filter(|&&b| b == block).count() as u16;
+        let removed = if block == taken { edges - 1 } else { edges };
+        ssa.blocks[block].predecessors -= removed;
+    }
+}
+
+fn fold_entry(
+    ssa: &SSA,
+    types: &Types,
+    v: V,
+    entry: &Entry,
+    resolve: impl Fn(Value) -> Value,
+) -> Option<Value> {
+    let int = |value: Value| match resolve(value) {
+        Value::Int(n, size) => Some((wrap(n, size), size)),
+        _ => None,
+    };
+
+    if let Entry::Field { of: Value::V(of), field, .. } = entry {
+        return match ssa.entry_of(*of) {
+            Entry::Construct(params) => Some(resolve(params[field.0 as usize])),
+            _ => None,
+        };
+    }
+
+    let MonoType::Int(to) = *ssa.type_of(v) else {
+        return None;
+    };
+
+    let n = match entry {
+        Entry::BinOp(op, [lhs, rhs]) => {
+            let ((lhs, _), (rhs, _)) = (int(*lhs)?, int(*rhs)?);
+            let (lhs, rhs) = (wrap(lhs, to), wrap(rhs, to));
+            // 64-bit operands can overflow an i128 when multiplied, the low bits are kept either way
+            let n = match op {
+                BinOp::Add => lhs.wrapping_add(rhs),
+                BinOp::Sub => lhs.wrapping_sub(rhs),
+                BinOp::Mul => lhs.wrapping_mul(rhs),
+                BinOp::Div if rhs == 0 => return None,
+                BinOp::Div => lhs / rhs,
+                BinOp::And => lhs & rhs,
+            };
+
+            // Signed division overflow traps at runtime, so it has to stay
+            if *op == BinOp::Div && wrap(n, to) != n {
+                return None;
+            }
+
+            n
+        }
+        Entry::IntCmpInclusive([lhs, rhs], ord, size) => {
+            int_cmp(int(*lhs)?.0, int(*rhs)?.0, *ord, *size) as i128
+        }
+        Entry::TagFromSum { of: Value::V(of) } => match ssa.entry_of(*of) {
+            Entry::Variant(var, _) => var.0 as i128,
+            _ => return None,
+        },
+        Entry::Reduce(v) => int(*v)?.0,
+        Entry::ExtendSigned(v) => {
+            let (n, from) = int(*v)?;
+            wrap(n, IntSize::new(true, from.bits()))
+        }
+        Entry::ExtendUnsigned(v) => {
+            let (n, from) = int(*v)?;
+            wrap(n, IntSize::new(false, from.bits()))
+        }
+        Entry::BitNot(v) => !int(*v)?.0,
+        Entry::SizeOf(ty) => types.scalar_size_and_align_of(ty)?.0 as i128,
+        Entry::AlignOf(ty) => types.scalar_size_and_align_of(ty)?.1 as i128,
+        _ => return None,
+    };
+
+    Some(Value::Int(wrap(n, to), to))
+}
+
+// Calls through a function pointer folded into a constant become static calls
+fn devirtualise(ssa: &mut SSA) -> Vec<MonoFunc> {
+    let mut called = vec![];
+
+    for entry in ssa.ventries.values_mut() {
+        if let Entry::CallValue(Value::FuncPtr(mfunc), params) = entry {
+            info!("devirtualising the call to {mfunc}");
+            called.push(*mfunc);
+            *entry = Entry::CallStatic(*mfunc, take(params));
+        }
+    }
+
+    called
+}
+
+// Block-local value numbering
+//
+// Pure entries identical to an earlier entry in the same block have their uses replaced by that
+// earlier value, leaving the duplicate for `dead_value_iter` to remove.
+fn cse_iter(ssa: &mut SSA) -> Changed {
+    let mut duplicates: HashMap<V, Value> = HashMap::new();
+
+    for block in ssa.blocks() {
+        let binfo = ssa.block_info(block);
+        let mut seen: Vec<V> = vec![];
+
+        for v in ssa.ventries.range(binfo.start, binfo.end) {
+            let entry = &ssa.ventries[v];
+            if !entry.is_pure() {
+                continue;
+            }
+
+            let previous = seen
+                .iter()
+                .find(|&&p| ssa.ventries[p] == *entry && ssa.vtypes[p] == ssa.vtypes[v]);
+
+            match previous {
+                Some(&p) => {
+                    trace!("{v} is a duplicate of {p}");
+                    duplicates.insert(v, p.value());
+                }
+                None => seen.push(v),
+            }
+        }
+    }
+
+    substitute(ssa, &duplicates)
+}
+
+// Delete pure entries without any users
+fn dead_value_iter(ssa: &mut SSA) -> Changed {
+    let counts = ssa.use_counts();
+
+    let dead = ssa
+        .ventries
+        .iter()
+        .filter(|(v, entry)| counts[*v] == 0 && entry.is_pure())
+        .map(|(v, _)| v)
+        .collect::<Vec<_>>();
+
+    // Deleting from the back keeps the earlier dead values in place
+    for &v in dead.iter().rev() {
+        info!("removing unused {v}");
+        ssa.delete_unused(v);
+    }
+
+    !dead.is_empty()
+}
+
+// Replace all uses of the given values
+fn substitute(ssa: &mut SSA, with: &HashMap<V, Value>) -> Changed {
+    let mut changed = false;
+
+    for entry in ssa.ventries.values_mut() {
+        let mut uses_replaced = false;
+        for_entry(entry, &mut |v| uses_replaced |= with.contains_key(&v));
+
+        if uses_replaced {
+            let mut on_v = |v| with.get(&v).copied().unwrap_or(Value::V(v));
+            for_entry_mut(entry, &mut on_v, &mut |b| b);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn func_opt_iter(lir: &mut LIR, settings: &OptSettings, func: MonoFunc, _: Block) -> Changed {
     for v in lir.functions[func].ssa.ventries.keys() {
         match &lir.functions[func].ssa.ventries[v] {
-            Entry::CallStatic(mfunc, params) if should_inline(lir, *mfunc) => {
+            Entry::CallStatic(mfunc, params) if should_inline(lir, settings, func, *mfunc) => {
                 info!(
                     "inlining the call to {} inside of {}",
                     &lir.functions[*mfunc].symbol, &lir.functions[func].symbol
@@ -473,75 +1044,470 @@ fn func_opt_iter(lir: &mut LIR, func: MonoFunc, _: Block) -> Changed {
     false
 }
 
-// inline small functions or those who are only invoked once
-//
-// TODO: make sure directly recursive functions still work to inline
-// (I think they can since it can re-jump to entry)
-fn should_inline(lir: &LIR, func: MonoFunc) -> bool {
-    let func = &lir.functions[func];
-    func.invocations == 1
-        || (func.ssa.ventries.len() - func.ssa.block_params(Block::entry()).count()) < 3
-}
-
-impl Entry {
-    pub fn is_terminator(&self) -> bool {
-        match self {
-            Entry::JmpFunc(..)
-            | Entry::JmpBlock(..)
-            | Entry::Return(..)
-            | Entry::Select { .. }
-            | Entry::JmpTable(..)
-            | Entry::Trap(..) => true,
-            _ => false,
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::debuginfo::Item;
-    use crate::lir::{ty_fmt, MonoType};
-    use lumina_key as key;
-    use lumina_key::{Map, M};
-    use tracing::info;
+// Redirect a call passing a lambda known at the call site to a copy of the callee specialised for
+// that lambda, turning the calls through the closure into static calls.
+fn specialise_iter(lir: &mut LIR, func: MonoFunc) -> Changed {
+    let ssa = &lir.functions[func].ssa;
 
-    fn test_block_opts(hi: bool, name: &str, ssa: SSA) -> (String, String) {
-        let mut fuel = 5;
+    let found = ssa.ventries.iter().find_map(|(v, entry)| {
+        let (Entry::CallStatic(callee, params) | Entry::JmpFunc(callee, params)) = entry else {
+            return None;
+        };
 
-        let item = Item::Defined(M(key::Module::from(0), key::Func::from(0)));
-        let mut func = Function::new(name.into(), item, ssa, MonoType::u(8), 1);
+        params.iter().enumerate().find_map(|(i, p)| {
+            let thunk = known_lambda(lir, ssa, *p)?;
+            let key = (*callee, i as u32, thunk);
+
+            match lir.memo_specialisations.get(&key) {
+                Some(&mfunc) if mfunc == *callee => None,
+                Some(_) => Some((v, key)),
+                None => {
+                    let cssa = &lir.functions[*callee].ssa;
+                    calls_closure(cssa, cssa.get_block_param(Block::entry(), i as u32))
+                        .then_some((v, key))
+                }
+            }
+        })
+    });
 
-        let mut changed = true;
+    let Some((v, (callee, i, thunk))) = found else {
+        return false;
+    };
 
-        let types = Map::new();
-        lumina_util::enable_highlighting(hi);
-        let before = format!("BEFORE:\n{}", ty_fmt(&types, &func.ssa));
+    let mfunc = specialise(lir, callee, i, thunk);
+    info!(
+        "specialising the call to {} inside of {} for {thunk}",
+        &lir.functions[callee].symbol, &lir.functions[func].symbol
+    );
 
-        while fuel != 0 && changed {
-            changed = func
-                .ssa
-                .blocks
-                .keys()
-                .any(|block| block_opt_iter(&mut func, MonoFunc(0), block));
+    match &mut lir.functions[func].ssa.ventries[v] {
+        Entry::CallStatic(target, _) | Entry::JmpFunc(target, _) => *target = mfunc,
+        _ => unreachable!(),
+    }
 
-            if changed {
-                info!("MIDDLE OPTIMIZATION:\n{}", ty_fmt(&types, &func));
-            }
+    lir.functions[callee].invocations -= 1;
+    lir.functions[mfunc].invocations += 1;
 
-            fuel -= 1;
-        }
+    true
+}
 
-        let after = format!("AFTER:\n{}", ty_fmt(&types, &func.ssa));
+// Copy the callee with its closure parameter rebuilt from the known lambda, so that folding can see
+// which function the closure calls.
+fn specialise(lir: &mut LIR, callee: MonoFunc, i: u32, thunk: MonoFunc) -> MonoFunc {
+    if let Some(&mfunc) = lir.memo_specialisations.get(&(callee, i, thunk)) {
+        return mfunc;
+    }
 
-        (before, after)
+    let original = &lir.functions[callee];
+    let mut ssa = original.ssa.clone();
+
+    let params = ssa.block_params(Block::entry()).count() as u32;
+    let object = ssa.type_of(V(i)).as_key();
+
+    let data = Entry::Field { of: V(i).value(), key: object, field: key::Field(0) };
+    let closure = Entry::Construct(vec![V(params).value(), Value::FuncPtr(thunk)]);
+    let prologue = vec![(data, MonoType::u8_pointer()), (closure, object.into())];
+    let forwarded = (0..params).map(|p| V(p).value()).collect();
+    prepend_entry_block(&mut ssa, prologue, forwarded);
+
+    let replaced = ssa.get_block_param(Block(1), i);
+    substitute(
+        &mut ssa,
+        &HashMap::from([(replaced, V(params + 1).value())]),
+    );
+
+    let symbol = format!("{}_with_{thunk}_at_{i}", original.symbol);
+    let mut func = Function::new(
+        symbol,
+        original.kind.clone(),
+        ssa,
+        original.returns.clone(),
+        0,
+    );
+    func.directly_recursive = original.directly_recursive;
+    func.inline = original.inline;
+
+    let mfunc = lir.functions.push(func);
+    lir.memo_specialisations.insert((callee, i, thunk), mfunc);
+    // Recursive calls passing the closure along should keep calling the specialisation
+    lir.memo_specialisations.insert((mfunc, i, thunk), mfunc);
+
+    let called = lir.functions[mfunc]
+        .ssa
+        .ventries
+        .values()
+        .filter_map(|entry| match entry {
+            Entry::CallStatic(mfunc, _) | Entry::JmpFunc(mfunc, _) => Some(*mfunc),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for mfunc in called {
+        lir.functions[mfunc].invocations += 1;
     }
 
-    #[test]
-    fn inline_block() {
-        lumina_util::test_logger();
+    mfunc
+}
 
-        let mut ssa = SSA::new();
+// The lambda of a closure object constructed in this function, following block parameters as
+// long as every jump passes along a closure with the same lambda
+fn known_lambda(lir: &LIR, ssa: &SSA, value: Value) -> Option<MonoFunc> {
+    let mut lambda = None;
+    let mut pending = vec![value];
+    let mut seen = HashSet::new();
+
+    while let Some(value) = pending.pop() {
+        let Value::V(v) = value else {
+            return None;
+        };
+
+        if !seen.insert(v) {
+            continue;
+        }
+
+        match ssa.entry_of(v) {
+            Entry::Construct(fields) if lir.mono.is_closure_object(ssa.type_of(v)) => {
+                match (fields[1], lambda) {
+                    (Value::FuncPtr(thunk), None) => lambda = Some(thunk),
+                    (Value::FuncPtr(thunk), Some(known)) if thunk == known => {}
+                    _ => return None,
+                }
+            }
+            Entry::BlockParam(block, i) if *block != Block::entry() => {
+                for entry in ssa.ventries.values() {
+                    for jump in jumps(entry) {
+                        if jump.id == *block {
+                            pending.push(jump.params[*i as usize]);
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    lambda
+}
+
+// Whether the closure parameter is called, or handed to another function which might call it
+fn calls_closure(ssa: &SSA, param: V) -> bool {
+    let mut derived = vec![param];
+    let mut seen = HashSet::from([param]);
+
+    while let Some(v) = derived.pop() {
+        for entry in ssa.ventries.values() {
+            match entry {
+                Entry::Field { of, field, .. } if *of == Value::V(v) && field.0 == 1 => {
+                    return true
+                }
+                Entry::CallStatic(_, params) | Entry::JmpFunc(_, params)
+                    if params.contains(&Value::V(v)) =>
+                {
+                    return true
+                }
+                _ => {}
+            }
+
+            for jump in jumps(entry) {
+                for (i, p) in jump.params.iter().enumerate() {
+                    let bparam = ssa.get_block_param(jump.id, i as u32);
+                    if *p == Value::V(v) && seen.insert(bparam) {
+                        derived.push(bparam);
+                    }
+                }
+            }
+        }
+    }
+
+    false
+}
+
+fn jumps(entry: &Entry) -> Vec<&BlockJump> {
+    match entry {
+        Entry::JmpBlock(jump) => vec![jump],
+        Entry::Select { on_true, on_false, .. } => vec![on_true, on_false],
+        _ => vec![],
+    }
+}
+
+// Inline functions invoked only once, and otherwise weigh the cost of the callee against the
+// threshold.
+//
+// Leaf functions and closure thunks get a larger budget since inlining them removes the call
+// entirely. Functions invoked from many places are limited by how much they'd grow the program.
+fn should_inline(lir: &LIR, settings: &OptSettings, caller: MonoFunc, callee: MonoFunc) -> bool {
+    if caller == callee {
+        return false;
+    }
+
+    let func = &lir.functions[callee];
+
+    // Inlining a recursive function would only expose another call to it
+    if calls(&func.ssa, callee) {
+        return false;
+    }
+
+    match func.inline {
+        Some(Inline::Never) => return false,
+        Some(Inline::Always) => return true,
+        _ => {}
+    }
+
+    if func.invocations == 1 {
+        return true;
+    }
+
+    let cost = inline_cost(&func.ssa);
+    if cost < CALL_COST {
+        return true;
+    }
+
+    let mut budget = settings.inline_threshold;
+    if func.inline == Some(Inline::Hint) {
+        budget *= 4;
+    }
+    if is_leaf(&func.ssa) {
+        budget *= 2;
+    }
+    if matches!(func.kind, Item::Autogenerated(..)) {
+        budget *= 2;
+    }
+
+    cost <= budget && cost.saturating_mul(func.invocations) <= budget * MAX_INLINE_GROWTH
+}
+
+// Estimated size of a function's body, with each additional block weighing as a branch
+fn inline_cost(ssa: &SSA) -> u32 {
+    let params = ssa.block_params(Block::entry()).count();
+    let entries = ssa.ventries.len() - params;
+    let branches = ssa.blocks.len() - 1;
+    (entries + branches * 2) as u32
+}
+
+fn calls(ssa: &SSA, target: MonoFunc) -> bool {
+    ssa.ventries.values().any(|entry| {
+        matches!(entry, Entry::CallStatic(mfunc, _) | Entry::JmpFunc(mfunc, _) if *mfunc == target)
+    })
+}
+
+fn is_leaf(ssa: &SSA) -> bool {
+    ssa.ventries.values().all(|entry| {
+        !matches!(
+            entry,
+            Entry::CallStatic(..)
+                | Entry::CallValue(..)
+                | Entry::CallExtern(..)
+                | Entry::JmpFunc(..)
+        )
+    })
+}
+
+impl Entry {
+    /// Whether the entry has no effect besides producing its value
+    ///
+    /// Pure entries don't read memory and can't trap, so they can be removed when unused and
+    /// deduplicated when equal.
+    pub fn is_pure(&self) -> bool {
+        match self {
+            Entry::BinOp(BinOp::Div, _) => false,
+            Entry::Construct(_)
+            | Entry::Replicate(..)
+            | Entry::Variant(..)
+            | Entry::RefStaticVal(_)
+            | Entry::Field { .. }
+            | Entry::CastFromSum { .. }
+            | Entry::TagFromSum { .. }
+            | Entry::Indice { .. }
+            | Entry::BinOp(..)
+            | Entry::IntCmpInclusive(..)
+            | Entry::IntAbs(_)
+            | Entry::Transmute(_)
+            | Entry::SizeOf(_)
+            | Entry::AlignOf(_)
+            | Entry::Reduce(_)
+            | Entry::ExtendSigned(_)
+            | Entry::ExtendUnsigned(_)
+            | Entry::IntToFloat(..)
+            | Entry::FloatToInt(..)
+            | Entry::BitNot(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_terminator(&self) -> bool {
+        match self {
+            Entry::JmpFunc(..)
+            | Entry::JmpBlock(..)
+            | Entry::Return(..)
+            | Entry::Select { .. }
+            | Entry::JmpTable(..)
+            | Entry::Trap(..) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::attr::Repr;
+    use crate::lir::{ty_fmt, MonoType, MonoTypeData, MonoTypeKey, MonomorphisedTypes};
+    use crate::Target;
+    use lumina_collections::MMap;
+    use lumina_key as key;
+    use lumina_key::{Map, M};
+    use tracing::info;
+
+    fn test_block_opts(hi: bool, name: &str, ssa: SSA) -> (String, String) {
+        let mut fuel = 5;
+
+        let item = Item::Defined(M(key::Module::from(0), key::Func::from(0)));
+        let mut func = Function::new(name.into(), item, ssa, MonoType::u(8), 1);
+
+        let mut changed = true;
+
+        let types = Map::new();
+        lumina_util::enable_highlighting(hi);
+        let before = format!("BEFORE:\n{}", ty_fmt(&types, &func.ssa));
+
+        while fuel != 0 && changed {
+            changed = func
+                .ssa
+                .blocks
+                .keys()
+                .any(|block| block_opt_iter(&mut func, MonoFunc(0), block));
+
+            if changed {
+                info!("MIDDLE OPTIMIZATION:\n{}", ty_fmt(&types, &func));
+            }
+
+            fuel -= 1;
+        }
+
+        let after = format!("AFTER:\n{}", ty_fmt(&types, &func.ssa));
+
+        (before, after)
+    }
+
+    fn test_value_opts(types: &Map<MonoTypeKey, MonoTypeData>, ssa: SSA) -> (String, String) {
+        let mut ssa = ssa;
+        let mut fuel = 5;
+
+        lumina_util::enable_highlighting(false);
+        let before = format!("BEFORE:\n{}", ty_fmt(types, &ssa));
+
+        while fuel != 0 && (cse_iter(&mut ssa) | dead_value_iter(&mut ssa)) {
+            fuel -= 1;
+        }
+
+        let after = format!("AFTER:\n{}", ty_fmt(types, &ssa));
+
+        (before, after)
+    }
+
+    fn test_types() -> MonomorphisedTypes {
+        let closure = M(key::Module(0), key::Trait::from(0));
+        MonomorphisedTypes::new(closure, 64, Repr::Lumina)
+    }
+
+    fn test_lir() -> LIR {
+        LIR::new(
+            HashMap::new(),
+            test_types(),
+            MMap::new(),
+            Target::native(),
+            MMap::new(),
+        )
+    }
+
+    fn test_fold_opts(types: &Types, name: &str, ssa: SSA) -> (String, String) {
+        let item = Item::Defined(M(key::Module::from(0), key::Func::from(0)));
+        let mut func = Function::new(name.into(), item, ssa, MonoType::u(64), 1);
+
+        lumina_util::enable_highlighting(false);
+        let mut before = format!("BEFORE:\n{}", ty_fmt(types, &func.ssa));
+
+        let mut fuel = 5;
+        while fuel != 0 && fold_iter(&mut func, types) {
+            fuel -= 1;
+        }
+
+        before += &format!("\nFOLDED:\n{}", ty_fmt(types, &func.ssa));
+
+        let mut fuel = 5;
+        while fuel != 0
+            && func
+                .ssa
+                .blocks
+                .keys()
+                .any(|block| block_opt_iter(&mut func, MonoFunc(0), block))
+        {
+            fuel -= 1;
+        }
+
+        let after = format!("AFTER:\n{}", ty_fmt(types, &func.ssa));
+
+        (before, after)
+    }
+
+    #[test]
+    fn dead_values() {
+        lumina_util::test_logger();
+
+        let mut ssa = SSA::new();
+
+        let block = [Block::entry(), ssa.new_block()];
+
+        let v0 = ssa.add_block_param(block[0], MonoType::u(8));
+        let v1 = ssa.add(v0.value(), Value::u(1, 8), MonoType::u(8));
+        let _v2 = ssa.mul(v1, v1, MonoType::u(8));
+        let v3 = ssa.sub(v0.value(), Value::u(2, 8), MonoType::u(8));
+        let _v4 = ssa.div(v0.value(), Value::u(0, 8), MonoType::u(8));
+        ssa.jump(block[1], vec![v3]);
+
+        ssa.switch_to_block(block[1]);
+        let p = ssa.add_block_param(block[1], MonoType::u(8));
+        let _v5 = ssa.size_of(MonoType::u(32), lumina_typesystem::IntSize::new(false, 64));
+        ssa.return_(p.value());
+
+        let (before, after) = test_value_opts(&Map::new(), ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn common_subexpressions() {
+        lumina_util::test_logger();
+
+        let mut records = test_types();
+        let pair = records.get_or_make_tuple(vec![MonoType::u(8), MonoType::u(8)]);
+        let outer = records.get_or_make_tuple(vec![pair.into(), MonoType::u(8)]);
+
+        let mut ssa = SSA::new();
+
+        let block = [Block::entry(), ssa.new_block()];
+
+        let v0 = ssa.add_block_param(block[0], MonoType::u(8));
+        let v1 = ssa.add(v0.value(), Value::u(1, 8), MonoType::u(8));
+        let v2 = ssa.add(v0.value(), Value::u(1, 8), MonoType::u(8));
+        let v3 = ssa.mul(v1, v1, MonoType::u(8));
+        let v4 = ssa.mul(v2, v2, MonoType::u(8));
+        let v5 = ssa.construct(vec![v3, v4], pair.into());
+        ssa.jump(block[1], vec![]);
+
+        // Not deduplicated across blocks
+        ssa.switch_to_block(block[1]);
+        let v6 = ssa.add(v0.value(), Value::u(1, 8), MonoType::u(8));
+        let v7 = ssa.construct(vec![v5, v6], outer.into());
+        ssa.return_(v7);
+
+        let (before, after) = test_value_opts(&records.types, ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn inline_block() {
+        lumina_util::test_logger();
+
+        let mut ssa = SSA::new();
 
         let block = [Block::entry(), ssa.new_block()];
 
@@ -558,6 +1524,39 @@ mod tests {
         insta::assert_snapshot!(format!("{before}\n{after}"));
     }
 
+    #[test]
+    fn inline_earlier_block() {
+        lumina_util::test_logger();
+
+        let mut ssa = SSA::new();
+
+        let block = [Block::entry(), ssa.new_block(), ssa.new_block()];
+
+        let v0 = ssa.add_block_param(block[0], MonoType::u(8));
+        ssa.select(
+            v0.value(),
+            [
+                (block[2], vec![v0.value()]),
+                (block[2], vec![Value::u(1, 8)]),
+            ],
+        );
+
+        // Declared before the block jumping to it, so its values are moved forwards when inlined
+        ssa.switch_to_block(block[1]);
+        let p = ssa.add_block_param(block[1], MonoType::u(8));
+        let v1 = ssa.mul(p.value(), p.value(), MonoType::u(8));
+        let v2 = ssa.add(v1, v0.value(), MonoType::u(8));
+        ssa.return_(v2);
+
+        ssa.switch_to_block(block[2]);
+        let q = ssa.add_block_param(block[2], MonoType::u(8));
+        let v3 = ssa.add(q.value(), Value::u(1, 8), MonoType::u(8));
+        ssa.jump(block[1], vec![v3]);
+
+        let (before, after) = test_block_opts(false, "inline_earlier_block", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
     #[test]
     fn tricky() {
         lumina_util::test_logger();
@@ -584,6 +1583,244 @@ mod tests {
         insta::assert_snapshot!(format!("{before}\n{after}"));
     }
 
+    #[test]
+    fn self_tail_call() {
+        lumina_util::test_logger();
+
+        let mut ssa = SSA::new();
+
+        let block = [Block::entry(), ssa.new_block(), ssa.new_block()];
+
+        let v0 = ssa.add_block_param(block[0], MonoType::u(8));
+        let v1 = ssa.add_block_param(block[0], MonoType::u(8));
+        let v2 = ssa.eq([v0.value(), Value::u(0, 8)], IntSize::new(false, 8));
+        ssa.select(v2, [(block[1], vec![]), (block[2], vec![])]);
+
+        ssa.switch_to_block(block[1]);
+        ssa.return_(v1.value());
+
+        ssa.switch_to_block(block[2]);
+        let v3 = ssa.sub(v0.value(), Value::u(1, 8), MonoType::u(8));
+        let v4 = ssa.add(v1.value(), v0.value(), MonoType::u(8));
+        ssa.jump(MonoFunc(0), vec![v3, v4]);
+
+        let (before, after) = test_block_opts(false, "self_tail_call", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn loop_self_tail_calls() {
+        lumina_util::test_logger();
+
+        let fkey = MonoFunc(3);
+        let other = MonoFunc(1);
+
+        let mut ssa = SSA::new();
+
+        let block = [
+            Block::entry(),
+            ssa.new_block(),
+            ssa.new_block(),
+            ssa.new_block(),
+            ssa.new_block(),
+        ];
+
+        let v0 = ssa.add_block_param(block[0], MonoType::u(8));
+        let v1 = ssa.add_block_param(block[0], MonoType::u(8));
+        let v2 = ssa.eq([v0.value(), Value::u(0, 8)], IntSize::new(false, 8));
+        ssa.select(v2, [(block[1], vec![]), (block[2], vec![])]);
+
+        ssa.switch_to_block(block[1]);
+        let v3 = ssa.eq([v1.value(), Value::u(0, 8)], IntSize::new(false, 8));
+        ssa.select(v3, [(block[3], vec![]), (block[4], vec![])]);
+
+        // Calls to self from several blocks all jump to the same loop header
+        ssa.switch_to_block(block[2]);
+        let v4 = ssa.sub(v0.value(), Value::u(1, 8), MonoType::u(8));
+        ssa.jump(fkey, vec![v4, v1.value()]);
+
+        ssa.switch_to_block(block[4]);
+        let v5 = ssa.sub(v1.value(), Value::u(1, 8), MonoType::u(8));
+        ssa.jump(fkey, vec![v0.value(), v5]);
+
+        // Tail calls to other functions are left alone
+        ssa.switch_to_block(block[3]);
+        ssa.jump(other, vec![v1.value()]);
+
+        let item = Item::Defined(M(key::Module::from(0), key::Func::from(0)));
+        let mut func = Function::new("loop".into(), item, ssa, MonoType::u(8), 3);
+
+        let types = Map::new();
+        lumina_util::enable_highlighting(false);
+        let before = format!("BEFORE:\n{}", ty_fmt(&types, &func.ssa));
+
+        super::loop_self_tail_calls(&mut func, fkey);
+        assert_eq!(func.invocations, 1);
+
+        let after = format!("AFTER:\n{}", ty_fmt(&types, &func.ssa));
+
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn escape_analysis() {
+        lumina_util::test_logger();
+
+        let item = Item::Defined(M(key::Module::from(0), key::Func::from(0)));
+        let ptr = || MonoType::Pointer(Box::new(MonoType::u(8)));
+
+        let mut records = test_types();
+        let wrapper = records.get_or_make_tuple(vec![ptr(), MonoType::u(8)]);
+
+        // Only reads through the pointer
+        let reader = {
+            let mut ssa = SSA::new();
+            let p = ssa.add_block_param(Block::entry(), ptr());
+            let v = ssa.deref(p.value(), MonoType::u(8));
+            ssa.return_(v);
+            ssa
+        };
+
+        // Hands the pointer back to the caller
+        let identity = {
+            let mut ssa = SSA::new();
+            let p = ssa.add_block_param(Block::entry(), ptr());
+            ssa.return_(p.value());
+            ssa
+        };
+
+        let mut ssa = SSA::new();
+        let block = [Block::entry(), ssa.new_block(), ssa.new_block()];
+
+        let read = ssa.alloc(MonoType::u(8));
+        ssa.write(read, Value::u(1, 8));
+        let v0 = ssa.call(MonoFunc(1), vec![read], MonoType::u(8));
+
+        let returned = ssa.alloc(MonoType::u(8));
+        let v1 = ssa.call(MonoFunc(2), vec![returned], ptr());
+
+        let stored = ssa.alloc(MonoType::u(8));
+        ssa.write(v1, stored);
+
+        ssa.jump(block[1], vec![v0]);
+
+        ssa.switch_to_block(block[1]);
+        let n = ssa.add_block_param(block[1], MonoType::u(8));
+        let looped = ssa.alloc(MonoType::u(8));
+        ssa.write(looped, n.value());
+        let v2 = ssa.call(MonoFunc(1), vec![looped], MonoType::u(8));
+        let v3 = ssa.eq([v2, Value::u(0, 8)], IntSize::new(false, 8));
+        ssa.select(v3, [(block[2], vec![]), (block[1], vec![v2])]);
+
+        ssa.switch_to_block(block[2]);
+        let wrapped = ssa.alloc(MonoType::u(8));
+        let record = ssa.construct(vec![wrapped, v2], wrapper.into());
+        ssa.return_(record);
+
+        let mut functions = Map::new();
+        for (name, ssa) in [("main", ssa), ("reader", reader), ("identity", identity)] {
+            functions.push(Function::new(
+                name.into(),
+                item.clone(),
+                ssa,
+                MonoType::u(8),
+                1,
+            ));
+        }
+
+        lumina_util::enable_highlighting(false);
+        let before = format!(
+            "BEFORE:\n{}",
+            ty_fmt(&records.types, &functions[MonoFunc(0)].ssa)
+        );
+
+        let escapes = escape::param_escapes(&functions);
+        escape::stack_allocate(&mut functions[MonoFunc(0)].ssa, &escapes);
+
+        let after = format!(
+            "AFTER:\n{}",
+            ty_fmt(&records.types, &functions[MonoFunc(0)].ssa)
+        );
+
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn closure_specialisation() {
+        lumina_util::test_logger();
+
+        let item = Item::Defined(M(key::Module::from(0), key::Func::from(0)));
+        let closure = M(key::Module::from(0), key::Trait::from(0));
+
+        let mut lir = test_lir();
+
+        let vtable = MonoType::fn_pointer([MonoType::u8_pointer(), MonoType::u(8)], MonoType::u(8));
+        let object = lir
+            .mono
+            .types
+            .push(MonoTypeData::DynTraitObject { trait_: closure, vtable: vtable.clone() });
+
+        let [apply, lambda] = [MonoFunc(1), MonoFunc(2)];
+
+        let main = {
+            let mut ssa = SSA::new();
+            let data = ssa.add_block_param(Block::entry(), MonoType::u8_pointer());
+            let f = ssa.construct(vec![data.value(), Value::FuncPtr(lambda)], object.into());
+            let v = ssa.call(apply, vec![f, Value::u(1, 8)], MonoType::u(8));
+            ssa.return_(v);
+            ssa
+        };
+
+        // Calls the closure through its vtable
+        let applyssa = {
+            let mut ssa = SSA::new();
+            let f = ssa.add_block_param(Block::entry(), object.into());
+            let x = ssa.add_block_param(Block::entry(), MonoType::u(8));
+            let data = ssa.field(f.value(), object, key::Field(0), MonoType::u8_pointer());
+            let call = ssa.field(f.value(), object, key::Field(1), vtable);
+            let v = ssa.call(call, vec![data, x.value()], MonoType::u(8));
+            ssa.return_(v);
+            ssa
+        };
+
+        let lambdassa = {
+            let mut ssa = SSA::new();
+            ssa.add_block_param(Block::entry(), MonoType::u8_pointer());
+            let x = ssa.add_block_param(Block::entry(), MonoType::u(8));
+            ssa.return_(x.value());
+            ssa
+        };
+
+        for (name, ssa) in [("main", main), ("apply", applyssa), ("lambda", lambdassa)] {
+            let func = Function::new(name.into(), item.clone(), ssa, MonoType::u(8), 1);
+            lir.functions.push(func);
+        }
+
+        lumina_util::enable_highlighting(false);
+        let before = format!(
+            "BEFORE:\n{}\n{}",
+            ty_fmt(&lir.mono.types, &lir.functions[MonoFunc(0)].ssa),
+            ty_fmt(&lir.mono.types, &lir.functions[apply].ssa),
+        );
+
+        assert!(specialise_iter(&mut lir, MonoFunc(0)));
+        let specialised = MonoFunc(3);
+
+        fold_iter(&mut lir.functions[specialised], &lir.mono.types);
+        assert_eq!(
+            devirtualise(&mut lir.functions[specialised].ssa),
+            vec![lambda]
+        );
+
+        let after = format!(
+            "AFTER:\n{}\n{}",
+            ty_fmt(&lir.mono.types, &lir.functions[MonoFunc(0)].ssa),
+            ty_fmt(&lir.mono.types, &lir.functions[specialised].ssa),
+        );
+
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
     #[test]
     fn practical() {
         lumina_util::test_logger();
@@ -607,6 +1844,283 @@ mod tests {
         insta::assert_snapshot!(format!("{before}\n{after}"));
     }
 
+    #[test]
+    fn fold() {
+        lumina_util::test_logger();
+        use lumina_typesystem::IntSize;
+
+        let mut records = test_types();
+        let u64_ = IntSize::new(false, 64);
+        let pair = records.get_or_make_tuple(vec![MonoType::u(64), MonoType::u(64)]);
+
+        let mut ssa = SSA::new();
+
+        let block = [Block::entry(), ssa.new_block(), ssa.new_block()];
+
+        let v0 = ssa.add_block_param(block[0], MonoType::u(64));
+        let v1 = ssa.size_of(MonoType::u(32), u64_);
+        let v2 = ssa.mul(v1, Value::u(8, 64), MonoType::u(64));
+        let v3 = ssa.construct(vec![v0.value(), v2], pair.into());
+        let v4 = ssa.field(v3, pair, key::Field(1), MonoType::u(64));
+        let v5 = ssa.gti([v4, Value::u(16, 64)], u64_);
+        ssa.select(v5, [(block[1], vec![v4]), (block[2], vec![])]);
+
+        ssa.switch_to_block(block[1]);
+        let p = ssa.add_block_param(block[1], MonoType::u(64));
+        ssa.return_(p.value());
+
+        ssa.switch_to_block(block[2]);
+        let v = ssa.sub(v0.value(), Value::u(1, 64), MonoType::u(64));
+        ssa.return_(v);
+
+        let (before, after) = test_fold_opts(&records.types, "fold", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn fold_variant_tag() {
+        lumina_util::test_logger();
+        use lumina_typesystem::IntSize;
+
+        let mut records = test_types();
+        let payload = records.get_or_make_tuple(vec![MonoType::u(8)]);
+        let unit = records.get_or_make_tuple(vec![]);
+        let sum = records.types.push(MonoTypeData::Sum {
+            tag: IntSize::new(false, 8),
+            key: M(key::Module(0), key::Sum::from(0)),
+            variants: [unit, payload].into_iter().collect(),
+        });
+
+        let mut ssa = SSA::new();
+
+        let block = [Block::entry(), ssa.new_block(), ssa.new_block()];
+
+        let v0 = ssa.variant(key::Variant(1), vec![Value::u(3, 8)], sum);
+        let v1 = ssa.tag_of(v0, IntSize::new(false, 8));
+        ssa.jump_table(v1, vec![block[1], block[2]]);
+
+        ssa.switch_to_block(block[1]);
+        ssa.return_(Value::u(0, 8));
+
+        ssa.switch_to_block(block[2]);
+        let v2 = ssa.cast_payload(v0, payload.into());
+        let v3 = ssa.field(v2, payload, key::Field(0), MonoType::u(8));
+        ssa.return_(v3);
+
+        let (before, after) = test_fold_opts(&records.types, "fold_variant_tag", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn jump_table_inlining() {
+        lumina_util::test_logger();
+
+        let mut ssa = SSA::new();
+
+        let block = [
+            Block::entry(),
+            ssa.new_block(),
+            ssa.new_block(),
+            ssa.new_block(),
+            ssa.new_block(),
+        ];
+
+        let v0 = ssa.add_block_param(block[0], MonoType::u(8));
+        ssa.jump_table(v0.value(), vec![block[1], block[2], block[1]]);
+
+        // Forwards to block3 from both entries of the jump table
+        ssa.switch_to_block(block[1]);
+        ssa.jump(block[3], vec![]);
+
+        // Jump tables can't pass the parameter along, so this one has to stay
+        ssa.switch_to_block(block[2]);
+        ssa.jump(block[4], vec![v0.value()]);
+
+        ssa.switch_to_block(block[3]);
+        ssa.return_(Value::u(0, 8));
+
+        ssa.switch_to_block(block[4]);
+        let p = ssa.add_block_param(block[4], MonoType::u(8));
+        ssa.return_(p.value());
+
+        let (before, after) = test_block_opts(false, "jump_table_inlining", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn fold_equal_comparisons() {
+        lumina_util::test_logger();
+        use lumina_typesystem::IntSize;
+
+        let mut records = test_types();
+        let u64_ = IntSize::new(false, 64);
+        let triple = records.get_or_make_tuple(vec![MonoType::bool(); 3]);
+
+        let mut ssa = SSA::new();
+
+        let v0 = ssa.lti([Value::u(5, 64), Value::u(5, 64)], u64_);
+        let v1 = ssa.gti([Value::u(5, 64), Value::u(5, 64)], u64_);
+        let v2 = ssa.eq([Value::u(5, 64), Value::u(5, 64)], u64_);
+        let v3 = ssa.construct(vec![v0, v1, v2], triple.into());
+        ssa.return_(v3);
+
+        let (before, after) = test_fold_opts(&records.types, "fold_equal_comparisons", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn fold_wrapping() {
+        lumina_util::test_logger();
+
+        let records = test_types();
+
+        let max = Value::u(u64::MAX as i128, 64);
+
+        let mut ssa = SSA::new();
+
+        let v0 = ssa.mul(max, max, MonoType::u(64));
+        let v1 = ssa.add(v0, max, MonoType::u(64));
+        let v2 = ssa.sub(v1, v0, MonoType::u(64));
+        ssa.return_(v2);
+
+        let (before, after) = test_fold_opts(&records.types, "fold_wrapping", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn fold_block_params() {
+        lumina_util::test_logger();
+        use lumina_typesystem::IntSize;
+
+        let records = test_types();
+        let u64_ = IntSize::new(false, 64);
+
+        let mut ssa = SSA::new();
+
+        let block = [
+            Block::entry(),
+            ssa.new_block(),
+            ssa.new_block(),
+            ssa.new_block(),
+        ];
+
+        // Both edges of the select lead to the same block with the same parameter
+        let v0 = ssa.add_block_param(block[0], MonoType::u(64));
+        let v1 = ssa.eq([Value::u(1, 64), Value::u(1, 64)], u64_);
+        ssa.select(
+            v1,
+            [(block[1], vec![v0.value()]), (block[1], vec![v0.value()])],
+        );
+
+        // The taken block is listed twice in the jump table
+        ssa.switch_to_block(block[1]);
+        let p = ssa.add_block_param(block[1], MonoType::u(64));
+        ssa.jump_table(Value::u(0, 8), vec![block[2], block[3], block[2]]);
+
+        ssa.switch_to_block(block[2]);
+        let v2 = ssa.add(p.value(), Value::u(1, 64), MonoType::u(64));
+        ssa.return_(v2);
+
+        ssa.switch_to_block(block[3]);
+        ssa.return_(Value::u(0, 64));
+
+        let (before, after) = test_fold_opts(&records.types, "fold_block_params", ssa);
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
+    #[test]
+    fn inlining() {
+        lumina_util::test_logger();
+
+        let item = Item::Defined(M(key::Module::from(0), key::Func::from(0)));
+        let mut lir = test_lir();
+
+        // Leaf functions get twice the threshold, so `under` fits and `over` doesn't
+        let settings = OptSettings { inline_threshold: 4, ..OptSettings::default() };
+
+        let [under, over, hinted, never, always] = [1, 2, 3, 4, 5].map(MonoFunc);
+
+        let main = {
+            let mut ssa = SSA::new();
+            let x = ssa.add_block_param(Block::entry(), MonoType::u(8));
+            let v0 = ssa.call(under, vec![x.value()], MonoType::u(8));
+            let v1 = ssa.call(over, vec![v0], MonoType::u(8));
+            let v2 = ssa.call(hinted, vec![v1], MonoType::u(8));
+            let v3 = ssa.call(never, vec![v2], MonoType::u(8));
+            let v4 = ssa.call(always, vec![v3], MonoType::u(8));
+            ssa.return_(v4);
+            ssa
+        };
+
+        let increments = |n: u8| {
+            let mut ssa = SSA::new();
+            let mut x = ssa.add_block_param(Block::entry(), MonoType::u(8)).value();
+            for i in 0..n {
+                x = ssa.add(x, Value::u(i as i128, 8), MonoType::u(8));
+            }
+            ssa
+        };
+
+        let functions = [
+            ("main", main, None),
+            ("under", increments(6), None),
+            ("over", increments(8), None),
+            ("hinted", increments(8), Some(Inline::Hint)),
+            ("never", increments(1), Some(Inline::Never)),
+            ("always", increments(20), Some(Inline::Always)),
+        ];
+
+        for (name, mut ssa, inline) in functions {
+            if name != "main" {
+                let ret = V(ssa.ventries.len() as u32 - 1).value();
+                match inline {
+                    // Tail calls are typed as unit, the inlined call should be typed by the call site
+                    Some(Inline::Always) => ssa.jump(never, vec![ret]),
+                    _ => ssa.return_(ret),
+                };
+            }
+
+            // Also called from elsewhere, so they're not inlined for being invoked only once
+            let mut func = Function::new(name.into(), item.clone(), ssa, MonoType::u(8), 2);
+            func.inline = inline;
+            lir.functions.push(func);
+        }
+
+        lumina_util::enable_highlighting(false);
+        let before = format!(
+            "BEFORE:\n{}",
+            ty_fmt(&lir.mono.types, &lir.functions[MonoFunc(0)].ssa),
+        );
+
+        let mut fuel = 10;
+        while fuel != 0 && func_opt_iter(&mut lir, &settings, MonoFunc(0), Block::entry()) {
+            fuel -= 1;
+        }
+
+        let ssa = &lir.functions[MonoFunc(0)].ssa;
+        let calls = ssa
+            .ventries
+            .iter()
+            .filter_map(|(v, entry)| match entry {
+                Entry::CallStatic(mfunc, _) => Some((*mfunc, ssa.type_of(v).clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            calls,
+            vec![
+                (over, MonoType::u(8)),
+                (never, MonoType::u(8)),
+                (never, MonoType::u(8))
+            ]
+        );
+
+        let after = format!("AFTER:\n{}", ty_fmt(&lir.mono.types, ssa));
+
+        insta::assert_snapshot!(format!("{before}\n{after}"));
+    }
+
     #[test]
     fn functions() {
         lumina_util::test_logger();
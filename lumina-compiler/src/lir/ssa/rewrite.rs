@@ -133,6 +133,16 @@ impl SSA {
         r.boff = -1;
         self.apply(V(0), &r);
     }
+
+    /// Remove an entry without users, offsetting all values after it
+    pub fn delete_unused(&mut self, v: V) {
+        self.delete_range_no_offset(v, 1);
+
+        // A block starting at the removed entry now starts at the entry which replaced it
+        let mut r = Rewrite::new(V(v.0 + 1), Block(0));
+        r.voff = -1;
+        self.apply(V(0), &r);
+    }
 }
 
 // Offset predecessor for any blocks referenced by the entry
@@ -189,12 +199,16 @@ where
             for_values_mut(params, on_v);
         }
         Entry::BinOp(_, [lhs, rhs])
+        | Entry::Indice { of: lhs, indice: rhs }
         | Entry::WritePtr { ptr: lhs, value: rhs }
+        | Entry::AtomicStore { ptr: lhs, value: rhs }
+        | Entry::AtomicRmw(_, [lhs, rhs])
         | Entry::IntCmpInclusive([lhs, rhs], _, _) => {
             for_value_mut(lhs, on_v);
             for_value_mut(rhs, on_v);
         }
-        Entry::MemCpy { dst, src, count } => {
+        Entry::MemCpy { dst, src, count }
+        | Entry::AtomicCas { ptr: dst, expected: src, replacement: count } => {
             for_value_mut(dst, on_v);
             for_value_mut(src, on_v);
             for_value_mut(count, on_v);
@@ -208,10 +222,10 @@ where
         | Entry::BitNot(v)
         | Entry::CastFromSum { of: v }
         | Entry::TagFromSum { of: v }
-        | Entry::Indice { of: v, .. }
         | Entry::Return(v)
         | Entry::Reduce(v)
         | Entry::Deref(v)
+        | Entry::AtomicLoad(v)
         | Entry::Dealloc { ptr: v }
         | Entry::ExtendSigned(v)
         | Entry::ExtendUnsigned(v)
@@ -251,12 +265,16 @@ pub(super) fn for_entry(entry: &Entry, f: &mut dyn FnMut(V)) {
             for_values(&on_false.params, f);
         }
         Entry::BinOp(_, [lhs, rhs])
+        | Entry::Indice { of: lhs, indice: rhs }
         | Entry::WritePtr { ptr: lhs, value: rhs }
+        | Entry::AtomicStore { ptr: lhs, value: rhs }
+        | Entry::AtomicRmw(_, [lhs, rhs])
         | Entry::IntCmpInclusive([lhs, rhs], _, _) => {
             for_value(lhs, f);
             for_value(rhs, f);
         }
-        Entry::MemCpy { dst, src, count } => {
+        Entry::MemCpy { dst, src, count }
+        | Entry::AtomicCas { ptr: dst, expected: src, replacement: count } => {
             for_value(dst, f);
             for_value(src, f);
             for_value(count, f);
@@ -271,10 +289,10 @@ pub(super) fn for_entry(entry: &Entry, f: &mut dyn FnMut(V)) {
         | Entry::BitNot(v)
         | Entry::CastFromSum { of: v }
         | Entry::TagFromSum { of: v }
-        | Entry::Indice { of: v, .. }
         | Entry::Return(v)
         | Entry::Reduce(v)
         | Entry::Deref(v)
+        | Entry::AtomicLoad(v)
         | Entry::Dealloc { ptr: v }
         | Entry::ExtendSigned(v)
         | Entry::ExtendUnsigned(v)
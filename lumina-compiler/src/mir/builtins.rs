@@ -1,10 +1,16 @@
 use crate::{hir, mir, mir::Expr};
-use lumina_typesystem::{Container, Transformer, Ty, Var};
+use lumina_typesystem::{Container, IType, IntSize, Transformer, Ty, Type, Var};
 use lumina_util::{Span, Spanned, Tr};
 use mir::func::InstCall;
+use mir::lower::FinError;
 use std::collections::HashMap;
 
-pub fn signature<'t, 's>(lower: &mut mir::Verify<'t, 's>, span: Span, name: &str) -> InstCall {
+pub fn signature<'t, 's>(
+    lower: &mut mir::Verify<'t, 's>,
+    span: Span,
+    name: &str,
+    tanot: &hir::TypeAnnotation<'s>,
+) -> InstCall {
     macro_rules! sig {
         ($($param:tt),* => $ret:tt) => {
             {
@@ -26,6 +32,10 @@ pub fn signature<'t, 's>(lower: &mut mir::Verify<'t, 's>, span: Span, name: &str
     macro_rules! ty {
         (bool) => { Ty::bool() };
         (uint) => { Ty::Int(lower.target.uint()) };
+        (u8) => { Ty::Int(IntSize::new(false, 8)) };
+        (string) => { Ty::defined(lower.items.pinfo.string, vec![]) };
+        ((maybe $inner:tt)) => { Ty::defined(lower.items.pinfo.maybe, vec![ty!($inner)]) };
+        (annotated) => { annotated(lower, span, tanot) };
         ((pointer $inner:tt)) => { Ty::pointer(ty!($inner)) };
         (($($param:tt),*)) => { Ty::tuple(vec![$(ty!($param)),*]) };
         ($generic:literal) => {
@@ -53,10 +63,28 @@ pub fn signature<'t, 's>(lower: &mut mir::Verify<'t, 's>, span: Span, name: &str
         "memcpy" => sig! { (pointer 'a'), (pointer 'a'), uint => () },
 
         "write" => sig! { (pointer 'a'), 'a' => () },
+        "atomic_load" => sig! { (pointer 'a') => 'a' },
+        "atomic_store" => sig! { (pointer 'a'), 'a' => () },
+        "atomic_cas" => sig! { (pointer 'a'), 'a', 'a' => 'a' },
+        "atomic_rmw" => sig! { string, (pointer 'a'), 'a' => 'a' },
         "offset" => sig! { (pointer 'a'), uint => (pointer 'a') },
         "reflect_type" => {
             InstCall::Local(Ty::defined(lower.items.pinfo.reflect_type, vec![]).tr(span))
         }
+        "field_name" => sig! { uint => string },
+        "variant_of" => sig! { 'a' => uint },
+        "field" => {
+            let call = sig! { 'a', uint => (maybe annotated) };
+            let ty = ty!('a');
+            lower.current.casts_and_matches.push_back(ty.tr(span));
+            call
+        }
+        "implementation" => {
+            let call = sig! { 'a' => (maybe annotated) };
+            let ty = ty!('a');
+            lower.current.casts_and_matches.push_back(ty.tr(span));
+            call
+        }
         "size_of" => sig! { direct uint },
         "align_of" => sig! { direct uint },
         "alloca" => sig! { direct (pointer 'a') },
@@ -70,6 +98,22 @@ pub fn signature<'t, 's>(lower: &mut mir::Verify<'t, 's>, span: Span, name: &str
     }
 }
 
+// The type given to a builtin with `builtin:name(self as ty)`
+fn annotated<'s>(lower: &mut mir::Verify, span: Span, tanot: &hir::TypeAnnotation<'s>) -> IType {
+    match tanot.for_entity.first() {
+        Some((_, ty)) => ty.clone(),
+        None => {
+            lower
+                .error("missing type annotation")
+                .eline(span, "")
+                .text("this builtin expects a type to be given with `(self as ty)`")
+                .emit();
+
+            IType::poison()
+        }
+    }
+}
+
 pub fn lower<'t, 's>(
     lower: &mut mir::Lower<'t, 's>,
     name: &str,
@@ -94,6 +138,19 @@ pub fn lower<'t, 's>(
         "deref" => lower.lower_builtin(params, |[inner]| Expr::Deref(Box::new(inner))),
         "memcpy" => lower.lower_builtin(params, |params| Expr::MemCpy(Box::new(params))),
         "write" => lower.lower_builtin(params, |p| Expr::Write(Box::new(p))),
+        "atomic_load" => lower.lower_builtin(params, |p| Expr::AtomicLoad(Box::new(p))),
+        "atomic_store" => lower.lower_builtin(params, |p| Expr::AtomicStore(Box::new(p))),
+        "atomic_cas" => lower.lower_builtin(params, |p| Expr::AtomicCas(Box::new(p))),
+        "atomic_rmw" => match params.split_first() {
+            Some((op, params)) => match atomic_rmw_op(op) {
+                Some(op) => lower.lower_builtin(params, |p| Expr::AtomicRmw(op, Box::new(p))),
+                None => {
+                    lower.errors.push(FinError::InvalidAtomicOp(op.span));
+                    Expr::Poison
+                }
+            },
+            None => Expr::Poison,
+        },
         "offset" => lower.lower_builtin(params, |p| Expr::Num("plus", Box::new(p))),
         "unreachable" => {
             let (name, ty) = tanot.for_entity[0].clone();
@@ -109,6 +166,29 @@ pub fn lower<'t, 's>(
             let ty = lower.finalizer().transform(&ty);
             Expr::ReflectTypeOf(ty)
         }
+        "field_name" => {
+            let (name, ty) = tanot.for_entity[0].clone();
+            assert_eq!(*name, "self");
+            let ty = lower.finalizer().transform(&ty);
+            lower.lower_builtin(params, |[n]| Expr::ReflectFieldName(ty, Box::new(n)))
+        }
+        "variant_of" => lower.lower_builtin(params, |[v]| Expr::ReflectVariant(Box::new(v))),
+        "field" => {
+            let params = lower.lower_exprs(params);
+            let (object, ty) = lower.reflected_types(tanot);
+            match <[Expr; 2]>::try_from(params) {
+                Ok(params) => Expr::ReflectField(Box::new(params), ty, object),
+                Err(_) => Expr::Poison,
+            }
+        }
+        "implementation" => {
+            let params = lower.lower_exprs(params);
+            let (object, ty) = lower.reflected_types(tanot);
+            match <[Expr; 1]>::try_from(params) {
+                Ok([v]) => Expr::ReflectImplementation(Box::new(v), ty, object),
+                Err(_) => Expr::Poison,
+            }
+        }
         "size_of" => {
             let (name, ty) = tanot.for_entity[0].clone();
             assert_eq!(*name, "self");
@@ -131,6 +211,20 @@ pub fn lower<'t, 's>(
     }
 }
 
+// The operation of `atomic_rmw` has to be known statically
+fn atomic_rmw_op(op: &Tr<hir::Expr>) -> Option<&'static str> {
+    match &op.value {
+        hir::Expr::Lit(hir::Literal::String(name)) => {
+            ATOMIC_RMW_OPS.iter().find(|n| **n == *name).copied()
+        }
+        _ => None,
+    }
+}
+
+pub(super) const ATOMIC_RMW_OPS: &[&str] = &[
+    "add", "sub", "and", "or", "xor", "xchg", "umin", "umax", "smin", "smax",
+];
+
 impl<'t, 's> mir::Lower<'t, 's> {
     fn lower_builtin<const N: usize>(
         &mut self,
@@ -142,4 +236,15 @@ impl<'t, 's> mir::Lower<'t, 's> {
             Err(_) => Expr::Poison,
         }
     }
+
+    // The annotated trait object and the type of the value being reflected upon
+    fn reflected_types(&mut self, tanot: &hir::TypeAnnotation<'s>) -> (Type, Type) {
+        let ty = self.current.casts_and_matches.pop_front().unwrap();
+        let mut fin = self.finalizer();
+        let object = match tanot.for_entity.first() {
+            Some((_, object)) => fin.transform(object),
+            None => Type::poison(),
+        };
+        (object, fin.transform(&ty))
+    }
 }
@@ -0,0 +1,154 @@
+//! Checks for implementations which match the same types or which aren't allowed to be declared
+
+use crate::prelude::*;
+use lumina_typesystem::{
+    Compatibility, ConcreteType, Container, Generic, GenericKind, ImplIndex, Overlap, Ty, Type,
+};
+
+/// Implementations may only overlap when one of them is declared with `default impl` and the
+/// other one specialises it.
+///
+/// Two `default` implementations may overlap if the more specific one is selected first, which
+/// is only the case if its implementor has a concrete root while the other's doesn't.
+pub fn check(hir: &hir::HIR, iquery: &ImplIndex) {
+    iquery.for_each_overlap_candidate(|lhs, rhs| {
+        let (lheader, rheader) = (header(hir, lhs), header(hir, rhs));
+        let Some(overlap) = Overlap::unified(&lheader, &rheader) else {
+            return;
+        };
+
+        if !satisfies_constraints(hir, iquery, lhs, &|ty| overlap.lhs(ty))
+            || !satisfies_constraints(hir, iquery, rhs, &|ty| overlap.rhs(ty))
+        {
+            return;
+        }
+
+        if !iquery.is_default(lhs) && !iquery.is_default(rhs) {
+            hir.sources
+                .error("conflicting implementations")
+                .m(lhs.0)
+                .eline(hir.impltors[lhs].span, "")
+                .m(rhs.0)
+                .iline(hir.impltors[rhs].span, "overlaps with this implementation")
+                .text("one of them can be declared `default impl` to be specialised by the other")
+                .emit();
+
+            return;
+        }
+
+        let concrete = |header: &[Type]| ConcreteType::try_from(&header[0]).is_ok();
+
+        let specialises = |specific, sheader: &[Type], general, gheader: &[Type]| {
+            iquery.is_default(general)
+                && (!iquery.is_default(specific) || (concrete(sheader) && !concrete(gheader)))
+                && Overlap::is_instance_of(sheader, gheader)
+        };
+
+        if specialises(lhs, &lheader, rhs, &rheader) || specialises(rhs, &rheader, lhs, &lheader) {
+            return;
+        }
+
+        hir.sources
+            .error("ambiguous specialisation")
+            .m(lhs.0)
+            .eline(hir.impltors[lhs].span, "")
+            .m(rhs.0)
+            .iline(hir.impltors[rhs].span, "overlaps with this implementation")
+            .text("the more general of two overlapping implementations must be `default`")
+            .emit();
+    });
+
+    for impl_ in hir.impls.iter() {
+        check_orphan(hir, impl_);
+    }
+}
+
+/// Libraries included with `ext` may not implement a trait from another library unless the
+/// implementor or one of the trait parameters is a type declared by the library itself.
+fn check_orphan(hir: &hir::HIR, impl_: M<key::Impl>) {
+    let lookups = &hir.lookups;
+
+    if !lookups.is_ext_library(impl_.0) {
+        return;
+    }
+
+    let lib = lookups.library_of(impl_.0);
+    let (trait_, _) = &hir.itraits[impl_];
+
+    if lookups.library_of(trait_.0) == lib {
+        return;
+    }
+
+    let is_local = |ty: &Type| match ty {
+        Ty::Container(Container::Defined(kind, _), _) => lookups.library_of(kind.0) == lib,
+        _ => false,
+    };
+
+    if header(hir, impl_).iter().any(is_local) {
+        return;
+    }
+
+    hir.sources
+        .error("orphan implementation")
+        .m(impl_.0)
+        .eline(hir.impltors[impl_].span, "")
+        .text("libraries may only implement foreign traits for types they declare themselves")
+        .emit();
+}
+
+// Headers only truly overlap if the types they unify to satisfy both implementations' constraints.
+//
+// Constraints on types which are still generic after unification are assumed to be satisfiable.
+fn satisfies_constraints(
+    hir: &hir::HIR,
+    iquery: &ImplIndex,
+    impl_: M<key::Impl>,
+    apply: &dyn Fn(&Type) -> Type,
+) -> bool {
+    let get_impl_data = &|ikey: M<key::Impl>| {
+        let impltor = &*hir.impltors[ikey];
+        let iforall = &hir.impls[ikey];
+        let (trait_, trait_params) = &hir.itraits[ikey];
+        (*trait_, iforall, impltor, trait_params.as_slice())
+    };
+
+    hir.impls[impl_].generics.iter().all(|(key, gdata)| {
+        let ty = apply(&Ty::Generic(Generic::new(key, GenericKind::Parent)));
+
+        gdata.trait_constraints.iter().all(|con| {
+            let con = lumina_typesystem::Constraint {
+                span: con.span,
+                trait_: con.trait_,
+                params: con.params.iter().map(apply).collect(),
+            };
+
+            if has_generics(&ty) || con.params.iter().any(has_generics) {
+                return true;
+            }
+
+            Compatibility::constraint(
+                iquery,
+                None,
+                get_impl_data,
+                &|_| unreachable!("generics are rejected before constraint checking"),
+                &ty,
+                &con,
+            )
+        })
+    })
+}
+
+fn has_generics(ty: &Type) -> bool {
+    match ty {
+        Ty::Generic(_) => true,
+        Ty::Container(_, params) => params.iter().any(has_generics),
+        _ => false,
+    }
+}
+
+// The implementor followed by the trait parameters
+fn header(hir: &hir::HIR, impl_: M<key::Impl>) -> Vec<Type> {
+    std::iter::once(hir.impltors[impl_].value.clone())
+        .chain(hir.itraits[impl_].1.iter().cloned())
+        .collect()
+}
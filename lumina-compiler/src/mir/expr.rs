@@ -161,7 +161,7 @@ impl<'a, 's> Verify<'a, 's> {
                 hir::Literal::Bool(_) => IType::bool(),
                 hir::Literal::Int(_, _, var) => IType::infer(*var),
                 hir::Literal::Float(_) => IType::f64(),
-                hir::Literal::String(_) => {
+                hir::Literal::String(_) | hir::Literal::RawString(_) => {
                     let record = self.items.pinfo.string;
                     IType::string(record, vec![])
                 }
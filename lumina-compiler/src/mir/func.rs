@@ -88,11 +88,6 @@ impl<'a, 's> Verify<'a, 's> {
                         let pforall = &hir.impls[*impl_];
                         (pforall, fdef)
                     }
-                    hir::FuncDefKind::InheritedDefault(_, _) => {
-                        todo!(
-                            "I think we do want to generate the `Done` form by copying the other"
-                        );
-                    }
                     hir::FuncDefKind::TraitHeader(_, forall, typing) => {
                         let forall = forall.rename_to_keys();
                         let typing =
@@ -221,6 +216,20 @@ impl<'a, 's> Verify<'a, 's> {
             self.type_check_and_emit(ret, expected);
         }
 
+        // Now that the implementors are known, infer the associated types of trait constraints
+        self.resolve_associated_types();
+
+        // Implementors may still depend on int literals and records inferred by their fields
+        {
+            let ts = self.hir.type_system(
+                &mut self.tenvs[fkey],
+                self.target,
+                &self.field_lookup[module],
+            );
+            Finalizer::new(ts, None).infer_records_and_ints();
+        }
+        self.resolve_associated_types();
+
         let mut forall = self.fdef.forall.borrow_mut();
         let lforalls = self.fdef.lambdas.foralls.borrow_mut();
 
@@ -298,6 +307,7 @@ impl<'a, 's> Verify<'a, 's> {
 
         let mut function = lower::Function::new(typing, lambdas, lcaptures, expr);
         function.no_mangle = self.fdef.no_mangle;
+        function.inline = self.fdef.inline;
 
         function
     }
@@ -437,6 +447,67 @@ impl<'a, 's> Verify<'a, 's> {
         }
     }
 
+    /// Infer the associated types of trait constraints whose implementor is known.
+    ///
+    /// Returns whether any new type information was gained
+    pub fn resolve_associated_types(&mut self) -> bool {
+        let hir = self.hir;
+        let fdef = self.fdef;
+        let pforall = self.pforall;
+        let fkey = self.current.fkey;
+
+        let in_trait = match &hir.funcs[fkey] {
+            hir::FuncDefKind::TraitDefaultMethod(key, _, _, _) => Some(*key),
+            _ => None,
+        };
+
+        let visible = &|trait_: M<key::Trait>| {
+            hir.traits[trait_].1.generics.len() - hir.assoc_names[trait_].len()
+        };
+
+        // Generics and `self` aren't resolved by implementations but by their own constraints
+        let implied = &|ty: &Type, trait_: M<key::Trait>, params: &[Type]| match ty {
+            Ty::Generic(generic) => match generic.kind {
+                GenericKind::Entity => {
+                    let forall = fdef.forall.borrow();
+                    implied_by(&forall[generic.key].trait_constraints, trait_, params)
+                }
+                GenericKind::Lambda(lkey) => {
+                    let forall = &fdef.lambdas.foralls.borrow()[lkey];
+                    implied_by(&forall[generic.key].trait_constraints, trait_, params)
+                }
+                GenericKind::Parent => {
+                    implied_by(&pforall[generic.key].trait_constraints, trait_, params)
+                }
+            },
+            _ if in_trait == Some(trait_) => {
+                let n = params.len();
+                let own = params.iter().enumerate().all(|(i, ty)| {
+                    *ty == Ty::Generic(Generic::new(key::Generic(i as u32), GenericKind::Parent))
+                });
+
+                own.then(|| {
+                    (n..hir.traits[trait_].1.generics.len())
+                        .map(|i| Generic::new(key::Generic(i as u32), GenericKind::Parent))
+                        .map(Ty::Generic)
+                        .collect()
+                })
+            }
+            _ => None,
+        };
+
+        let get_impl_data = &|ikey: M<key::Impl>| {
+            let impltor = &*hir.impltors[ikey];
+            let iforall = &hir.impls[ikey];
+            let (trait_, trait_params) = &hir.itraits[ikey];
+            (*trait_, iforall, impltor, trait_params.as_slice())
+        };
+
+        let env = &mut self.tenvs[fkey];
+        hir.type_system(env, self.target, &self.field_lookup[fkey.0])
+            .resolve_associated_types(self.iquery, visible, implied, get_impl_data)
+    }
+
     #[track_caller]
     pub fn new_bind_as(&mut self, key: key::Bind, ty: Tr<IType>) {
         self.current.binds.insert(key, ty);
@@ -530,10 +601,16 @@ impl<'a, 's> Verify<'a, 's> {
         match &ty.value {
             IType::Simple(_) => None,
             IType::Container(Container::Defined(key, _), _) => Some(key.0),
-            IType::Special(var) => self
-                .type_system()
-                .try_get_known_type(*var)
-                .and_then(|ty| self.module_of_type(ty.as_ref())),
+            IType::Special(var) => {
+                let known = match self.type_system().try_get_known_type(*var) {
+                    None if self.resolve_associated_types() => {
+                        self.type_system().try_get_known_type(*var)
+                    }
+                    known => known,
+                };
+
+                known.and_then(|ty| self.module_of_type(ty.as_ref()))
+            }
             IType::Container(Container::Pointer, _) => self.hir.lookups.find_lib("std", "ptr"),
             IType::Container(Container::Array, _) => self.hir.lookups.find_lib("std", "array"),
             IType::Int(size) => self.hir.lookups.find_lib("std", "math").and_then(|math| {
@@ -720,7 +797,7 @@ impl<'a, 's> Verify<'a, 's> {
                     }
                 }
             }
-            hir::Callable::Builtin(name) => builtins::signature(self, span, *name),
+            hir::Callable::Builtin(name) => builtins::signature(self, span, name, tanot),
             hir::Callable::Func(mnfunc) => {
                 self.type_of_nfunc(span, M(mnfunc.module, mnfunc.key), tanot)
             }
@@ -853,7 +930,6 @@ impl<'a, 's> Verify<'a, 's> {
                                 let trait_params = (&finst).transforms(i_trait_params);
                                 (*trait_, trait_params)
                             }
-                            hir::FuncDefKind::InheritedDefault(_, _) => todo!(),
                             _ => panic!("unexpected `self`"),
                         };
 
@@ -895,7 +971,6 @@ impl<'a, 's> Verify<'a, 's> {
                             let ret = (&typing.returns).tr(f.typing.returns.span);
                             (types, ret)
                         }
-                        hir::FuncDefKind::InheritedDefault(_, _) => unreachable!(),
                     };
 
                     let ptypes = types
@@ -992,7 +1067,6 @@ impl<'a, 's> Verify<'a, 's> {
                         }
                         hir::FuncDefKind::TraitDefaultMethod(_, forall, _, _)
                         | hir::FuncDefKind::TraitHeader(_, forall, _) => forall.find(**name),
-                        hir::FuncDefKind::InheritedDefault(_, _) => unreachable!(),
                         hir::FuncDefKind::Extern { .. } => {
                             panic!("generics for extern function")
                         }
@@ -1043,6 +1117,48 @@ impl<'a, 's> Verify<'a, 's> {
     }
 }
 
+/// Get the associated types from the constraint of a generic for the same trait
+fn implied_by<T>(
+    constraints: &[Constraint<T>],
+    trait_: M<key::Trait>,
+    params: &[Type],
+) -> Option<Vec<Type>> {
+    constraints
+        .iter()
+        .find(|con| {
+            con.trait_ == trait_
+                && con
+                    .params
+                    .iter()
+                    .zip(params)
+                    .all(|(ty, exp)| is_static_eq(ty, exp))
+        })
+        .and_then(|con| {
+            con.params[params.len()..]
+                .iter()
+                .map(|ty| match ty {
+                    Ty::Generic(generic) => Some(Ty::Generic(*generic)),
+                    _ => None,
+                })
+                .collect()
+        })
+}
+
+fn is_static_eq<T>(ty: &Ty<T>, other: &Type) -> bool {
+    match (ty, other) {
+        (Ty::Container(con, params), Ty::Container(ocon, oparams)) => {
+            con == ocon
+                && params.len() == oparams.len()
+                && params.iter().zip(oparams).all(|(p, o)| is_static_eq(p, o))
+        }
+        (Ty::Generic(generic), Ty::Generic(ogeneric)) => generic == ogeneric,
+        (Ty::Int(size), Ty::Int(osize)) => size == osize,
+        (Ty::Const(const_), Ty::Const(oconst)) => const_ == oconst,
+        (Ty::Simple(name), Ty::Simple(oname)) => name == oname,
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 pub enum InstCall {
     LocalCall(Span, Vec<IType>, IType, Container),
@@ -42,7 +42,15 @@ pub enum Expr {
     MemCpy(Box<[Self; 3]>),
     Deref(Box<Self>),
     Write(Box<[Self; 2]>),
+    AtomicLoad(Box<[Self; 1]>),
+    AtomicStore(Box<[Self; 2]>),
+    AtomicCas(Box<[Self; 3]>),
+    AtomicRmw(&'static str, Box<[Self; 2]>),
     ReflectTypeOf(Type),
+    ReflectFieldName(Type, Box<Self>),
+    ReflectVariant(Box<Self>),
+    ReflectField(Box<[Self; 2]>, Type, Type),
+    ReflectImplementation(Box<Self>, Type, Type),
     SizeOf(Type),
     AlignOf(Type),
     Alloca(Type),
@@ -314,21 +322,27 @@ impl<'a, 's> Lower<'a, 's> {
                 }
             }
             hir::Literal::String(str) => {
-                let ro_key = self.str_to_ro(*str);
+                let ro_key = self.str_to_ro(str);
+                self.lower_string_literal(ro_key)
+            }
+            hir::Literal::RawString(str) => {
+                let ro_key = self.bytes_to_ro(str.as_bytes().to_vec());
+                self.lower_string_literal(ro_key)
+            }
+        }
+    }
 
-                let len = self.read_only_table[ro_key].0 .0.len();
+    fn lower_string_literal(&mut self, ro_key: M<key::ReadOnly>) -> Expr {
+        let len = self.read_only_table[ro_key].0 .0.len();
 
-                let stringable = self.items.pinfo.stringable;
-                let func = NFunc::Method(*stringable, STRINGABLE_FROM_RAW_PARTS);
-                let ptr = Expr::ReadOnly(ro_key);
-                let len = Expr::Int(self.target.uint(), len as i128);
-                let mapper =
-                    GenericMapper::new(vec![], Some(Ty::string(self.items.pinfo.string, vec![])));
+        let stringable = self.items.pinfo.stringable;
+        let func = NFunc::Method(*stringable, STRINGABLE_FROM_RAW_PARTS);
+        let ptr = Expr::ReadOnly(ro_key);
+        let len = Expr::Int(self.target.uint(), len as i128);
+        let mapper = GenericMapper::new(vec![], Some(Ty::string(self.items.pinfo.string, vec![])));
 
-                let call = Callable::Func(M(stringable.0, func), mapper);
-                Expr::Call(call, vec![ptr, len])
-            }
-        }
+        let call = Callable::Func(M(stringable.0, func), mapper);
+        Expr::Call(call, vec![ptr, len])
     }
 
     fn lower_cast(&mut self, expr: mir::Expr, ty_of_expr: Tr<Type>, to: Type) -> Expr {
@@ -678,7 +692,49 @@ impl fmt::Display for Expr {
                 p[1],
                 p[2]
             ),
+            Expr::AtomicLoad(p) => write!(f, "{op}{} {}{cp}", "atomic-load".keyword(), &p[0]),
+            Expr::AtomicStore(p) => write!(
+                f,
+                "{op}{} {} {}{cp}",
+                "atomic-store".keyword(),
+                &p[0],
+                &p[1]
+            ),
+            Expr::AtomicCas(p) => write!(
+                f,
+                "{op}{} {} {} {}{cp}",
+                "atomic-cas".keyword(),
+                &p[0],
+                &p[1],
+                &p[2]
+            ),
+            Expr::AtomicRmw(kind, p) => {
+                let header = format!("atomic-{kind}");
+                write!(f, "{op}{} {} {}{cp}", header.keyword(), &p[0], &p[1])
+            }
             Expr::ReflectTypeOf(ty) => write!(f, "{op}{} {ty}{cp}", "type-of".keyword()),
+            Expr::ReflectFieldName(ty, n) => {
+                write!(f, "{op}{} {ty} {n}{cp}", "field-name-of".keyword())
+            }
+            Expr::ReflectVariant(v) => write!(f, "{op}{} {v}{cp}", "variant-of".keyword()),
+            Expr::ReflectField(p, _, object) => {
+                write!(
+                    f,
+                    "{op}{} {} {} {} {object}{cp}",
+                    "field-of".keyword(),
+                    p[0],
+                    p[1],
+                    "as".keyword()
+                )
+            }
+            Expr::ReflectImplementation(v, _, object) => {
+                write!(
+                    f,
+                    "{op}{} {v} {} {object}{cp}",
+                    "implementation-of".keyword(),
+                    "as".keyword()
+                )
+            }
             Expr::SizeOf(ty) => write!(f, "{op}{} {ty}{cp}", "size-of".keyword()),
             Expr::AlignOf(ty) => write!(f, "{op}{} {ty}{cp}", "align-of".keyword()),
             Expr::Alloca(ty) => write!(f, "{op}{} {ty}{cp}", "alloca".keyword()),
@@ -25,6 +25,8 @@ pub struct Function {
     pub lcaptures: Map<key::Lambda, Vec<key::Bind>>,
     #[new(default)]
     pub no_mangle: bool,
+    #[new(default)]
+    pub inline: Option<ast::Inline>,
     pub expr: Expr,
 }
 
@@ -101,6 +103,7 @@ pub enum FinError {
     BadGenericArrayCount { got: Tr<usize> },
     DuplicateField(Tr<String>, Span),
     MissingField(String, Span),
+    InvalidAtomicOp(Span),
 }
 
 impl<'a, 's> Lower<'a, 's> {
@@ -139,15 +142,17 @@ impl<'a, 's> Lower<'a, 's> {
     }
 
     fn str_to_ro(&mut self, str: &'s str) -> M<key::ReadOnly> {
-        let str = escape(str);
+        self.bytes_to_ro(escape(str))
+    }
 
+    fn bytes_to_ro(&mut self, bytes: Vec<u8>) -> M<key::ReadOnly> {
         // We set the type to `u8` because when this data is accessed
         // with ReadOnly, it's treated by-reference so it'll become `*u8`
         let ty = Type::u8();
 
         self.read_only_table.push(
             self.current.fkey.0,
-            (mir::ReadOnlyBytes(str.into_boxed_slice()), ty),
+            (mir::ReadOnlyBytes(bytes.into_boxed_slice()), ty),
         )
     }
 
@@ -173,33 +178,9 @@ impl<'a, 's> Lower<'a, 's> {
     }
 }
 
+// Invalid escape sequences have already been reported by the parser
 fn escape(str: &str) -> Vec<u8> {
-    let mut buffer = Vec::with_capacity(str.len());
-    let mut bytes = str.bytes();
-
-    loop {
-        let Some(mut b) = bytes.next() else {
-            break buffer;
-        };
-
-        match b {
-            b'\\' => match bytes.next() {
-                Some(b'n') => b = b'\n',
-                Some(b'r') => b = b'\r',
-                Some(b't') => b = b'\t',
-                Some(b'\\') => b = b'\\',
-                Some(b'"') => b = b'"',
-                Some(b'\'') => b = b'\'',
-                Some(b'0') => b = b'\0',
-                b => {
-                    panic!("ET: invalid escape sequence: {b:?}");
-                }
-            },
-            _ => {}
-        }
-
-        buffer.push(b);
-    }
+    lumina_parser::unescape(str, |_| {})
 }
 
 pub struct ParamsLower<'l, 'a, 's> {
@@ -448,6 +429,15 @@ pub fn emit_fin_error<'s>(
             .m(module)
             .eline(rspan, format!("field `{field}` is missing"))
             .emit(),
+        FinError::InvalidAtomicOp(span) => sources
+            .error("invalid atomic operation")
+            .m(module)
+            .eline(span, "")
+            .text(format!(
+                "expected a string literal of one of: {}",
+                super::builtins::ATOMIC_RMW_OPS.join(", ")
+            ))
+            .emit(),
     }
 }
 
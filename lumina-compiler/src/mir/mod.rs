@@ -20,6 +20,7 @@ pub use func::FunctionStatus;
 use func::InstInfo;
 use lumina_typesystem::{ImplIndex, Type};
 mod builtins;
+mod coherence;
 mod patc;
 mod tcheck;
 
@@ -45,6 +46,7 @@ pub struct MIR {
     pub impltors: MMap<key::Impl, Tr<Type>>,
     pub itraits: MMap<key::Impl, (M<key::Trait>, Vec<Type>)>,
     pub val_initializers: MMap<key::Val, M<key::Func>>,
+    pub thread_locals: HashSet<M<key::Val>>,
     pub trait_objects: MMap<key::Trait, Option<SelfPositions>>,
     pub type_repr: hir::TypeRepr,
 
@@ -87,13 +89,12 @@ pub fn run<'a, 'h, 's>(
 
     let fields = hir.lookups.to_field_lookup();
 
+    coherence::check(&hir, iquery);
+
     // Verify members of implementations, as well as statically finishing the method typings
     let mut imethods = hir.impls.secondary();
     for impl_ in hir.impls.iter() {
-        let (assoc, methods) = verify_impl_headers(&hir, &mut tenvs, impl_, target.int_size());
-        if !assoc.is_empty() {
-            unimplemented!("associated types");
-        }
+        let methods = verify_impl_headers(&hir, &mut tenvs, impl_, target.int_size());
         imethods.push(impl_.0, methods);
     }
 
@@ -147,9 +148,9 @@ pub fn run<'a, 'h, 's>(
                 .map(|m| hir.sources.name_of_module(m))
                 .collect(),
             func_names: hir.func_names.map(|_, name| name.value.to_string()),
-            record_names: hir.records.map(|M(_, name), _| name.to_string()),
-            sum_names: hir.sums.map(|M(_, name), _| name.to_string()),
-            trait_names: hir.traits.map(|M(_, name), _| name.to_string()),
+            record_names: hir.records.map(|_, (name, _)| name.value.to_string()),
+            sum_names: hir.sums.map(|_, (name, _)| name.value.to_string()),
+            trait_names: hir.traits.map(|_, (name, _)| name.value.to_string()),
             field_names: hir
                 .fnames
                 .map(|_, fields| fields.values().map(|name| name.to_string()).collect()),
@@ -167,6 +168,7 @@ pub fn run<'a, 'h, 's>(
             field_types: hir.field_types,
             variant_types: hir.variant_types,
             val_initializers: hir.val_initializers,
+            thread_locals: hir.thread_locals,
         },
         has_failed,
     )
@@ -263,10 +265,7 @@ fn verify_impl_headers<'s>(
     tenvs: &mut MMap<key::Func, TEnv<'s>>,
     impl_: M<key::Impl>,
     default_int_size: u8,
-) -> (
-    Map<key::AssociatedType, Tr<Type>>,
-    Map<key::Method, Option<M<key::Func>>>,
-) {
+) -> Map<key::Method, Option<M<key::Func>>> {
     let imod = impl_.0;
     let mname = hir.sources.name_of_module(imod);
 
@@ -360,31 +359,28 @@ fn verify_impl_headers<'s>(
         })
         .collect::<Map<key::Method, _>>();
 
-    tinst.clear_assignments_of_kind(GenericKind::Entity);
-
-    let mut missing_associations: Vec<key::AssociatedType> = vec![];
+    // The associated types themselves are already resolved into the trait parameters of the
+    // implementation, so we only need to check for any which are missing or unknown.
+    let missing_associations = hir.assoc_names[*trkey]
+        .iter()
+        .filter(|&(assoc, aname)| {
+            hir.assoc[*trkey][assoc].is_none()
+                && hir.iassoc[impl_].iter().all(|(name, _)| name != aname)
+        })
+        .map(|(assoc, _)| assoc)
+        .collect::<Vec<key::AssociatedType>>();
 
-    let resolved_associations = hir.assoc_names[*trkey]
+    let unknown_associations = hir.iassoc[impl_]
         .iter()
-        .map(
-            |(assoc, &aname)| match hir.iassoc[impl_].iter().find(|(name, _)| aname == *name) {
-                Some((_, ty)) => ty.clone(),
-                // Use the default (if it exists)
-                None => match hir.assoc[*trkey][assoc].as_ref() {
-                    Some(ty) => (&tinst).transform(&**ty).tr(ty.span),
-                    None => {
-                        missing_associations.push(assoc);
-                        Type::poison().tr(hir.impltors[impl_].span)
-                    }
-                },
-            },
-        )
-        .collect::<Map<key::AssociatedType, _>>();
+        .filter(|(name, _)| hir.assoc_names[*trkey].values().all(|aname| aname != name))
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
 
     let error = hir.sources.error("bad implementation");
 
     if missing_methods.len()
         + missing_associations.len()
+        + unknown_associations.len()
         + failed_methods.len()
         + unknown_methods.len()
         != 0
@@ -408,6 +404,18 @@ fn verify_impl_headers<'s>(
             }
         }
 
+        for (i, name) in unknown_associations.iter().enumerate() {
+            error = error.text(format!(
+                "{}  {} {name}",
+                "unknown".symbol(),
+                "type".keyword()
+            ));
+
+            if i == unknown_associations.len() - 1 {
+                error = error.text("");
+            }
+        }
+
         for (i, (method, forall, typing)) in missing_methods.iter().enumerate() {
             let env = TEnv::new();
             let fname = hir.func_names[trmethodmap[*method].inside(tmod)];
@@ -459,7 +467,7 @@ fn verify_impl_headers<'s>(
         error.emit();
     }
 
-    (resolved_associations, resolved_methods)
+    resolved_methods
 }
 
 struct ImplComparison<'a, 's> {
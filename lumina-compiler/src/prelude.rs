@@ -6,5 +6,5 @@ pub use itertools::Itertools;
 pub use lumina_key as key;
 pub use lumina_key::{MMap, Map, M};
 pub use lumina_util::{Span, Spanned, Tr};
-pub use std::collections::HashMap;
+pub use std::collections::{HashMap, HashSet};
 pub use tracing::{error, info, trace, warn};
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Optimisation settings of a build, set by `val profile` in `config.lm` and overridden by flags
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub opt_level: OptLevel,
+    pub inline_threshold: Option<u32>,
+    /// Passes explicitly enabled or disabled regardless of the optimisation level
+    pub passes: HashMap<Pass, bool>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptLevel {
+    O0,
+    O1,
+    #[default]
+    O2,
+    Os,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pass {
+    Fold,
+    Values,
+    Specialise,
+    Blocks,
+    Inline,
+    Escape,
+}
+
+impl Profile {
+    pub fn enabled(&self, pass: Pass) -> bool {
+        self.passes
+            .get(&pass)
+            .copied()
+            .unwrap_or_else(|| self.opt_level.runs(pass))
+    }
+
+    pub fn inline_threshold(&self) -> u32 {
+        self.inline_threshold
+            .unwrap_or_else(|| self.opt_level.inline_threshold())
+    }
+}
+
+impl OptLevel {
+    /// Whether the pass runs at this level unless explicitly toggled
+    pub fn runs(self, pass: Pass) -> bool {
+        match self {
+            OptLevel::O0 => false,
+            OptLevel::O1 => matches!(pass, Pass::Fold | Pass::Values | Pass::Blocks),
+            OptLevel::O2 => true,
+            // Specialisation duplicates the higher-order function for every lambda
+            OptLevel::Os => pass != Pass::Specialise,
+        }
+    }
+
+    pub fn inline_threshold(self) -> u32 {
+        match self {
+            OptLevel::Os => 3,
+            _ => 12,
+        }
+    }
+}
+
+impl Pass {
+    pub const ALL: [Pass; 6] = [
+        Pass::Fold,
+        Pass::Values,
+        Pass::Specialise,
+        Pass::Blocks,
+        Pass::Inline,
+        Pass::Escape,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Fold => "fold",
+            Pass::Values => "values",
+            Pass::Specialise => "specialise",
+            Pass::Blocks => "blocks",
+            Pass::Inline => "inline",
+            Pass::Escape => "escape",
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = &'static str;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "s" => Ok(OptLevel::Os),
+            _ => Err("expected one of 0, 1, 2 or s"),
+        }
+    }
+}
+
+impl FromStr for Pass {
+    type Err = &'static str;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        Pass::ALL
+            .into_iter()
+            .find(|pass| pass.name() == str)
+            .ok_or("unknown optimisation pass")
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptLevel::O0 => "O0".fmt(f),
+            OptLevel::O1 => "O1".fmt(f),
+            OptLevel::O2 => "O2".fmt(f),
+            OptLevel::Os => "Os".fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.name().fmt(f)
+    }
+}
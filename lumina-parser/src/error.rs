@@ -18,6 +18,8 @@ pub enum Error {
     ConflictingBars(IndentConflict),
     MissingReturnType(Span),
     NestedWhere { previous: Span, kw: Span },
+    InvalidEscape(Span),
+    IntOverflow(Span),
 }
 
 impl<'a> Parser<'a> {
@@ -80,4 +82,12 @@ impl<'a> Parser<'a> {
     pub(crate) fn err_nested_where(&mut self, previous: Span, kw: Span) {
         self.errors.push(Error::NestedWhere { previous, kw });
     }
+
+    pub(crate) fn err_invalid_escape(&mut self, span: Span) {
+        self.errors.push(Error::InvalidEscape(span));
+    }
+
+    pub(crate) fn err_int_overflow(&mut self, span: Span) {
+        self.errors.push(Error::IntOverflow(span));
+    }
 }
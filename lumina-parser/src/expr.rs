@@ -1,5 +1,5 @@
 use super::{
-    select, AnnotatedPath, CurlyInit, Fields, ListLength, Parser, Pattern, Token, Type, T,
+    lexer, select, AnnotatedPath, CurlyInit, Fields, ListLength, Parser, Pattern, Token, Type, T,
 };
 use itertools::Itertools;
 use lumina_util::{Highlighting, Identifier, Span, Spanned, Tr};
@@ -29,11 +29,17 @@ pub enum Expr<'a> {
     CastAs(Box<Tr<Self>>, Tr<Type<'a>>),
 
     List(Vec<Tr<Self>>, ListLength<'a>),
+    // [f x | x <- xs, pred x]
+    Comprehension(Box<Tr<Self>>, Vec<Qualifier<'a>>),
     Tuple(Vec<Tr<Self>>),
+    // "a {b} c"
+    Interpolated(Vec<Tr<Self>>),
     Record {
         init: Box<CurlyInit<'a>>,
         fields: Fields<'a, Self>,
     },
+    // { k => v, k2 => v2 }
+    Map(Vec<(Tr<Self>, Tr<Self>)>),
     If(Box<[Tr<Self>; 3]>),
     Do(Box<[Tr<Self>; 2]>),
     Let(Tr<Pattern<'a>>, Box<[Tr<Self>; 2]>),
@@ -56,11 +62,19 @@ impl<'a> Expr<'a> {
     }
 }
 
+#[derive(Clone, Debug)]
+pub enum Qualifier<'a> {
+    Generator(Tr<Pattern<'a>>, Tr<Expr<'a>>),
+    Filter(Tr<Expr<'a>>),
+    Let(Tr<Pattern<'a>>, Tr<Expr<'a>>),
+}
+
 #[derive(Clone, Debug)]
 pub enum Literal<'a> {
     Int(bool, u128),
     Float(f64),
     String(&'a str),
+    RawString(&'a str),
     Char(&'a str),
 }
 
@@ -217,6 +231,62 @@ impl<'a> Parser<'a> {
     pub fn expr(&mut self) -> Option<Tr<Expr<'a>>> {
         ExprParser::new(self).expr()
     }
+
+    // The first element of a list may be followed by the qualifiers of a list comprehension
+    fn list_elem(&mut self, first: bool) -> Option<Tr<Expr<'a>>> {
+        let elem = self.expr()?;
+
+        match self.lexer.peek() {
+            (T::Bar, _) if first => {
+                self.progress();
+                let qualifiers = self.qualifiers()?;
+                let span = elem.span;
+                Some(Expr::Comprehension(Box::new(elem), qualifiers).tr(span))
+            }
+            _ => Some(elem),
+        }
+    }
+
+    // Parses qualifiers up until but not including the closing `]`
+    fn qualifiers(&mut self) -> Option<Vec<Qualifier<'a>>> {
+        let mut qualifiers = vec![];
+
+        loop {
+            let qualifier = match self.lexer.peek() {
+                (T::Let, _) => {
+                    self.progress();
+                    let pattern = self.let_pattern()?;
+                    self.expect(T::Equal)?;
+                    Qualifier::Let(pattern, self.expr()?)
+                }
+                _ if self.is_generator() => {
+                    let pattern = self.generator_pattern()?;
+                    self.progress();
+                    Qualifier::Generator(pattern, self.expr()?)
+                }
+                _ => Qualifier::Filter(self.expr()?),
+            };
+
+            qualifiers.push(qualifier);
+
+            match self.lexer.peek() {
+                (T::Comma, _) => self.progress(),
+                (T::CloseList, _) => break Some(qualifiers),
+                t => {
+                    self.err_unexpected_token(t, "`,` or `]`");
+                    break None;
+                }
+            }
+        }
+    }
+
+    // Whether the next qualifier is in the form of `pattern <- expr`, which can't be known until
+    // after the pattern has been parsed.
+    fn is_generator(&mut self) -> bool {
+        let mut parser = self.clone();
+        parser.generator_pattern().is_some()
+            && matches!(parser.lexer.peek(), (T::Operator, span) if parser.take(span) == "<-")
+    }
 }
 
 impl<'p, 'a> ExprParser<'p, 'a> {
@@ -236,6 +306,7 @@ impl<'p, 'a> ExprParser<'p, 'a> {
             T::Path => self.expr_path(span, true),
             T::Default => self.expr_path(span, true),
             T::StringLiteral => self.expr_string(span),
+            T::RawStringLiteral => self.expr_raw_string(span),
             T::CharLiteral => self.expr_char(span),
             T::OpenParen => self.expr_parenthesis(span),
             T::OpenCurly => self.expr_record(span),
@@ -263,6 +334,7 @@ impl<'p, 'a> ExprParser<'p, 'a> {
             T::Path => self.expr_path(span, false),
             T::Default => self.expr_path(span, false),
             T::StringLiteral => self.expr_string(span),
+            T::RawStringLiteral => self.expr_raw_string(span),
             T::CharLiteral => self.expr_char(span),
             T::OpenParen => self.expr_parenthesis(span),
             T::OpenCurly => self.expr_record(span),
@@ -353,17 +425,16 @@ impl<'p, 'a> ExprParser<'p, 'a> {
 
     fn expr_int(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
         let raw = self.parser.take(span);
-        let parse = |span| self.parser.take(span).parse::<u128>().unwrap();
         let (sign, n) = if raw.as_bytes()[0] == b'-' {
-            (true, parse(span.move_indice(1)))
+            (true, self.parser.int(span.move_indice(1)))
         } else {
-            (false, parse(span))
+            (false, self.parser.int(span))
         };
         Some(Expr::Lit(Literal::Int(sign, n)).tr(span))
     }
 
     fn expr_float(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
-        let n = self.parser.take(span).parse().unwrap();
+        let n = lexer::parse_float(self.parser.take(span));
         Some(Expr::Lit(Literal::Float(n)).tr(span))
     }
 
@@ -423,7 +494,7 @@ impl<'p, 'a> ExprParser<'p, 'a> {
             },
             T::Int => {
                 self.parser.progress();
-                let n = self.parser.take(span).parse::<usize>().unwrap();
+                let n = self.parser.int(span) as usize;
                 let span = lhs.span.extend(span);
                 let expr = Expr::TupleAccess(Box::new(lhs), n.tr(span));
                 self.expr_path_field_accessors_next(expr.tr(span))
@@ -470,11 +541,92 @@ impl<'p, 'a> ExprParser<'p, 'a> {
 
     fn expr_string(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
         let inner_span = span.move_indice(1).extend_length(-1);
-        Some(Expr::Lit(Literal::String(self.parser.take(inner_span))).tr(span))
+        let str = self.parser.take(inner_span);
+
+        if !str.contains('{') {
+            self.parser.check_escapes(inner_span);
+            return Some(Expr::Lit(Literal::String(str)).tr(span));
+        }
+
+        let bytes = str.as_bytes();
+        let mut parts = vec![];
+        let mut start = 0;
+        let mut i = 0;
+
+        let at = |i: usize, len: usize| Span::new(inner_span.indice + i as u32, len as u16);
+
+        while let Some(&c) = bytes.get(i) {
+            match c {
+                b'\\' if bytes.get(i + 1) == Some(&b'u') => {
+                    i += 2 + lexer::unicode_escape_len(&bytes[i + 2..])
+                }
+                b'\\' => i += 2,
+                b'{' => match lexer::interpolation_end(&bytes[i + 1..]) {
+                    Some(len) => {
+                        if start != i {
+                            let lit = Literal::String(&str[start..i]);
+                            self.parser.check_escapes(at(start, i - start));
+                            parts.push(Expr::Lit(lit).tr(at(start, i - start)));
+                        }
+
+                        let inner = &str[i + 1..i + 1 + len];
+                        parts.push(self.interpolated(inner, at(i + 1, len)));
+                        i += len + 2;
+                        start = i;
+                    }
+                    // Braces not followed by an expression are kept as text
+                    None => i += 1,
+                },
+                _ => i += 1,
+            }
+        }
+
+        if parts.is_empty() {
+            self.parser.check_escapes(inner_span);
+            return Some(Expr::Lit(Literal::String(str)).tr(span));
+        }
+
+        if start < bytes.len() {
+            let lit = Literal::String(&str[start..]);
+            self.parser.check_escapes(at(start, bytes.len() - start));
+            parts.push(Expr::Lit(lit).tr(at(start, bytes.len() - start)));
+        }
+
+        Some(Expr::Interpolated(parts).tr(span))
+    }
+
+    fn interpolated(&mut self, src: &'a str, span: Span) -> Tr<Expr<'a>> {
+        let mut parser = Parser::new(src).with_offset(span.indice as usize);
+        let expr = parser.expr().unwrap_or(Expr::Poison.tr(span));
+
+        match parser.lexer.peek() {
+            (T::EOF, _) => {}
+            t => parser.err_unexpected_token(t, "end of interpolation"),
+        }
+
+        self.parser.errors.extend(parser.errors);
+        expr
+    }
+
+    fn expr_raw_string(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
+        let raw = self.parser.take(span);
+        let hashes = raw[1..].bytes().take_while(|&c| c == b'#').count();
+
+        let closing = format!("\"{}", "#".repeat(hashes));
+        if raw.len() < 3 + hashes * 2 || !raw.ends_with(&closing) {
+            self.parser.err_unmatched(span, "raw string");
+            return Some(Expr::Poison.tr(span));
+        }
+
+        let inner_span = span
+            .move_indice(2 + hashes as i32)
+            .extend_length(-(1 + hashes as i16));
+        Some(Expr::Lit(Literal::RawString(self.parser.take(inner_span))).tr(span))
     }
 
     fn expr_char(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
         let inner_span = span.move_indice(1).extend_length(-1);
+        self.parser.check_escapes(inner_span);
         Some(Expr::Lit(Literal::Char(self.parser.take(inner_span))).tr(span))
     }
 
@@ -492,16 +644,81 @@ impl<'p, 'a> ExprParser<'p, 'a> {
     }
 
     fn expr_record(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
+        if self.is_map() {
+            return self.expr_map(span);
+        }
+
         self.parser.shared_record(span).map(|curly| {
             Expr::Record { init: Box::new(curly.init), fields: curly.fields }.tr(curly.span)
         })
     }
 
+    // Whether the curly braces contain `key => value` entries rather than record fields, decided by
+    // whether a top-level `=>` comes before anything ending the first key or field.
+    fn is_map(&mut self) -> bool {
+        let mut lexer = self.parser.lexer.clone();
+        let mut depth = 0_usize;
+
+        loop {
+            match lexer.next() {
+                (T::OpenParen | T::FnOpenParen | T::FnPtrOpenParen, _) => depth += 1,
+                (T::OpenList | T::OpenCurly | T::OpenAttribute, _) => depth += 1,
+                (T::CloseParen | T::CloseList | T::CloseCurly, _) if depth != 0 => depth -= 1,
+                (T::Operator, span) if depth == 0 && self.parser.take(span) == "=>" => return true,
+                (T::CloseParen | T::CloseList | T::CloseCurly, _) => return false,
+                (T::Comma | T::Bar | T::Tilde | T::Equal, _) if depth == 0 => return false,
+                (t, _) if t == T::EOF || t.is_header() => return false,
+                _ => {}
+            }
+        }
+    }
+
+    fn expr_map(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
+        let mut entries = vec![];
+
+        loop {
+            match self.parser.expr() {
+                Some(expr) => match split_map_entry(expr) {
+                    Ok(entry) => entries.push(entry),
+                    Err(span) => {
+                        self.parser
+                            .err_expected_but_got(span, "`key => value`", "an expression");
+                    }
+                },
+                None => match self.parser.recover_for([T::Comma, T::CloseCurly], false) {
+                    T::Comma | T::CloseCurly => {}
+                    _ => {
+                        self.parser.err_unmatched(span, "the map");
+                        return None;
+                    }
+                },
+            }
+
+            select! { self.parser, "`,` or `}`", end;
+                T::Comma => {
+                    // allow trailing comma
+                    if let Some(end) = self.parser.next_is(|t| t == T::CloseCurly) {
+                        break Some(Expr::Map(entries).tr(span.extend(end)));
+                    }
+                },
+                T::CloseCurly => break Some(Expr::Map(entries).tr(span.extend(end))),
+            }
+        }
+    }
+
     fn expr_list(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
-        let (elems, ender, end) =
-            self.parser
-                .shared_list(span, Parser::expr, Some(Expr::Poison.tr(span)))?;
-        Some(Expr::List(elems, ender).tr(span.extend(end)))
+        let mut first = true;
+        let (mut elems, ender, end) = self.parser.shared_list(
+            span,
+            |parser| parser.list_elem(std::mem::take(&mut first)),
+            Some(Expr::Poison.tr(span)),
+        )?;
+
+        let span = span.extend(end);
+        match elems.as_slice() {
+            [Tr { value: Expr::Comprehension(..), .. }] => Some(elems.remove(0).value.tr(span)),
+            _ => Some(Expr::List(elems, ender).tr(span)),
+        }
     }
 
     fn expr_if(&mut self, span: Span) -> Option<Tr<Expr<'a>>> {
@@ -704,6 +921,7 @@ impl T {
             T::Path,
             T::CharLiteral,
             T::StringLiteral,
+            T::RawStringLiteral,
             T::OpenParen,
             T::OpenCurly,
             T::OpenList,
@@ -769,6 +987,16 @@ impl<'a> fmt::Display for Expr<'a> {
             Expr::Record { init, fields } => {
                 write!(f, "{oc} {}{} {cc}", init, fields.iter().format(", "))
             }
+            Expr::Map(entries) => write!(
+                f,
+                "{oc} {} {cc}",
+                entries
+                    .iter()
+                    .format_with(", ", |(k, v), f| f(&format_args!(
+                        "{k} {} {v}",
+                        "=>".symbol()
+                    )))
+            ),
             Expr::Lit(lit) => lit.fmt(f),
             Expr::If(exprs) => {
                 let cond = exprs[0].to_string();
@@ -837,13 +1065,63 @@ impl<'a> fmt::Display for Expr<'a> {
             Expr::PassFptr(fkey) => write!(f, "{}{}", "#!".symbol(), fkey),
             Expr::Do(exprs) => write!(f, "{do_} {} {then_} {}", exprs[0], exprs[1]),
             Expr::List(elems, length) => write!(f, "{ol}{}{length}{cl}", elems.iter().format(", ")),
+            Expr::Comprehension(elem, qualifiers) => {
+                write!(f, "{ol}{elem} | {}{cl}", qualifiers.iter().format(", "))
+            }
             Expr::Tuple(elems) => write!(f, "{op}{}{cp}", elems.iter().format(", ")),
+            Expr::Interpolated(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    match &part.value {
+                        Expr::Lit(Literal::String(str)) => write!(f, "{str}")?,
+                        expr => write!(f, "{{{expr}}}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
             Expr::CastAs(v, ty) => write!(f, "{op}{v} {} {ty}{cp}", "as".keyword()),
             Expr::Poison => "???".fmt(f),
         }
     }
 }
 
+// Split `a + b => c` into `(a + b, c)` at the first `=>` operator
+fn split_map_entry<'a>(expr: Tr<Expr<'a>>) -> Result<(Tr<Expr<'a>>, Tr<Expr<'a>>), Span> {
+    let operators = |init: Tr<Expr<'a>>, ops: Vec<(Tr<&'a str>, Tr<Expr<'a>>)>| match ops.last() {
+        None => init,
+        Some((_, last)) => {
+            let span = init.span.extend(last.span);
+            Expr::Operators { init: Box::new(init), ops }.tr(span)
+        }
+    };
+
+    match expr.value {
+        Expr::Operators { init, mut ops } => {
+            let Some(i) = ops.iter().position(|(op, _)| **op == "=>") else {
+                return Err(expr.span);
+            };
+
+            let mut value_ops = ops.split_off(i);
+            let (_, value) = value_ops.remove(0);
+
+            Ok((operators(*init, ops), operators(value, value_ops)))
+        }
+        _ => Err(expr.span),
+    }
+}
+
+impl<'a> fmt::Display for Qualifier<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Qualifier::Generator(pat, expr) => write!(f, "{pat} {} {expr}", "<-".symbol()),
+            Qualifier::Filter(expr) => expr.fmt(f),
+            Qualifier::Let(pat, expr) => {
+                write!(f, "{} {pat} {} {expr}", "let".keyword(), "=".symbol())
+            }
+        }
+    }
+}
+
 impl<'a> fmt::Display for Literal<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -851,6 +1129,8 @@ impl<'a> fmt::Display for Literal<'a> {
             Literal::Int(false, n) => n.fmt(f),
             Literal::Float(n) => n.fmt(f),
             Literal::String(str) => write!(f, "\"{str}\""),
+            Literal::RawString(str) if str.contains('"') => write!(f, "r#\"{str}\"#"),
+            Literal::RawString(str) => write!(f, "r\"{str}\""),
             Literal::Char(c) => write!(f, "\'{c}\'"),
         }
     }
@@ -2,19 +2,187 @@ use logos::{Lexer as LogosLexer, Logos, SpannedIter};
 use lumina_util::Span;
 
 fn find_str_end<'src>(end: u8, lex: &mut LogosLexer<'src, Token>) {
+    let i = str_end(end, lex.remainder().as_bytes());
+    lex.bump((i + 1).min(lex.remainder().len()));
+}
+
+// String literals may contain interpolated expressions such as `"{x + 1}"`, which in turn may
+// contain string literals of their own.
+fn str_end(end: u8, bytes: &[u8]) -> usize {
     let mut i = 0;
-    let bytes = lex.remainder().as_bytes();
 
     while let Some(&c) = bytes.get(i) {
         match c {
             b'\\' if bytes.get(i + 1) == Some(&end) => i += 2,
             b'\\' if bytes.get(i + 1) == Some(&b'\\') => i += 2,
+            b'\\' if end == b'"' && bytes.get(i + 1) == Some(&b'{') => i += 2,
+            b'\\' if bytes.get(i + 1) == Some(&b'u') => {
+                i += 2 + unicode_escape_len(&bytes[i + 2..])
+            }
+            b'{' if end == b'"' => match interpolation_end(&bytes[i + 1..]) {
+                Some(len) => i += len + 2,
+                None => i += 1,
+            },
             c if c == end => break,
             _ => i += 1,
         }
     }
 
-    lex.bump(i + 1);
+    i
+}
+
+fn find_raw_str_end<'src>(hashes: usize, lex: &mut LogosLexer<'src, Token>) {
+    let rem = lex.remainder();
+    let end = format!("\"{}", "#".repeat(hashes));
+    let i = rem.find(&end).map(|i| i + end.len()).unwrap_or(rem.len());
+    lex.bump(i);
+}
+
+// Length of the `{...}` following a `\u` escape
+pub(crate) fn unicode_escape_len(bytes: &[u8]) -> usize {
+    match bytes.first() {
+        Some(b'{') => bytes
+            .iter()
+            .position(|&c| c == b'}' || c == b'"' || c == b'\n')
+            .map(|i| i + (bytes[i] == b'}') as usize)
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+// Index of the `}` closing an interpolation, or `None` if the `{` doesn't start one
+//
+// Only a `{` directly followed by an expression and closed on the same line starts an
+// interpolation, so that text such as `"{ Point | x = 1 }"` is kept as-is.
+pub(crate) fn interpolation_end(bytes: &[u8]) -> Option<usize> {
+    if matches!(
+        bytes.first(),
+        None | Some(b'}') | Some(b' ' | b'\t' | b'\r' | b'\n')
+    ) {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut i = 0;
+
+    while let Some(&c) = bytes.get(i) {
+        match c {
+            b'"' => i += str_end(b'"', &bytes[i + 1..]) + 2,
+            b'{' => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' if depth == 0 => return Some(i),
+            b'}' => {
+                depth -= 1;
+                i += 1;
+            }
+            b'\n' => return None,
+            _ => i += 1,
+        }
+    }
+
+    None
+}
+
+/// Parse the digits of an unsigned integer literal, which may be prefixed by a radix and use `_`
+/// as a separator.
+///
+/// Returns `None` if the literal doesn't fit in 128 bits.
+pub(crate) fn parse_int(str: &str) -> Option<u128> {
+    let (radix, digits) = match str.get(..2) {
+        Some("0x") => (16, &str[2..]),
+        Some("0o") => (8, &str[2..]),
+        Some("0b") => (2, &str[2..]),
+        _ => (10, str),
+    };
+
+    digits
+        .bytes()
+        .filter(|&c| c != b'_')
+        .try_fold(0u128, |n, c| {
+            let digit = (c as char).to_digit(radix)?;
+            n.checked_mul(radix as u128)?.checked_add(digit as u128)
+        })
+}
+
+pub(crate) fn parse_float(str: &str) -> f64 {
+    str.replace('_', "").parse().unwrap()
+}
+
+/// Decode the escape sequences of a string or char literal
+///
+/// Invalid escape sequences are reported by their byte range and kept as-is.
+pub fn unescape(str: &str, mut on_invalid: impl FnMut(std::ops::Range<usize>)) -> Vec<u8> {
+    let bytes = str.as_bytes();
+    let mut buffer = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while let Some(&c) = bytes.get(i) {
+        if c != b'\\' {
+            buffer.push(c);
+            i += 1;
+            continue;
+        }
+
+        let escaped = match bytes.get(i + 1) {
+            Some(b'n') => Some(b'\n'),
+            Some(b'r') => Some(b'\r'),
+            Some(b't') => Some(b'\t'),
+            Some(b'0') => Some(b'\0'),
+            Some(b @ (b'\\' | b'"' | b'\'' | b'{')) => Some(*b),
+            _ => None,
+        };
+
+        if let Some(b) = escaped {
+            buffer.push(b);
+            i += 2;
+            continue;
+        }
+
+        let len = match bytes.get(i + 1) {
+            // A line continuation skips the newline and the indentation of the next line
+            Some(b'\n') => {
+                let indent = bytes[i + 2..]
+                    .iter()
+                    .take_while(|&&c| c == b' ' || c == b'\t')
+                    .count();
+                Some(2 + indent)
+            }
+            Some(b'x') => str
+                .get(i + 2..i + 4)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .map(|b| {
+                    buffer.push(b);
+                    4
+                }),
+            Some(b'u') => {
+                let len = unicode_escape_len(&bytes[i + 2..]);
+                str.get(i + 3..i + 1 + len)
+                    .filter(|_| len > 2 && bytes[i + 1 + len] == b'}')
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .map(|c| {
+                        let mut utf8 = [0; 4];
+                        buffer.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                        len + 2
+                    })
+            }
+            _ => None,
+        };
+
+        match len {
+            Some(len) => i += len,
+            None => {
+                let len = 1 + str[i + 1..].chars().next().map_or(0, char::len_utf8);
+                on_invalid(i..i + len);
+                buffer.extend_from_slice(&bytes[i..i + len]);
+                i += len;
+            }
+        }
+    }
+
+    buffer
 }
 
 #[derive(PartialEq, Debug, Clone, Copy, Logos)]
@@ -29,9 +197,17 @@ pub enum Token {
     #[token("'", |lex| find_str_end(b'\'', lex))]
     CharLiteral,
 
-    #[regex("\\-?\\d+")]
+    #[token("r\"", |lex| find_raw_str_end(0, lex))]
+    #[token("r#\"", |lex| find_raw_str_end(1, lex))]
+    RawStringLiteral,
+
+    #[regex("\\-?\\d[\\d_]*")]
+    #[regex("\\-?0x[0-9a-fA-F_]+")]
+    #[regex("\\-?0o[0-7_]+")]
+    #[regex("\\-?0b[01_]+")]
     Int,
-    #[regex("\\-?\\d+\\.\\d+")]
+    #[regex("\\-?\\d[\\d_]*\\.\\d[\\d_]*([eE][+-]?\\d[\\d_]*)?")]
+    #[regex("\\-?\\d[\\d_]*[eE][+-]?\\d[\\d_]*")]
     Float,
 
     #[regex("//[^\n]*")]
@@ -179,6 +355,7 @@ impl Token {
             T::Val => "static",
             T::LineDocComment | T::LineComment => "documentation comment",
             T::StringLiteral => "string literal",
+            T::RawStringLiteral => "raw string literal",
             T::CharLiteral => "char literal",
             T::If => "start of if expression",
             T::Bar => "vertical bar",
@@ -401,6 +578,43 @@ mod tests {
         cmp! { r#""hello\\\"" a"# => T::StringLiteral, T::Path, T::EOF };
     }
 
+    #[test]
+    fn str_interpolation() {
+        cmp! { r#""a {x} b" a"# => T::StringLiteral, T::Path, T::EOF };
+        cmp! { r#""a {f "b"} c" a"# => T::StringLiteral, T::Path, T::EOF };
+        cmp! { r#""a {{ x = "}" }} c" a"# => T::StringLiteral, T::Path, T::EOF };
+        cmp! { r#""a \{ b" a"# => T::StringLiteral, T::Path, T::EOF };
+        cmp! { r#""{ a | b = 1 }" a"# => T::StringLiteral, T::Path, T::EOF };
+        cmp! { r#""{}" a"# => T::StringLiteral, T::Path, T::EOF };
+    }
+
+    #[test]
+    fn raw_strings() {
+        cmp! { r#"r"a\" a"# => T::RawStringLiteral, T::Path, T::EOF };
+        cmp! { r##"r#"a "b" {c}"# a"## => T::RawStringLiteral, T::Path, T::EOF };
+        cmp! { "r a" => T::Path, T::Path, T::EOF };
+    }
+
+    #[test]
+    fn numbers() {
+        cmp! { "0xff_FF 0o17 0b1010 1_000" => T::Int, T::Int, T::Int, T::Int, T::EOF };
+        cmp! { "1.5e3 2E-4 1_0.0_1 -3e2" => T::Float, T::Float, T::Float, T::Float, T::EOF };
+        assert_eq!(parse_int("0xff_FF"), Some(0xffff));
+        assert_eq!(parse_int("0b1010"), Some(0b1010));
+        assert_eq!(parse_int("0o17"), Some(0o17));
+        assert_eq!(parse_int("1_000"), Some(1000));
+        assert_eq!(parse_int("340282366920938463463374607431768211456"), None);
+    }
+
+    #[test]
+    fn escapes() {
+        let mut invalid = vec![];
+        let bytes = unescape(r"a\n\x41\u{e9}\{\q", |range| invalid.push(range));
+        assert_eq!(bytes, b"a\nA\xc3\xa9{\\q");
+        assert_eq!(invalid, [15..17]);
+        assert_eq!(unescape("a\\\n    b", |_| {}), b"ab");
+    }
+
     #[test]
     fn regexes() {
         cmp! { "username" => T::Path };
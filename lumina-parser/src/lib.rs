@@ -3,11 +3,11 @@ use lumina_util::{Identifier, Span, Spanned, Tr};
 use std::fmt;
 
 mod lexer;
-pub use lexer::Token;
+pub use lexer::{unescape, Token};
 use lexer::{Lexer, Token as T};
 
 mod expr;
-pub use expr::{Expr, Literal};
+pub use expr::{Expr, Literal, Qualifier};
 pub mod func;
 pub mod r#impl;
 pub mod pat;
@@ -136,7 +136,8 @@ impl<'a> Parser<'a> {
             | Declaration::Impl(r#impl::Declaration { attributes, .. })
             | Declaration::Type(ty::Declaration { attributes, .. })
             | Declaration::Alias(alias::Declaration { attributes, .. })
-            | Declaration::Function(func::Declaration { attributes, .. }) => {
+            | Declaration::Function(func::Declaration { attributes, .. })
+            | Declaration::Val(val::Declaration { attributes, .. }) => {
                 attributes.extend(attribute);
                 decl
             }
@@ -251,8 +252,34 @@ impl<'a> Parser<'a> {
     pub fn taken(&self, span: Span) -> Tr<&'a str> {
         self.take(span).tr(span)
     }
+    // Anything outside of the source, such as around an interpolated expression, is treated as whitespace
     pub fn at(&self, i: u32) -> char {
-        self.lexer.source()[i as usize..].chars().next().unwrap()
+        (i as usize)
+            .checked_sub(self.lexer.span_offset)
+            .and_then(|i| self.lexer.source().get(i..))
+            .and_then(|str| str.chars().next())
+            .unwrap_or(' ')
+    }
+
+    /// Parse the digits of an integer literal, excluding its sign
+    fn int(&mut self, span: Span) -> u128 {
+        match lexer::parse_int(self.take(span)) {
+            Some(n) => n,
+            None => {
+                self.err_int_overflow(span);
+                0
+            }
+        }
+    }
+
+    fn check_escapes(&mut self, span: Span) {
+        let str = self.take(span);
+        let mut invalid = vec![];
+        unescape(str, |range| invalid.push(range));
+        for range in invalid {
+            let at = Span::new(span.indice + range.start as u32, range.len() as u16);
+            self.err_invalid_escape(at);
+        }
     }
 
     pub fn next_then<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
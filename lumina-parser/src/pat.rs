@@ -1,4 +1,6 @@
-use super::{expr::ExprParser, select, CurlyInit, Error, Expr, Fields, ListLength, Parser, Tr, T};
+use super::{
+    expr::ExprParser, lexer, select, CurlyInit, Error, Expr, Fields, ListLength, Parser, Tr, T,
+};
 use itertools::Itertools;
 use lumina_util::{Highlighting, Identifier, Span, Spanned};
 use std::fmt;
@@ -46,6 +48,12 @@ impl<'a> Parser<'a> {
         self.pattern(true)
     }
 
+    // Operator patterns aren't allowed without parenthesis since `<-` would otherwise be parsed
+    // as part of the pattern
+    pub fn generator_pattern(&mut self) -> Option<Tr<Pattern<'a>>> {
+        self.pattern_without_followup(true)
+    }
+
     fn pattern(&mut self, params: bool) -> Option<Tr<Pattern<'a>>> {
         self.pattern_without_followup(params)
             .and_then(|p| self.pat_followup(p))
@@ -124,11 +132,10 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn int_to_bound(&self, span: Span) -> Bound {
-        let raw = self.take(span);
-        match raw.as_bytes()[0] {
-            b'-' => Bound::Neg(raw[1..].parse().unwrap()),
-            _ => Bound::Pos(raw.parse().unwrap()),
+    fn int_to_bound(&mut self, span: Span) -> Bound {
+        match self.take(span).as_bytes()[0] {
+            b'-' => Bound::Neg(self.int(span.move_indice(1))),
+            _ => Bound::Pos(self.int(span)),
         }
     }
 
@@ -145,7 +152,7 @@ impl<'a> Parser<'a> {
     }
 
     fn pat_float(&mut self, span: Span) -> Option<Tr<Pattern<'a>>> {
-        let n = self.take(span).parse().unwrap();
+        let n = lexer::parse_float(self.take(span));
         let p = Pattern::Float(n);
         Some(p.tr(span))
     }
@@ -165,7 +172,9 @@ impl<'a> Parser<'a> {
     }
 
     fn pat_string(&mut self, span: Span, params: bool) -> Option<Tr<Pattern<'a>>> {
-        let str = self.take(span.move_indice(1).extend_length(-1));
+        let inner_span = span.move_indice(1).extend_length(-1);
+        self.check_escapes(inner_span);
+        let str = self.take(inner_span);
 
         let params = if params {
             self.pat_params(true)?
@@ -178,7 +187,9 @@ impl<'a> Parser<'a> {
     }
 
     fn pat_char(&mut self, span: Span, params: bool) -> Option<Tr<Pattern<'a>>> {
-        let str = self.take(span.move_indice(1).extend_length(-1));
+        let inner_span = span.move_indice(1).extend_length(-1);
+        self.check_escapes(inner_span);
+        let str = self.take(inner_span);
 
         let params = if params {
             self.pat_params(true)?
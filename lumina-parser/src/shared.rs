@@ -338,7 +338,7 @@ impl<'a> Parser<'a> {
                 Token::SemiColon => {
                     let length = match self.lexer.next() {
                         (Token::Int, nspan) => {
-                            let num = self.take(nspan).parse::<u64>().unwrap().tr(nspan);
+                            let num = (self.int(nspan) as u64).tr(nspan);
                             ListLength::Exact(num)
                         }
                         (Token::Path, nspan) if !self.take(nspan).contains(":") => {
@@ -127,6 +127,24 @@ expr!(expr_simple_annotated_record, "{ point int | x = 1, y = 2 }");
 expr!(expr_simple_infered_record, "{ x = 1, y = 2 }");
 expr!(expr_nested_tuple, "(((x, y), (x, y), ()))");
 expr!(expr_string_literal, "\"hello\\\" world\"");
+expr!(expr_string_interpolation, "\"a {x + 1} b {f \"c {y}\"}\"");
+expr!(
+    expr_literals,
+    "f 0xff 0b11 1_000 1.5e3 r\"a\\b\" r#\"\"q\"\"#"
+);
+expr!(
+    expr_list_comprehension,
+    "[f x y | x <- xs, p x, let y = x, (a, b) <- ys]"
+);
+expr!(expr_map, "{ \"a\" => 1, f x + 1 => g y, }");
+expr!(
+    expr_map_nested,
+    "{ (k, { 1 => 2 }) => [v], { 3 => 4 } => 5 }"
+);
+expr!(
+    expr_record_of_map,
+    "{ Point | x = { a => b }, y = { p ~ z = 1 } }"
+);
 expr!(expr_simple_constr, "(Just 20)");
 expr!(expr_nested_constr, "(Just (Just (Pair 10 20)))");
 expr!(expr_simple_lambda, "\\x y -> x + y");
@@ -9,6 +9,7 @@ pub struct Declaration<'a> {
     pub type_: Option<Tr<Type<'a>>>,
     pub value: Tr<Expr<'a>>,
     pub public: bool,
+    pub attributes: Vec<Tr<Expr<'a>>>,
 }
 
 impl<'a> Parser<'a> {
@@ -36,6 +37,7 @@ impl<'a> Parser<'a> {
             span: name.span.extend(value.span),
             value,
             public: false,
+            attributes: vec![],
         })
     }
 }
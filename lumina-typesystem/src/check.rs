@@ -1,7 +1,7 @@
 use super::{
     tenv::FieldMismatch, Compatibility as C, ConstGeneric, ConstValue, Constraint, Container,
-    Forall, GetForall, GetImplData, IType, ImplIndex, IntConstraint, IntSize, Static, Transformer,
-    Ty, Type, TypeSystem, Upgrade, Var,
+    Downgrade, Forall, GenericMapper, GetForall, GetImplData, IType, ImplIndex, IntConstraint,
+    IntSize, Static, TEnv, Transformer, Ty, Type, TypeSystem, Upgrade, Var,
 };
 use key::M;
 use lumina_key as key;
@@ -44,7 +44,9 @@ impl<'a, 's> TypeSystem<'a, 's> {
 
     fn unify_(&mut self, u: &mut Unify, got: &IType, exp: &IType) -> bool {
         match (got, exp) {
-            (Ty::Container(gcon, gparams), Ty::Container(econ, eparams)) if gcon == econ => {
+            (Ty::Container(gcon, gparams), Ty::Container(econ, eparams))
+                if gcon == econ && (*gcon != Container::App || gparams.len() == eparams.len()) =>
+            {
                 self.unifys(u, gparams, eparams)
             }
             (Ty::Simple("self"), _) if self.env.self_.is_some() => {
@@ -58,6 +60,12 @@ impl<'a, 's> TypeSystem<'a, 's> {
             (Ty::Generic(g), Ty::Generic(e)) => g == e,
             (Ty::Int(g), Ty::Int(e)) => g == e,
             (Ty::Simple("poison"), _) | (_, Ty::Simple("poison")) => true,
+            (Ty::Container(Container::App, gparams), exp) if !matches!(exp, Ty::Special(_)) => {
+                self.unify_app(u, false, gparams, exp)
+            }
+            (got, Ty::Container(Container::App, eparams)) if !matches!(got, Ty::Special(_)) => {
+                self.unify_app(u, true, eparams, got)
+            }
             (Ty::Simple(g), Ty::Simple(e)) => g == e,
             (Ty::Const(g), Ty::Const(e)) => g == e,
             (Ty::Special(g), Ty::Special(e)) if g == e => true,
@@ -79,6 +87,58 @@ impl<'a, 's> TypeSystem<'a, 's> {
         gots.len() == exps.len() && gots.iter().zip(exps).all(|(g, e)| self.unify_(u, g, e))
     }
 
+    // Unify an applied type constructor by splitting the trailing type parameters off the other
+    // type, so that `f a` unifies with `Maybe int` by inferring `f` as `Maybe`.
+    fn unify_app(&mut self, u: &mut Unify, flip: bool, app: &[IType], ty: &IType) -> bool {
+        let (head, args) = app.split_first().unwrap();
+
+        if let Some(head) = self.known_head(head) {
+            let app = Ty::app(head, args.to_vec());
+            return self.unifyf(u, flip, &app, ty);
+        }
+
+        match ty {
+            Ty::Container(Container::App, params) if params.len() > app.len() => {
+                let k = params.len() - args.len();
+                let ohead = Ty::app(params[0].clone(), params[1..k].to_vec());
+                self.unifyf(u, flip, head, &ohead) && self.unifysf(u, flip, args, &params[k..])
+            }
+            Ty::Container(Container::App, params) => self.unify_app(u, !flip, params, &{
+                Ty::Container(Container::App, app.to_vec())
+            }),
+            Ty::Container(con, params) if params.len() >= args.len() => {
+                let k = params.len() - args.len();
+                let ohead = Ty::constructor(Ty::Container(*con, params[..k].to_vec()));
+                self.unifyf(u, flip, head, &ohead) && self.unifysf(u, flip, args, &params[k..])
+            }
+            _ => false,
+        }
+    }
+
+    // The type constructor of an application if it's already been inferred
+    fn known_head(&mut self, head: &IType) -> Option<IType> {
+        match head {
+            Ty::Special(var) => {
+                if let Some((_, fty)) = self.try_get_field_if_is_field(*var) {
+                    return Some(fty);
+                }
+
+                self.env.vars[*var]
+                    .assignment
+                    .as_ref()
+                    .map(|ty| ty.value.clone())
+            }
+            Ty::Simple("self") => self.env.self_.clone(),
+            _ => None,
+        }
+    }
+
+    fn unifysf(&mut self, u: &mut Unify, flip: bool, gots: &[IType], exps: &[IType]) -> bool {
+        gots.iter()
+            .zip(exps)
+            .all(|(g, e)| self.unifyf(u, flip, g, e))
+    }
+
     fn unify_into_var(&mut self, u: &mut Unify, flip: bool, var: Var, ty: &IType) -> bool {
         // If the var is a field to a record and that record has-been/can-be inferred,
         // then use the known type from that instead of the var.
@@ -124,6 +184,13 @@ impl<'a, 's> TypeSystem<'a, 's> {
                     self.assign_record_to_rvar(u.span, var, record, params.to_vec());
                     return true;
                 }
+                Ty::Container(Container::App, params) => {
+                    let Some(head) = self.known_head(&params[0]) else {
+                        return false;
+                    };
+                    let ty = Ty::app(head, params[1..].to_vec());
+                    return self.unify_into_var(u, flip, var, &ty);
+                }
                 _ => return false,
             }
         }
@@ -406,3 +473,148 @@ impl<'e, 's> TypeSystem<'e, 's> {
         }
     }
 }
+
+// Getter for the associated types implied by a generic's or `self`'s own constraints
+pub(crate) type GetImplied<'a> = &'a dyn Fn(&Type, M<key::Trait>, &[Type]) -> Option<Vec<Type>>;
+
+impl<'e, 's> TypeSystem<'e, 's> {
+    /// Infer the associated types of trait constraints whose implementor is known.
+    ///
+    /// Associated types are represented as trailing trait parameters which can't be written out
+    /// by the user. Once the implementor of such a constraint is known; they're unified with the
+    /// types declared by the matching implementation.
+    ///
+    /// `visible` gives the amount of trait parameters which aren't associated types.
+    ///
+    /// Returns whether any type variable was assigned
+    pub fn resolve_associated_types<'a, 't>(
+        &mut self,
+        index: &ImplIndex,
+        visible: &dyn Fn(M<key::Trait>) -> usize,
+        implied: GetImplied<'a>,
+        get_impl_data: GetImplData<'a, 't, 's>,
+    ) -> bool {
+        let assigned = |env: &TEnv| env.vars.values().filter(|v| v.assignment.is_some()).count();
+        let before = assigned(self.env);
+
+        loop {
+            let previous = assigned(self.env);
+
+            for var in self.env.vars.keys().collect::<Vec<_>>() {
+                for con in self.env.vars[var].trait_constraints.clone() {
+                    let n = visible(con.trait_);
+                    if con.params.len() <= n {
+                        continue;
+                    }
+
+                    let Some(ty) = self.try_known(&Ty::Special(var)) else {
+                        continue;
+                    };
+
+                    let Some(params) = con.params[..n]
+                        .iter()
+                        .map(|p| self.try_known(p))
+                        .collect::<Option<Vec<_>>>()
+                    else {
+                        continue;
+                    };
+
+                    let associated = match &ty {
+                        Ty::Generic(_) | Ty::Simple("self") => implied(&ty, con.trait_, &params),
+                        // Trait objects carry their associated types as trailing parameters
+                        Ty::Container(
+                            Container::Defined(M(module, key::TypeKind::Trait(trait_)), _),
+                            oparams,
+                        ) if trait_.inside(*module) == con.trait_ => Some(oparams[n..].to_vec()),
+                        _ => {
+                            Self::associated_of_impl(index, get_impl_data, &ty, con.trait_, &params)
+                        }
+                    };
+
+                    for (got, exp) in con.params[n..].iter().zip(associated.into_iter().flatten()) {
+                        if self.try_known(got).as_ref() != Some(&exp) {
+                            trace!("resolved associated type {got} of {ty} to {exp}");
+                            self.unify(con.span, got, &Downgrade.transform(&exp));
+                        }
+                    }
+                }
+            }
+
+            if assigned(self.env) == previous {
+                break;
+            }
+        }
+
+        assigned(self.env) != before
+    }
+
+    fn associated_of_impl<'a, 't>(
+        index: &ImplIndex,
+        get_impl_data: GetImplData<'a, 't, 's>,
+        impltor: &Type,
+        trait_: M<key::Trait>,
+        params: &[Type],
+    ) -> Option<Vec<Type>> {
+        let lhs_forall: GetForall = &|_| panic!("generic implementor in associated type lookup");
+
+        index.for_each_relevant(trait_, impltor.try_into().ok(), |ikey| {
+            let (_, iforall, iimpltor, itrtp) = get_impl_data(ikey);
+            let mut comp = C::new(index, lhs_forall, iforall, get_impl_data);
+
+            let n = params.len();
+            (comp.cmp(impltor, iimpltor) && comp.cmps(params, &itrtp[..n])).then_some(())?;
+
+            let mapping = comp.into_assignments();
+            itrtp[n..]
+                .iter()
+                .map(|ty| Self::try_instantiate(&mapping, ty))
+                .collect()
+        })
+    }
+
+    // Instantiate the associated type of an implementation, unless it refers to a generic which
+    // wasn't decided by the implementor.
+    fn try_instantiate(mapping: &GenericMapper<Static>, ty: &Type) -> Option<Type> {
+        match ty {
+            Ty::Generic(generic) => mapping.find(*generic).cloned(),
+            Ty::Container(con, params) => params
+                .iter()
+                .map(|ty| Self::try_instantiate(mapping, ty))
+                .collect::<Option<_>>()
+                .map(|params| Ty::Container(*con, params)),
+            other => Some(other.clone()),
+        }
+    }
+
+    /// Get the type if it's fully known without forcing any inference
+    pub fn try_known(&mut self, ty: &IType) -> Option<Type> {
+        match ty {
+            Ty::Special(var) => {
+                if let Some((_, fty)) = self.try_get_field_if_is_field(*var) {
+                    return self.try_known(&fty);
+                }
+
+                let assigned = self.env.vars[*var].assignment.clone()?;
+                self.try_known(&assigned)
+            }
+            Ty::Simple("self") if self.env.self_.is_some() => {
+                let self_ = self.env.self_.clone().unwrap();
+                self.try_known(&self_)
+            }
+            Ty::Container(Container::App, params) => params
+                .iter()
+                .map(|ty| self.try_known(ty))
+                .collect::<Option<_>>()
+                .map(Ty::reapply),
+            Ty::Container(con, params) => params
+                .iter()
+                .map(|ty| self.try_known(ty))
+                .collect::<Option<_>>()
+                .map(|params| Ty::Container(*con, params)),
+            Ty::Generic(generic) => Some(Ty::Generic(*generic)),
+            Ty::Int(intsize) => Some(Ty::Int(*intsize)),
+            Ty::Const(const_) => Some(Ty::Const(const_.clone())),
+            Ty::Simple(name) => Some(Ty::Simple(name)),
+        }
+    }
+}
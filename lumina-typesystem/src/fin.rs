@@ -26,7 +26,36 @@ impl<'a, 's> Finalizer<'a, 's> {
     pub fn infer_all_unknown_types(&mut self) {
         info!("inferring all {} vars", self.ts.env.vars.len());
 
-        // Infer remaining records and ints
+        self.infer_records_and_ints();
+
+        // Default other vars
+        for (var, vinfo) in self.ts.env.vars.iter_mut() {
+            if vinfo.assignment.is_some() {
+                continue;
+            }
+
+            trace!("defaulting {var} without constraints");
+
+            let ty = if let Some(ty) = vinfo.default.take() {
+                ty
+            } else if vinfo.lift_to_generic {
+                let forall = self.default_to_generic.as_deref_mut().unwrap();
+                Ty::Generic(Generic::new(
+                    forall.implicitly_declare().0,
+                    GenericKind::Entity,
+                ))
+            } else {
+                Ty::tuple(vec![])
+            };
+
+            vinfo.assignment = Some(ty.tr(vinfo.span));
+        }
+    }
+
+    /// Infer remaining records by their fields and default remaining ints
+    ///
+    /// Other vars are left as-is, so that they may still be inferred from associated types.
+    pub fn infer_records_and_ints(&mut self) {
         for var in self.ts.env.vars.keys() {
             // Finalize as a record
             if !self.ts.env.vars[var].fields.is_empty() {
@@ -77,26 +106,5 @@ impl<'a, 's> Finalizer<'a, 's> {
                 vinfo.assignment = Some(ty.tr(vinfo.span));
             }
         }
-
-        // Default other vars
-        for (var, vinfo) in self.ts.env.vars.iter_mut() {
-            if vinfo.assignment.is_some() {
-                continue;
-            }
-
-            trace!("defaulting {var} without constraints");
-
-            let ty = if vinfo.lift_to_generic {
-                let forall = self.default_to_generic.as_deref_mut().unwrap();
-                Ty::Generic(Generic::new(
-                    forall.implicitly_declare().0,
-                    GenericKind::Entity,
-                ))
-            } else {
-                Ty::tuple(vec![])
-            };
-
-            vinfo.assignment = Some(ty.tr(vinfo.span));
-        }
     }
 }
@@ -31,6 +31,8 @@ pub struct GenericData<'s, T> {
     pub name: &'s str,
     pub trait_constraints: Vec<Constraint<T>>,
     pub const_: Option<ConstGeneric>,
+    /// The amount of type parameters the generic is applied to, once known from its usage
+    pub arity: Option<usize>,
 }
 
 /// Mainly exists so we can have proper array types in the stdlib
@@ -43,7 +45,7 @@ pub enum ConstGeneric {
 
 impl<'s, T> GenericData<'s, T> {
     pub fn new(name: &'s str) -> Self {
-        Self { name, const_: None, trait_constraints: vec![] }
+        Self { name, const_: None, trait_constraints: vec![], arity: None }
     }
 
     pub fn map<'o, O>(
@@ -54,6 +56,7 @@ impl<'s, T> GenericData<'s, T> {
         GenericData {
             name: rename(self.name),
             const_: self.const_,
+            arity: self.arity,
             trait_constraints: self
                 .trait_constraints
                 .iter()
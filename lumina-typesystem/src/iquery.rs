@@ -4,21 +4,24 @@ use super::{
 };
 use derive_new::new;
 use lumina_key as key;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tracing::{info, warn};
 
-// TODO: Specialisation via explicit default notation
-
 /// The way we check constraints and select implementations is by making type check against the
 /// implementation's types. However; checking every single implementation to find a valid one would
 /// of course be an absurdly slow operation.
 ///
 /// The ImplIndex drastically reduces the amount of implementations that need to be checked by
 /// hashing the root of the type in both the trait and implementor.
+///
+/// Implementations declared with `default impl` may be overlapped by more specific
+/// implementations, which then take priority when selecting an implementation.
 #[derive(new, Debug)]
 pub struct ImplIndex {
     #[new(default)]
     traits: HashMap<M<key::Trait>, (IBlanked, IConcrete)>,
+    #[new(default)]
+    defaults: HashSet<M<key::Impl>>,
 }
 
 type IBlanked = Vec<M<key::Impl>>;
@@ -52,7 +55,17 @@ impl TryFrom<&Type> for ConcreteType {
 }
 
 impl ImplIndex {
-    pub fn insert(&mut self, trait_: M<key::Trait>, impltor: &Type, ikey: M<key::Impl>) -> () {
+    pub fn insert(
+        &mut self,
+        trait_: M<key::Trait>,
+        impltor: &Type,
+        ikey: M<key::Impl>,
+        default: bool,
+    ) -> () {
+        if default {
+            self.defaults.insert(ikey);
+        }
+
         let (blanked, concrete) = self
             .traits
             .entry(trait_)
@@ -81,21 +94,167 @@ impl ImplIndex {
             .unwrap_or(&[])
     }
 
+    pub fn is_default(&self, ikey: M<key::Impl>) -> bool {
+        self.defaults.contains(&ikey)
+    }
+
     pub fn for_each_relevant<T>(
         &self,
         trait_: M<key::Trait>,
         impltor: Option<ConcreteType>,
-        mut for_each: impl FnMut(M<key::Impl>) -> Option<T>,
+        for_each: impl FnMut(M<key::Impl>) -> Option<T>,
     ) -> Option<T> {
         let (blanked, concrete) = self.traits.get(&trait_)?;
 
-        match impltor {
-            Some(c) => concrete
-                .get(&c)
-                .and_then(|these| these.iter().copied().find_map(&mut for_each)),
-            None => None,
+        let concrete = impltor
+            .and_then(|c| concrete.get(&c))
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+
+        let relevant = concrete.iter().chain(blanked).copied();
+
+        // Specialised implementations take priority over the `default` implementations they overlap
+        relevant
+            .clone()
+            .filter(|ikey| !self.is_default(*ikey))
+            .chain(relevant.filter(|ikey| self.is_default(*ikey)))
+            .find_map(for_each)
+    }
+
+    /// Calls `f` for every pair of implementations of the same trait which might overlap based on
+    /// the root of their implementor.
+    pub fn for_each_overlap_candidate(&self, mut f: impl FnMut(M<key::Impl>, M<key::Impl>)) {
+        for (blanked, concrete) in self.traits.values() {
+            for impls in concrete.values() {
+                for (i, &lhs) in impls.iter().enumerate() {
+                    impls[i + 1..]
+                        .iter()
+                        .chain(blanked)
+                        .for_each(|&rhs| f(lhs, rhs));
+                }
+            }
+
+            for (i, &lhs) in blanked.iter().enumerate() {
+                blanked[i + 1..].iter().for_each(|&rhs| f(lhs, rhs));
+            }
+        }
+    }
+}
+
+/// Unification of the headers of two implementations, where a header is the implementor followed
+/// by the trait parameters.
+///
+/// Generics of the right-hand side are moved to `GenericKind::Entity` so that they don't collide
+/// with the generics of the left-hand side.
+pub struct Overlap {
+    mapping: HashMap<Generic, Type>,
+    rigid_lhs: bool,
+}
+
+impl Overlap {
+    /// Whether there's any type which would match both headers
+    pub fn check(lhs: &[Type], rhs: &[Type]) -> bool {
+        Overlap::unified(lhs, rhs).is_some()
+    }
+
+    /// Unify the headers, returning the mapping under which both would match the same type
+    pub fn unified(lhs: &[Type], rhs: &[Type]) -> Option<Self> {
+        let mut this = Overlap { mapping: HashMap::new(), rigid_lhs: false };
+        this.unifies(lhs, rhs).then_some(this)
+    }
+
+    /// Whether every type matching `specific` also matches `general`
+    pub fn is_instance_of(specific: &[Type], general: &[Type]) -> bool {
+        Overlap { mapping: HashMap::new(), rigid_lhs: true }.unifies(specific, general)
+    }
+
+    /// Apply the unified mapping to a type from the left-hand side
+    pub fn lhs(&self, ty: &Type) -> Type {
+        self.apply(ty)
+    }
+
+    /// Apply the unified mapping to a type from the right-hand side
+    pub fn rhs(&self, ty: &Type) -> Type {
+        self.apply(&Self::rename(ty))
+    }
+
+    fn apply(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Ty::Container(con, params) => {
+                Ty::Container(con, params.iter().map(|ty| self.apply(ty)).collect())
+            }
+            ty => ty,
+        }
+    }
+
+    fn unifies(&mut self, lhs: &[Type], rhs: &[Type]) -> bool {
+        lhs.len() == rhs.len()
+            && lhs
+                .iter()
+                .zip(rhs)
+                .all(|(l, r)| self.unify(l, &Self::rename(r)))
+    }
+
+    fn rename(ty: &Type) -> Type {
+        match ty {
+            Ty::Generic(generic) => Ty::Generic(Generic::new(generic.key, GenericKind::Entity)),
+            Ty::Container(con, params) => {
+                Ty::Container(*con, params.iter().map(Self::rename).collect())
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn bindable(&self, generic: Generic) -> bool {
+        !self.rigid_lhs || generic.kind == GenericKind::Entity
+    }
+
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Ty::Generic(generic) => match self.mapping.get(generic) {
+                Some(ty) => self.resolve(ty),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    fn unify(&mut self, lhs: &Type, rhs: &Type) -> bool {
+        match (self.resolve(lhs), self.resolve(rhs)) {
+            (Ty::Generic(l), Ty::Generic(r)) if l == r => true,
+            (lhs, Ty::Generic(generic)) if self.bindable(generic) => self.bind(generic, &lhs),
+            (Ty::Generic(generic), rhs) if self.bindable(generic) => self.bind(generic, &rhs),
+            (Ty::Container(lcon, lparams), Ty::Container(rcon, rparams)) => {
+                lcon == rcon
+                    && lparams.len() == rparams.len()
+                    && lparams.iter().zip(&rparams).all(|(l, r)| self.unify(l, r))
+            }
+            (Ty::Int(l), Ty::Int(r)) => l == r,
+            (Ty::Simple(l), Ty::Simple(r)) => l == r,
+            (Ty::Const(l), Ty::Const(r)) => l == r,
+            _ => false,
+        }
+    }
+
+    fn bind(&mut self, generic: Generic, ty: &Type) -> bool {
+        if self.occurs(generic, ty) {
+            return false;
+        }
+
+        self.mapping.insert(generic, ty.clone());
+        true
+    }
+
+    fn occurs(&self, generic: Generic, ty: &Type) -> bool {
+        match ty {
+            Ty::Generic(g) if *g == generic => true,
+            Ty::Generic(g) => self
+                .mapping
+                .get(g)
+                .is_some_and(|ty| self.occurs(generic, ty)),
+            Ty::Container(_, params) => params.iter().any(|ty| self.occurs(generic, ty)),
+            _ => false,
         }
-        .or_else(|| blanked.iter().copied().find_map(for_each))
     }
 }
 
@@ -136,7 +295,7 @@ impl<'a, 't, 's> Compatibility<'a, 't, 's> {
         self.mapping
     }
 
-    pub fn check_all(self) -> bool {
+    pub fn check_all(&self) -> bool {
         self.mapping
             .assignments_by_kind(GenericKind::Parent)
             .all(|(key, ty)| {
@@ -204,6 +363,13 @@ impl<'a, 't, 's> Compatibility<'a, 't, 's> {
                     return true;
                 }
             }
+            // Trait objects implement the trait they're an object of by dynamic dispatch
+            Type::Container(
+                Container::Defined(M(module, key::TypeKind::Trait(trait_)), _),
+                params,
+            ) if trait_.inside(*module) == con.trait_ && *params == con.params => {
+                return true;
+            }
             _ => {}
         }
 
@@ -239,6 +405,13 @@ impl<'a, 't, 's> Compatibility<'a, 't, 's> {
                 Ty::Container(Container::Defined(ekey, _), eparams),
             ) if gkey == ekey => self.cmps(gparams, eparams),
             (Ty::Special(_), _) | (_, Ty::Special(_)) => unreachable!(),
+            (Ty::Container(g, gparams), Ty::Container(Container::App, eparams))
+                if *g != Container::App && gparams.len() >= eparams.len() - 1 =>
+            {
+                let k = gparams.len() - (eparams.len() - 1);
+                let ghead = Ty::constructor(Ty::Container(*g, gparams[..k].to_vec()));
+                self.cmp(&ghead, &eparams[0]) && self.cmps(&gparams[k..], &eparams[1..])
+            }
             (_, Type::Generic(generic)) => self.map(got, *generic),
             _ => false,
         }
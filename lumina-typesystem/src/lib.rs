@@ -31,7 +31,7 @@ mod tenv;
 pub use tenv::{IntConstraint, TEnv, Var};
 
 mod iquery;
-pub use iquery::{Compatibility, ConcreteType, ImplIndex};
+pub use iquery::{Compatibility, ConcreteType, ImplIndex, Overlap};
 pub(crate) use iquery::{GetForall, GetImplData};
 
 mod check;
@@ -234,6 +234,10 @@ pub enum Container {
     Pointer,
     Array,
     Defined(M<key::TypeKind>, Ignored<Lang>),
+    /// A type constructor applied to type parameters, where the first parameter is the constructor.
+    ///
+    /// Without any other parameters this represents the unapplied type constructor itself.
+    App,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -287,6 +291,45 @@ impl<T> Ty<T> {
         Self::Container(Container::Closure, params)
     }
 
+    /// Apply type parameters to a type constructor
+    ///
+    /// Applications whose constructor is known are normalised into the type they construct.
+    pub fn app(head: Self, mut args: Vec<Self>) -> Self {
+        if args.is_empty() {
+            return head;
+        }
+
+        match head {
+            Ty::Container(Container::App, mut params) => {
+                let head = params.remove(0);
+                params.append(&mut args);
+                Ty::app(head, params)
+            }
+            Ty::Container(con, mut params) => {
+                params.append(&mut args);
+                Ty::Container(con, params)
+            }
+            head => {
+                args.insert(0, head);
+                Ty::Container(Container::App, args)
+            }
+        }
+    }
+
+    /// Rebuild an application from the parameters of a [`Container::App`]
+    pub fn reapply(mut params: Vec<Self>) -> Self {
+        match params.remove(0) {
+            head @ Ty::Container(Container::App, _) if params.is_empty() => head,
+            head if params.is_empty() => Ty::constructor(head),
+            head => Ty::app(head, params),
+        }
+    }
+
+    /// The unapplied type constructor of a type missing its trailing type parameters
+    pub fn constructor(ty: Self) -> Self {
+        Self::Container(Container::App, vec![ty])
+    }
+
     pub fn pointer(ty: Self) -> Self {
         let params = vec![ty];
         Self::Container(Container::Pointer, params)
@@ -364,6 +407,7 @@ impl Container {
             Container::Defined(key, Ignored { inner: Lang::None }) => {
                 Self::fmt_defined(key, elems, format, f, true)
             }
+            Container::App => Self::fmt_defined(format(&elems[0]), &elems[1..], format, f, true),
         }
     }
 
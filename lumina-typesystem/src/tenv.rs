@@ -26,6 +26,8 @@ pub struct VarInfo<'s> {
     pub(crate) field_of: Option<(Tr<&'s str>, Var)>,
 
     pub(crate) lift_to_generic: bool,
+    /// Type to assign if nothing else is inferred, such as the default map for map literals
+    pub(crate) default: Option<IType>,
 }
 
 #[derive(Clone, Copy)]
@@ -49,6 +51,7 @@ impl<'s> VarInfo<'s> {
             field_of: None,
 
             lift_to_generic: false,
+            default: None,
         }
     }
 }
@@ -131,6 +134,10 @@ impl<'s> TEnv<'s> {
         self.vars[var].lift_to_generic = true;
     }
 
+    pub fn set_default(&mut self, var: Var, ty: IType) {
+        self.vars[var].default = Some(ty);
+    }
+
     pub fn merge_vars<const N: usize>(&mut self, span: Span, vars: [Var; N]) -> Var {
         let mut int_constraint = None;
         let mut trait_constraints = vec![];
@@ -138,6 +145,7 @@ impl<'s> TEnv<'s> {
         let mut fields = vec![];
         let mut field_of = None;
         let mut lift_to_generic = false;
+        let mut default = None;
 
         let nvar = self.next_key();
         trace!("merging {vars:?} -> {nvar}");
@@ -148,6 +156,10 @@ impl<'s> TEnv<'s> {
 
             lift_to_generic |= vinfo.lift_to_generic;
 
+            if default.is_none() {
+                default = vinfo.default.take();
+            }
+
             if field_of.is_none() {
                 if let Some(fvar) = vinfo.field_of {
                     field_of = Some(fvar);
@@ -186,6 +198,7 @@ impl<'s> TEnv<'s> {
                 lift_to_generic,
                 fields,
                 field_of: None,
+                default,
             },
         );
 
@@ -134,3 +134,73 @@ fn replicated_issue() {
     assert_eq!(u64(), Upgrade(&env).special(&len));
     assert_eq!(Ty::pointer(u8()), Upgrade(&env).special(&ptr));
 }
+
+#[test]
+fn applied_type_vars() {
+    lumina_util::test_logger();
+
+    let mut env = TEnv::new();
+    let mut ts = system(&mut env);
+
+    let f = ts.env.var(span);
+    let a = ts.env.var(span);
+
+    // f a ∈ Vec u8
+    let got = Ty::app(Ty::infer(f), vec![Ty::infer(a)]);
+    assert!(ts.unify(span, &got, &Ty::defined(VEC, vec![u8()])));
+
+    // f u64 ∈ Vec u64
+    let again = Ty::app(Ty::infer(f), vec![u64()]);
+    assert!(ts.unify(span, &again, &Ty::defined(VEC, vec![u64()])));
+    assert!(!ts.unify(span, &again, &Ty::tuple(vec![u64()])));
+
+    let got = Upgrade(&env).transform(&got);
+    let constructor = Upgrade(&env).special(&f);
+
+    assert_eq!(got, Ty::defined(VEC, vec![u8()]));
+    assert_eq!(constructor, Ty::constructor(Ty::defined(VEC, vec![])));
+}
+
+fn parent<T>(n: u32) -> Ty<T> {
+    Ty::Generic(super::Generic::new(
+        key::Generic(n),
+        super::GenericKind::Parent,
+    ))
+}
+
+#[test]
+fn overlapping_impl_headers() {
+    use super::Overlap;
+
+    let vec = |param| Ty::defined(VEC, vec![param]);
+
+    assert!(Overlap::check(&[vec(parent(0))], &[vec(u8())]));
+    assert!(Overlap::check(&[parent(0)], &[vec(parent(0))]));
+    assert!(Overlap::check(
+        &[Ty::tuple(vec![parent(0), u8()])],
+        &[Ty::tuple(vec![i64(), parent(0)])]
+    ));
+
+    assert!(!Overlap::check(&[vec(u8())], &[vec(i64())]));
+    assert!(!Overlap::check(
+        &[Ty::tuple(vec![parent(0), parent(0)])],
+        &[Ty::tuple(vec![u8(), i64()])]
+    ));
+}
+
+#[test]
+fn specialised_impl_headers() {
+    use super::Overlap;
+
+    let vec = |param| Ty::defined(VEC, vec![param]);
+
+    assert!(Overlap::is_instance_of(&[vec(u8())], &[vec(parent(0))]));
+    assert!(Overlap::is_instance_of(&[vec(parent(0))], &[parent(0)]));
+    assert!(Overlap::is_instance_of(
+        &[vec(parent(0))],
+        &[vec(parent(0))]
+    ));
+
+    assert!(!Overlap::is_instance_of(&[vec(parent(0))], &[vec(u8())]));
+    assert!(!Overlap::is_instance_of(&[parent(0)], &[vec(parent(0))]));
+}
@@ -1,5 +1,6 @@
 use super::{
-    Constraint, Forall, Generic, GenericKind, IType, Inference, Span, Static, TEnv, Ty, Var,
+    Constraint, Container, Forall, Generic, GenericKind, IType, Inference, Span, Static, TEnv, Ty,
+    Var,
 };
 use derive_new::new;
 use itertools::Itertools;
@@ -218,6 +219,7 @@ pub trait Transformer<T> {
 
     fn transform(&mut self, ty: &Ty<T>) -> Ty<Self::Output> {
         match ty {
+            Ty::Container(Container::App, params) => Ty::reapply(self.transforms(params)),
             Ty::Container(container, params) => Ty::Container(*container, self.transforms(params)),
             Ty::Int(size) => Ty::Int(*size),
             Ty::Const(const_) => Ty::Const(const_.clone()),
@@ -68,9 +68,21 @@ pub fn build_project(
         return Err(ExitCode::FAILURE);
     }
 
-    let lir = compiler::lir::run(pinfo, target, &iquery, mir);
+    let mut profile = pconfig.profile.clone();
+    if let Some(level) = settings.opt_level {
+        profile.opt_level = level;
+    }
+    if let Some(threshold) = settings.inline_threshold {
+        profile.inline_threshold = Some(threshold);
+    }
+
+    let mut opt_settings = compiler::lir::OptSettings::new(&profile);
+    opt_settings.fuel = settings.lir_opt_fuel;
+    opt_settings.print_passes = settings.print_passes;
 
-    let object = compiler::backend::cranelift::run(target, dinfo, lir);
+    let lir = compiler::lir::run(pinfo, target, &iquery, mir, opt_settings);
+
+    let object = compiler::backend::cranelift::run(target, profile.opt_level, dinfo, lir);
 
     let output = match settings.output.as_deref() {
         Some(name) => {
@@ -175,22 +187,40 @@ pub fn project_info<'s>(
     let sys_init = function(&["std", "prelude", "_lumina_sys_init"])?;
     let alloc = function(&["std", "prelude", "alloc"])?;
     let dealloc = function(&["std", "prelude", "dealloc"])?;
+    let string_concat = function(&["std", "prelude", "<++>"])?;
 
     let closure = trait_(["std", "prelude", "Closure"].as_slice())?;
     let listable = trait_(&["std", "prelude", "Listable"])?;
+    let mappable = trait_(&["std", "prelude", "Mappable"])?;
     let stringable = trait_(&["std", "prelude", "Stringable"])?;
-    let reflect_type = trait_(&["std", "prelude", "Type"])?;
+    let to_string = trait_(&["std", "prelude", "ToString"])?;
 
     let maybe = resolve_or_error(from, lookups, &["std", "prelude", "Maybe"], |k| match k {
         ast::Entity::Type(key::TypeKind::Sum(key)) => Some(key),
         _ => None,
     })?;
 
+    let reflect_type = resolve_or_error(
+        from,
+        lookups,
+        &["std", "prelude", "TypeInfo"],
+        |k| match k {
+            ast::Entity::Type(key::TypeKind::Sum(key)) => Some(key),
+            _ => None,
+        },
+    )?;
+
     let list_default = resolve_or_error(from, lookups, &["std", "prelude", "List"], |k| match k {
         ast::Entity::Type(kind) => Some(kind),
         _ => None,
     })?;
 
+    let map_default =
+        resolve_or_error(from, lookups, &["std", "prelude", "HashMap"], |k| match k {
+            ast::Entity::Type(kind) => Some(kind),
+            _ => None,
+        })?;
+
     let string = resolve_or_error(from, lookups, &["std", "prelude", "string"], |k| match k {
         ast::Entity::Type(key::TypeKind::Record(key)) => Some(key),
         _ => None,
@@ -204,7 +234,11 @@ pub fn project_info<'s>(
         reflect_type,
         listable,
         list_default,
+        mappable,
+        map_default,
         stringable,
+        to_string,
+        string_concat,
         string,
         maybe,
     ))
@@ -245,6 +279,7 @@ pub fn project_error(err: compiler::ast::Error) -> lumina_util::Error {
                 ConfigError::InvalidTypeInStr(span) => {
                     main(span, "invalid type in string literal".into())
                 }
+                ConfigError::InvalidProfile(span, err) => main(span, err.into()),
             }
         }
         ast::Error::SrcDir(ioerr) => {
@@ -1,5 +1,6 @@
 use clap::{command, Args, Parser, Subcommand};
 use directories::BaseDirs;
+use lumina_compiler::OptLevel;
 use std::fs;
 use std::path::PathBuf as FilePathBuf;
 
@@ -83,6 +84,24 @@ pub struct BuildFlags {
     #[arg(short = 'o', long)]
     pub output: Option<String>,
 
+    /// Optimisation level, one of 0, 1, 2 or s
+    ///
+    /// Overrides the level of the profile in the project config
+    #[arg(short = 'O')]
+    pub opt_level: Option<OptLevel>,
+
+    /// Largest size of a function for it to be inlined at every call site
+    #[arg(long)]
+    pub inline_threshold: Option<u32>,
+
+    /// Stop optimising LIR after this many changes, for bisecting miscompilations
+    #[arg(long)]
+    pub lir_opt_fuel: Option<usize>,
+
+    /// Print every LIR optimisation pass and the functions it changed
+    #[arg(long)]
+    pub print_passes: bool,
+
     /// Path to lumina project, defaults to current directory
     pub project: Option<FilePathBuf>,
 }
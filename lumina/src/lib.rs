@@ -21,6 +21,10 @@ pub fn run(path: &str) -> std::process::Output {
         epanic: true,
         output: None,
         super_debug: false,
+        opt_level: None,
+        inline_threshold: None,
+        lir_opt_fuel: None,
+        print_passes: false,
         project: Some(environment.current_directory.clone()),
     };
 
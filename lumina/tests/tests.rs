@@ -56,3 +56,150 @@ fn tests_mem_nested_combination() {
 fn tests_mem_sum_in_struct() {
     run("tests/mem-sum-in-struct");
 }
+
+#[test]
+fn tests_mem_small_array() {
+    run("tests/mem-small-array");
+}
+
+#[test]
+fn tests_mem_large_array() {
+    run("tests/mem-large-array");
+}
+
+#[test]
+fn tests_mem_array_in_struct() {
+    run("tests/mem-array-in-struct");
+}
+
+#[test]
+fn tests_mem_array_in_sum() {
+    run("tests/mem-array-in-sum");
+}
+
+#[test]
+fn tests_mem_sum_niche() {
+    run("tests/mem-sum-niche");
+}
+
+#[test]
+fn tests_mem_sum_null_pointer() {
+    run("tests/mem-sum-null-pointer");
+}
+
+#[test]
+fn tests_mem_sum_multi_payload() {
+    run("tests/mem-sum-multi-payload");
+}
+
+#[test]
+fn tests_trait_associated_types() {
+    run("tests/trait-associated-types");
+}
+
+#[test]
+fn tests_iter_traits() {
+    run("tests/iter-traits");
+}
+
+#[test]
+fn tests_trait_default_methods() {
+    run("tests/trait-default-methods");
+}
+
+#[test]
+fn tests_trait_specialisation() {
+    run("tests/trait-specialisation");
+}
+
+#[test]
+fn tests_trait_coherence() {
+    run("tests/trait-coherence");
+}
+
+#[test]
+fn tests_higher_kinded_types() {
+    run("tests/higher-kinded-types");
+}
+
+#[test]
+fn tests_reflection() {
+    run("tests/reflection");
+}
+
+#[test]
+fn tests_derive() {
+    run("tests/derive");
+}
+
+#[test]
+fn tests_comptime_vals() {
+    run("tests/comptime-vals");
+}
+
+#[test]
+fn tests_threads() {
+    run("tests/threads");
+}
+
+#[test]
+fn tests_atomics() {
+    run("tests/atomics");
+}
+
+#[test]
+fn tests_string_interpolation() {
+    run("tests/string-interpolation");
+}
+
+#[test]
+fn tests_literals() {
+    run("tests/literals");
+}
+
+#[test]
+fn tests_list_comprehension() {
+    run("tests/list-comprehension");
+}
+
+#[test]
+fn tests_map_literals() {
+    run("tests/map-literals");
+}
+
+#[test]
+fn tests_repr_packed_align() {
+    run("tests/repr-packed-align");
+}
+
+#[test]
+fn tests_arrays() {
+    run("tests/arrays");
+}
+
+#[test]
+fn tests_tail_recursion() {
+    run("tests/tail-recursion");
+}
+
+#[test]
+fn tests_inline_attributes() {
+    run("tests/inline-attributes");
+}
+
+// Observes heap usage through glibc's `mallinfo2`
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn tests_escape_analysis() {
+    run("tests/escape-analysis");
+}
+
+#[test]
+fn tests_closure_specialisation() {
+    run("tests/closure-specialisation");
+}
+
+#[test]
+fn tests_opt_profile() {
+    run("tests/opt-profile");
+}